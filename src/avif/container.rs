@@ -0,0 +1,470 @@
+//! Provides parsing of the AVIF (ISOBMFF) container, to extract the AV1 payloads of an image for decoding.
+
+use anyhow::{bail, Result};
+
+/// The auxiliary type of an alpha plane item.
+const ALPHA_AUX_TYPE: &[u8] = b"urn:mpeg:mpegB:cicp:systems:auxiliary:alpha";
+
+/// The matrix coefficients of BT.601, this is assumed if the image does not have a `colr` box.
+pub const MATRIX_BT601: u16 = 6;
+
+/// The contents of an AVIF image which are needed for decoding it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AvifImage {
+    /// The width of the image in pixels.
+    pub width: u32,
+    /// The height of the image in pixels.
+    pub height: u32,
+    /// The AV1 payload (OBUs) of the color image.
+    pub color_data: Vec<u8>,
+    /// The AV1 payload (OBUs) of the alpha plane, `None` if the image is opaque.
+    pub alpha_data: Option<Vec<u8>>,
+    /// Whether the color channels are premultiplied with the alpha channel.
+    pub premultiplied_alpha: bool,
+    /// The matrix coefficients (as defined by ITU-T H.273) of the color image.
+    pub matrix_coefficients: u16,
+    /// Whether the color image uses the full range of the samples (studio swing otherwise).
+    pub full_range: bool,
+}
+
+/// The location of an item inside the file.
+#[derive(Debug, Default)]
+struct ItemLocation {
+    //0 means file offsets, 1 means offsets inside the `idat` box
+    construction_method: u8,
+    extents: Vec<(u64, u64)>,
+}
+
+/// An item and the 1-based index of one of its properties.
+type PropertyAssociation = (u32, u16);
+
+/// A box header and its body.
+#[derive(Debug)]
+struct IsoBox<'a> {
+    box_type: [u8; 4],
+    body: &'a [u8],
+}
+
+///
+/// Parses an AVIF file.
+///
+/// # Behavior
+/// Returns the primary item's AV1 payload, and the AV1 payload of its alpha plane if it has one.
+/// The color description defaults to full range BT.601 if the file does not contain it.
+///
+/// # Error
+/// Returns an error if the file is not a valid AVIF still image (Eg.: it is truncated or it is an image sequence).
+///
+pub fn parse_avif(file: &[u8]) -> Result<AvifImage> {
+    let boxes = parse_boxes(file)?;
+
+    let Some(ftyp) = find_box(&boxes, b"ftyp") else {
+        bail!("The file does not have an ftyp box.");
+    };
+
+    if !ftyp
+        .body
+        .chunks_exact(4)
+        .enumerate()
+        //Skip the minor version
+        .filter(|(idx, _)| *idx != 1)
+        .any(|(_, brand)| brand == b"avif")
+    {
+        bail!("The file is not an AVIF image.");
+    }
+
+    let Some(meta) = find_box(&boxes, b"meta") else {
+        bail!("The file does not have a meta box.");
+    };
+    let meta_boxes = parse_boxes(full_box_body(meta.body)?.1)?;
+
+    let Some(pitm) = find_box(&meta_boxes, b"pitm") else {
+        bail!("The file does not have a primary item.");
+    };
+    let (version, pitm_body) = full_box_body(pitm.body)?;
+    let primary_item = read_item_id(&mut Reader::new(pitm_body), version)?;
+
+    let Some(iloc) = find_box(&meta_boxes, b"iloc") else {
+        bail!("The file does not have an iloc box.");
+    };
+    let locations = parse_iloc(iloc.body)?;
+
+    let item_types = match find_box(&meta_boxes, b"iinf") {
+        Some(iinf) => parse_iinf(iinf.body)?,
+        None => vec![],
+    };
+
+    if item_types
+        .iter()
+        .any(|(item, item_type)| *item == primary_item && item_type != b"av01")
+    {
+        bail!("The primary item is not an AV1 image.");
+    }
+
+    //Properties of every item
+    let (properties, associations) = match find_box(&meta_boxes, b"iprp") {
+        Some(iprp) => parse_iprp(iprp.body)?,
+        None => (vec![], vec![]),
+    };
+    let item_properties = |item: u32| {
+        associations
+            .iter()
+            .filter(move |(associated_item, _)| *associated_item == item)
+            .filter_map(|(_, index)| properties.get(index.checked_sub(1)? as usize))
+    };
+
+    //References between the items
+    let references = match find_box(&meta_boxes, b"iref") {
+        Some(iref) => parse_iref(iref.body)?,
+        None => vec![],
+    };
+
+    let alpha_item = references
+        .iter()
+        .filter(|(reference_type, _, to_item)| {
+            reference_type == b"auxl" && *to_item == primary_item
+        })
+        .map(|(_, from_item, _)| *from_item)
+        .find(|item| {
+            item_properties(*item).any(|property| {
+                property.box_type == *b"auxC"
+                    && full_box_body(property.body)
+                        .map(|(_, body)| body.starts_with(ALPHA_AUX_TYPE))
+                        .unwrap_or(false)
+            })
+        });
+
+    let premultiplied_alpha = alpha_item.is_some_and(|alpha_item| {
+        references
+            .iter()
+            .any(|(reference_type, from_item, to_item)| {
+                reference_type == b"prem" && *from_item == primary_item && *to_item == alpha_item
+            })
+    });
+
+    let Some((width, height)) = item_properties(primary_item).find_map(|property| {
+        if property.box_type != *b"ispe" {
+            return None;
+        }
+
+        let mut reader = Reader::new(full_box_body(property.body).ok()?.1);
+
+        Some((reader.u32().ok()?, reader.u32().ok()?))
+    }) else {
+        bail!("The primary item does not have an ispe property.");
+    };
+
+    let (matrix_coefficients, full_range) = item_properties(primary_item)
+        .find_map(|property| {
+            if property.box_type != *b"colr" || !property.body.starts_with(b"nclx") {
+                return None;
+            }
+
+            let mut reader = Reader::new(&property.body[4..]);
+
+            //Color primaries and transfer characteristics
+            reader.skip(4).ok()?;
+
+            Some((reader.u16().ok()?, reader.u8().ok()? & 0x80 != 0))
+        })
+        .unwrap_or((MATRIX_BT601, true));
+
+    let idat = find_box(&meta_boxes, b"idat").map(|idat| idat.body);
+
+    let item_data = |item: u32| -> Result<Vec<u8>> {
+        let Some(location) = locations.iter().find(|(id, _)| *id == item) else {
+            bail!("The item {item} does not have a location.");
+        };
+
+        let source = match (location.1.construction_method, idat) {
+            (0, _) => file,
+            (1, Some(idat)) => idat,
+            (construction_method, _) => {
+                bail!("Unsupported item construction method: {construction_method}.")
+            }
+        };
+
+        let mut data = vec![];
+
+        for (offset, length) in &location.1.extents {
+            let start = *offset as usize;
+            //A length of 0 means the rest of the source
+            let end = if *length == 0 {
+                source.len()
+            } else {
+                start.saturating_add(*length as usize)
+            };
+
+            let Some(extent) = source.get(start..end) else {
+                bail!("The extent of item {item} is out of bounds.");
+            };
+
+            data.extend_from_slice(extent);
+        }
+
+        Ok(data)
+    };
+
+    Ok(AvifImage {
+        width,
+        height,
+        color_data: item_data(primary_item)?,
+        alpha_data: alpha_item.map(item_data).transpose()?,
+        premultiplied_alpha,
+        matrix_coefficients,
+        full_range,
+    })
+}
+
+/// A big endian reader over a byte slice.
+#[derive(Debug)]
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    fn take(&mut self, count: usize) -> Result<&'a [u8]> {
+        if self.bytes.len() < count {
+            bail!("Unexpected end of an AVIF box.");
+        }
+
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+
+        Ok(taken)
+    }
+
+    fn skip(&mut self, count: usize) -> Result<()> {
+        self.take(count).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into()?))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into()?))
+    }
+
+    /// Reads an unsigned integer of 0, 4 or 8 bytes.
+    fn sized(&mut self, size: u8) -> Result<u64> {
+        match size {
+            0 => Ok(0),
+            4 => Ok(self.u32()? as u64),
+            8 => Ok(u64::from_be_bytes(self.take(8)?.try_into()?)),
+            size => bail!("Invalid field size: {size}."),
+        }
+    }
+}
+
+/// Splits a byte slice into boxes.
+fn parse_boxes(mut bytes: &[u8]) -> Result<Vec<IsoBox<'_>>> {
+    let mut boxes = vec![];
+
+    while !bytes.is_empty() {
+        let mut reader = Reader::new(bytes);
+        let size = reader.u32()? as u64;
+        let box_type: [u8; 4] = reader.take(4)?.try_into()?;
+
+        let (header_size, size) = match size {
+            //The box extends to the end
+            0 => (8, bytes.len() as u64),
+            1 => (16, reader.sized(8)?),
+            size => (8, size),
+        };
+
+        if size < header_size || size > bytes.len() as u64 {
+            bail!("Invalid box size: {size}.");
+        }
+
+        boxes.push(IsoBox {
+            box_type,
+            body: &bytes[header_size as usize..size as usize],
+        });
+
+        bytes = &bytes[size as usize..];
+    }
+
+    Ok(boxes)
+}
+
+/// Returns the first box with the given type.
+fn find_box<'a, 'b>(boxes: &'b [IsoBox<'a>], box_type: &[u8; 4]) -> Option<&'b IsoBox<'a>> {
+    boxes.iter().find(|iso_box| iso_box.box_type == *box_type)
+}
+
+/// Returns the version and the body of a full box (the flags are dropped).
+fn full_box_body(body: &[u8]) -> Result<(u8, &[u8])> {
+    if body.len() < 4 {
+        bail!("Unexpected end of an AVIF box.");
+    }
+
+    Ok((body[0], &body[4..]))
+}
+
+/// Reads an item id, which is 16 bit in version 0 boxes and 32 bit otherwise.
+fn read_item_id(reader: &mut Reader<'_>, version: u8) -> Result<u32> {
+    if version == 0 {
+        Ok(reader.u16()? as u32)
+    } else {
+        reader.u32()
+    }
+}
+
+/// Parses the item locations of an `iloc` box.
+fn parse_iloc(body: &[u8]) -> Result<Vec<(u32, ItemLocation)>> {
+    let (version, body) = full_box_body(body)?;
+    let mut reader = Reader::new(body);
+
+    let sizes = reader.u8()?;
+    let (offset_size, length_size) = (sizes >> 4, sizes & 0x0F);
+    let sizes = reader.u8()?;
+    let base_offset_size = sizes >> 4;
+    let index_size = if version > 0 { sizes & 0x0F } else { 0 };
+
+    let item_count = if version < 2 {
+        reader.u16()? as u32
+    } else {
+        reader.u32()?
+    };
+
+    let mut locations = vec![];
+
+    for _ in 0..item_count {
+        let item = read_item_id(&mut reader, (version == 2) as u8)?;
+
+        let construction_method = if version > 0 {
+            (reader.u16()? & 0x0F) as u8
+        } else {
+            0
+        };
+
+        //Data reference index
+        reader.skip(2)?;
+
+        let base_offset = reader.sized(base_offset_size)?;
+        let extent_count = reader.u16()?;
+        let mut extents = vec![];
+
+        for _ in 0..extent_count {
+            reader.sized(index_size)?;
+
+            let offset = reader.sized(offset_size)?;
+            let length = reader.sized(length_size)?;
+
+            let Some(offset) = base_offset.checked_add(offset) else {
+                bail!("The offset of an extent of item {item} overflows.");
+            };
+
+            extents.push((offset, length));
+        }
+
+        locations.push((
+            item,
+            ItemLocation {
+                construction_method,
+                extents,
+            },
+        ));
+    }
+
+    Ok(locations)
+}
+
+/// Parses the item types of an `iinf` box.
+fn parse_iinf(body: &[u8]) -> Result<Vec<(u32, [u8; 4])>> {
+    let (version, body) = full_box_body(body)?;
+    let mut reader = Reader::new(body);
+
+    //Entry count
+    if version == 0 {
+        reader.skip(2)?;
+    } else {
+        reader.skip(4)?;
+    }
+
+    let mut item_types = vec![];
+
+    for infe in parse_boxes(reader.bytes)? {
+        let (version, body) = full_box_body(infe.body)?;
+
+        //Only version 2 and 3 item infos have item types
+        if version < 2 {
+            continue;
+        }
+
+        let mut reader = Reader::new(body);
+        let item = read_item_id(&mut reader, (version == 3) as u8)?;
+
+        //Item protection index
+        reader.skip(2)?;
+
+        item_types.push((item, reader.take(4)?.try_into()?));
+    }
+
+    Ok(item_types)
+}
+
+/// Parses the properties (`ipco`) and their associations (`ipma`) of an `iprp` box.
+fn parse_iprp(body: &[u8]) -> Result<(Vec<IsoBox<'_>>, Vec<PropertyAssociation>)> {
+    let boxes = parse_boxes(body)?;
+
+    let properties = match find_box(&boxes, b"ipco") {
+        Some(ipco) => parse_boxes(ipco.body)?,
+        None => vec![],
+    };
+
+    let mut associations = vec![];
+
+    for ipma in boxes.iter().filter(|iso_box| iso_box.box_type == *b"ipma") {
+        let flags = ipma.body.get(3).copied().unwrap_or_default();
+        let (version, body) = full_box_body(ipma.body)?;
+        let mut reader = Reader::new(body);
+
+        for _ in 0..reader.u32()? {
+            let item = read_item_id(&mut reader, version)?;
+
+            for _ in 0..reader.u8()? {
+                //The highest bit is the essential flag
+                let index = if flags & 1 != 0 {
+                    reader.u16()? & 0x7FFF
+                } else {
+                    (reader.u8()? & 0x7F) as u16
+                };
+
+                associations.push((item, index));
+            }
+        }
+    }
+
+    Ok((properties, associations))
+}
+
+/// Parses the references (type, from item, to item) of an `iref` box.
+fn parse_iref(body: &[u8]) -> Result<Vec<([u8; 4], u32, u32)>> {
+    let (version, body) = full_box_body(body)?;
+    let mut references = vec![];
+
+    for reference in parse_boxes(body)? {
+        let mut reader = Reader::new(reference.body);
+        let from_item = read_item_id(&mut reader, version)?;
+
+        for _ in 0..reader.u16()? {
+            references.push((
+                reference.box_type,
+                from_item,
+                read_item_id(&mut reader, version)?,
+            ));
+        }
+    }
+
+    Ok(references)
+}
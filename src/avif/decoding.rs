@@ -0,0 +1,567 @@
+//! Provides AV1 decoding of the images and video frames encoded by [`super::encoding`] and [`super::video`].
+//! The color conversion ([`yuv_to_rgb8`]) is always available, the decoding itself is done by [`dav1d`](https://docs.rs/dav1d) behind the `av1-decode` feature.
+
+use anyhow::{bail, Result};
+
+#[cfg(feature = "av1-decode")]
+use super::{container::parse_avif, video::EncodedVideoFrame};
+#[cfg(feature = "av1-decode")]
+use anyhow::anyhow;
+#[cfg(feature = "av1-decode")]
+use dav1d::{Decoder, PixelLayout, PlanarImageComponent, Settings};
+
+/// The pixel format of a [`DecodedImage`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PixelFormat {
+    /// 3 bytes per pixel.
+    Rgb8,
+    /// 4 bytes per pixel, the alpha is straight (not premultiplied).
+    Rgba8,
+}
+
+impl PixelFormat {
+    /// Returns the count of bytes a pixel takes up.
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            PixelFormat::Rgb8 => 3,
+            PixelFormat::Rgba8 => 4,
+        }
+    }
+}
+
+/// A decoded image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DecodedImage {
+    /// The interleaved pixels of the image, row by row.
+    pub pixels: Vec<u8>,
+    /// The width of the image in pixels.
+    pub width: usize,
+    /// The height of the image in pixels.
+    pub height: usize,
+    /// The format of the pixels.
+    pub format: PixelFormat,
+}
+
+/// The chroma subsampling of a [`YuvFrame`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChromaSubsampling {
+    /// The chroma planes are half the width and half the height of the image.
+    Cs420,
+    /// The chroma planes are half the width of the image.
+    Cs422,
+    /// The chroma planes are the size of the image.
+    Cs444,
+    /// There are no chroma planes.
+    Monochrome,
+}
+
+/// The matrix which converts between the YUV and the RGB color spaces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum YuvMatrix {
+    /// The planes are G, B and R (no conversion).
+    Identity,
+    /// ITU-R BT.601.
+    Bt601,
+    /// ITU-R BT.709.
+    Bt709,
+    /// ITU-R BT.2020 (non-constant luminance).
+    Bt2020,
+}
+
+impl YuvMatrix {
+    ///
+    /// Creates a [`YuvMatrix`] from the matrix coefficients defined by ITU-T H.273 (Eg.: [`super::container::AvifImage::matrix_coefficients`]).
+    ///
+    /// # Behavior
+    /// Unspecified and unsupported matrices fall back to BT.601.
+    ///
+    pub fn from_cicp(matrix_coefficients: u16) -> Self {
+        match matrix_coefficients {
+            0 => YuvMatrix::Identity,
+            1 => YuvMatrix::Bt709,
+            9 | 10 => YuvMatrix::Bt2020,
+            _ => YuvMatrix::Bt601,
+        }
+    }
+
+    /// Returns the red and blue luma coefficients of the matrix.
+    fn coefficients(&self) -> (f32, f32) {
+        match self {
+            YuvMatrix::Identity | YuvMatrix::Bt601 => (0.299, 0.114),
+            YuvMatrix::Bt709 => (0.2126, 0.0722),
+            YuvMatrix::Bt2020 => (0.2627, 0.0593),
+        }
+    }
+}
+
+/// A planar YUV frame, borrowed from a decoder.
+#[derive(Debug, Clone, Copy)]
+pub struct YuvFrame<'a> {
+    /// The Y, U and V planes, the chroma planes are ignored if the frame is [`ChromaSubsampling::Monochrome`].
+    pub planes: [&'a [u8]; 3],
+    /// The count of bytes between the starts of two rows, for every plane.
+    pub strides: [usize; 3],
+    /// The width of the frame in pixels.
+    pub width: usize,
+    /// The height of the frame in pixels.
+    pub height: usize,
+    /// The count of bits per sample (`8..=16`), samples above 8 bits are stored as little endian `u16`-s.
+    pub bit_depth: u8,
+    /// The chroma subsampling of the frame.
+    pub subsampling: ChromaSubsampling,
+    /// The color matrix of the frame.
+    pub matrix: YuvMatrix,
+    /// Whether the samples use the full range, or the limited (studio swing) range.
+    pub full_range: bool,
+}
+
+impl YuvFrame<'_> {
+    /// Reads a sample of a plane.
+    fn sample(&self, plane: usize, x: usize, y: usize) -> u16 {
+        let row = &self.planes[plane][y * self.strides[plane]..];
+
+        if self.bit_depth > 8 {
+            u16::from_le_bytes([row[x * 2], row[x * 2 + 1]])
+        } else {
+            row[x] as u16
+        }
+    }
+
+    /// Returns the horizontal and vertical shift of the chroma plane coordinates.
+    fn chroma_shift(&self) -> (usize, usize) {
+        match self.subsampling {
+            ChromaSubsampling::Cs420 => (1, 1),
+            ChromaSubsampling::Cs422 => (1, 0),
+            ChromaSubsampling::Cs444 | ChromaSubsampling::Monochrome => (0, 0),
+        }
+    }
+}
+
+///
+/// Converts a planar YUV frame into RGB8 pixels.
+///
+/// # Behavior
+/// Returns the interleaved RGB8 pixels of the frame.
+/// The chroma samples are not interpolated, every pixel uses the chroma sample covering it.
+///
+/// # Error
+/// Returns an error if the bit depth is invalid, or a plane is too small for the frame's size and stride.
+///
+pub fn yuv_to_rgb8(frame: &YuvFrame<'_>) -> Result<Vec<u8>> {
+    if !(8..=16).contains(&frame.bit_depth) {
+        bail!("Invalid bit depth: {}.", frame.bit_depth);
+    }
+
+    let bytes_per_sample = if frame.bit_depth > 8 { 2 } else { 1 };
+    let (shift_x, shift_y) = frame.chroma_shift();
+    let plane_count = if frame.subsampling == ChromaSubsampling::Monochrome {
+        1
+    } else {
+        3
+    };
+
+    for plane in 0..plane_count {
+        let (plane_width, plane_height) = if plane == 0 {
+            (frame.width, frame.height)
+        } else {
+            (
+                frame.width.div_ceil(1 << shift_x),
+                frame.height.div_ceil(1 << shift_y),
+            )
+        };
+
+        if plane_height == 0 {
+            continue;
+        }
+
+        let row_size = plane_width * bytes_per_sample;
+
+        if frame.strides[plane] < row_size
+            || frame.planes[plane].len() < frame.strides[plane] * (plane_height - 1) + row_size
+        {
+            bail!(
+                "Plane {plane} is too small for a {}x{} frame.",
+                frame.width,
+                frame.height
+            );
+        }
+    }
+
+    //Normalizes the samples into 0.0..=1.0 (luma) and -0.5..=0.5 (chroma)
+    let scale = (1 << (frame.bit_depth - 8)) as f32;
+    let max = ((1u32 << frame.bit_depth) - 1) as f32;
+    let normalize_luma = |sample: u16| {
+        if frame.full_range {
+            sample as f32 / max
+        } else {
+            (sample as f32 - 16. * scale) / (219. * scale)
+        }
+    };
+    let normalize_chroma = |sample: u16| {
+        if frame.full_range {
+            (sample as f32 - (1 << (frame.bit_depth - 1)) as f32) / max
+        } else {
+            (sample as f32 - 128. * scale) / (224. * scale)
+        }
+    };
+
+    let (kr, kb) = frame.matrix.coefficients();
+    let kg = 1. - kr - kb;
+    let to_u8 = |value: f32| (value * 255.).round().clamp(0., 255.) as u8;
+
+    let mut pixels = Vec::with_capacity(frame.width * frame.height * 3);
+
+    for y in 0..frame.height {
+        for x in 0..frame.width {
+            let luma = frame.sample(0, x, y);
+
+            if frame.subsampling == ChromaSubsampling::Monochrome {
+                let gray = to_u8(normalize_luma(luma));
+
+                pixels.extend_from_slice(&[gray, gray, gray]);
+
+                continue;
+            }
+
+            let u = frame.sample(1, x >> shift_x, y >> shift_y);
+            let v = frame.sample(2, x >> shift_x, y >> shift_y);
+
+            let (r, g, b) = if frame.matrix == YuvMatrix::Identity {
+                (normalize_luma(v), normalize_luma(luma), normalize_luma(u))
+            } else {
+                let (luma, cb, cr) = (
+                    normalize_luma(luma),
+                    normalize_chroma(u),
+                    normalize_chroma(v),
+                );
+                let r = luma + 2. * (1. - kr) * cr;
+                let b = luma + 2. * (1. - kb) * cb;
+
+                (r, (luma - kr * r - kb * b) / kg, b)
+            };
+
+            pixels.extend_from_slice(&[to_u8(r), to_u8(g), to_u8(b)]);
+        }
+    }
+
+    Ok(pixels)
+}
+
+///
+/// Decodes an AVIF file (Eg.: [`ravif::EncodedImage::avif_file`]).
+///
+/// # Behavior
+/// Returns an [`PixelFormat::Rgba8`] image if the file has an alpha plane, and an [`PixelFormat::Rgb8`] image otherwise.
+/// Premultiplied alpha is converted to straight alpha.
+///
+/// # Error
+/// Returns an error if the file is not a valid AVIF image, or the AV1 payload could not be decoded.
+///
+#[cfg(feature = "av1-decode")]
+pub fn decode_avif(file: &[u8]) -> Result<DecodedImage> {
+    let avif = parse_avif(file)?;
+
+    let mut color_decoder = create_decoder()?;
+    let mut color = decode_av1_picture(&mut color_decoder, avif.color_data)?;
+
+    let Some(color) = color.pop() else {
+        bail!("The AVIF image's color item did not contain a picture.");
+    };
+
+    let mut image = picture_to_rgb8(
+        &color,
+        YuvMatrix::from_cicp(avif.matrix_coefficients),
+        avif.full_range,
+        false,
+    )?;
+
+    let Some(alpha_data) = avif.alpha_data else {
+        return Ok(image);
+    };
+
+    let mut alpha_decoder = create_decoder()?;
+    let mut alpha = decode_av1_picture(&mut alpha_decoder, alpha_data)?;
+
+    let Some(alpha) = alpha.pop() else {
+        bail!("The AVIF image's alpha item did not contain a picture.");
+    };
+
+    if (alpha.width() as usize, alpha.height() as usize) != (image.width, image.height) {
+        bail!("The AVIF image's alpha plane does not match the size of the image.");
+    }
+
+    //Only the luma plane of the alpha picture is used
+    let alpha_plane = picture_to_rgb8(&alpha, YuvMatrix::Bt601, true, true)?;
+
+    image.pixels = image
+        .pixels
+        .chunks_exact(3)
+        .zip(alpha_plane.pixels.chunks_exact(3))
+        .flat_map(|(color, alpha)| {
+            let alpha = alpha[0];
+            let unpremultiply = |channel: u8| {
+                if avif.premultiplied_alpha && alpha != 0 {
+                    ((channel as u32 * 255 + alpha as u32 / 2) / alpha as u32).min(255) as u8
+                } else {
+                    channel
+                }
+            };
+
+            [
+                unpremultiply(color[0]),
+                unpremultiply(color[1]),
+                unpremultiply(color[2]),
+                alpha,
+            ]
+        })
+        .collect();
+    image.format = PixelFormat::Rgba8;
+
+    Ok(image)
+}
+
+///
+/// Decodes an AVIF file into RGB8 pixels.
+///
+/// # Behavior
+/// The alpha plane is dropped if the image has one.
+///
+/// # Error
+/// Returns an error if the file is not a valid AVIF image, or the AV1 payload could not be decoded.
+///
+#[cfg(feature = "av1-decode")]
+pub fn decode_avif_rgb8(file: &[u8]) -> Result<DecodedImage> {
+    let image = decode_avif(file)?;
+
+    if image.format == PixelFormat::Rgb8 {
+        return Ok(image);
+    }
+
+    Ok(DecodedImage {
+        pixels: image
+            .pixels
+            .chunks_exact(4)
+            .flat_map(|pixel| [pixel[0], pixel[1], pixel[2]])
+            .collect(),
+        format: PixelFormat::Rgb8,
+        ..image
+    })
+}
+
+///
+/// Decodes an AVIF file into RGBA8 pixels.
+///
+/// # Behavior
+/// Opaque images get an alpha of `255`.
+///
+/// # Error
+/// Returns an error if the file is not a valid AVIF image, or the AV1 payload could not be decoded.
+///
+#[cfg(feature = "av1-decode")]
+pub fn decode_avif_rgba8(file: &[u8]) -> Result<DecodedImage> {
+    let image = decode_avif(file)?;
+
+    if image.format == PixelFormat::Rgba8 {
+        return Ok(image);
+    }
+
+    Ok(DecodedImage {
+        pixels: image
+            .pixels
+            .chunks_exact(3)
+            .flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 255])
+            .collect(),
+        format: PixelFormat::Rgba8,
+        ..image
+    })
+}
+
+///
+/// Decodes a [`ravif::EncodedImage`] (Eg.: the output of [`super::encoding::encode_raw_image`]).
+///
+/// # Behavior
+/// This is the same as calling [`decode_avif`] on [`ravif::EncodedImage::avif_file`].
+///
+/// # Error
+/// Returns an error if the AV1 payload could not be decoded.
+///
+#[cfg(feature = "av1-decode")]
+pub fn decode_encoded_image(encoded_image: &ravif::EncodedImage) -> Result<DecodedImage> {
+    decode_avif(&encoded_image.avif_file)
+}
+
+///
+/// A streaming AV1 video decoder, the counterpart of [`super::video::Av1VideoEncoder`].
+///
+/// # Behavior
+/// The [`EncodedVideoFrame`]-s must be passed in the order they were encoded, starting with a keyframe.
+/// The decoder runs without frame delay, so every frame is returned from the call which received it.
+///
+/// # Information
+/// The frames are converted to RGB8 with full range BT.601, which is the color description [`super::video::Av1VideoEncoder`] uses.
+///
+#[cfg(feature = "av1-decode")]
+pub struct Av1VideoDecoder {
+    decoder: Decoder,
+    //The count of frames decoded
+    frame_count: u64,
+}
+
+#[cfg(feature = "av1-decode")]
+impl std::fmt::Debug for Av1VideoDecoder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Av1VideoDecoder")
+            .field("frame_count", &self.frame_count)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "av1-decode")]
+impl Av1VideoDecoder {
+    ///
+    /// Creates a new [`Av1VideoDecoder`].
+    ///
+    /// # Error
+    /// Returns an error if the decoder could not be created.
+    ///
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            decoder: create_decoder()?,
+            frame_count: 0,
+        })
+    }
+
+    ///
+    /// Decodes an [`EncodedVideoFrame`] into RGB8 images.
+    ///
+    /// # Behavior
+    /// Returns the frames which were finished by this packet, this is usually a single frame.
+    ///
+    /// # Error
+    /// Returns an error if the packet is corrupted, or the decoding was started from an inter frame.
+    ///
+    pub fn decode_frame(&mut self, frame: &EncodedVideoFrame) -> Result<Vec<DecodedImage>> {
+        let pictures = decode_av1_picture(&mut self.decoder, frame.data.clone())?;
+
+        self.frame_count += pictures.len() as u64;
+
+        pictures
+            .iter()
+            .map(|picture| picture_to_rgb8(picture, YuvMatrix::Bt601, true, false))
+            .collect()
+    }
+
+    /// Returns the count of frames decoded.
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+}
+
+/// Creates a single threaded [`Decoder`] which returns every picture immediately.
+#[cfg(feature = "av1-decode")]
+fn create_decoder() -> Result<Decoder> {
+    let mut settings = Settings::new();
+
+    settings.set_max_frame_delay(1);
+
+    Decoder::with_settings(&settings)
+        .map_err(|err| anyhow!("Failed to create the AV1 decoder: {err:?}."))
+}
+
+/// Sends the data into the decoder and returns the pictures it has finished.
+#[cfg(feature = "av1-decode")]
+fn decode_av1_picture(decoder: &mut Decoder, data: Vec<u8>) -> Result<Vec<dav1d::Picture>> {
+    let mut pictures = vec![];
+
+    let mut sent = decoder.send_data(data, None, None, None);
+
+    loop {
+        match sent {
+            Ok(()) => break,
+            //The decoder has to output pictures before it accepts the rest of the data
+            Err(dav1d::Error::Again) => {
+                match decoder.get_picture() {
+                    Ok(picture) => pictures.push(picture),
+                    Err(dav1d::Error::Again) => (),
+                    Err(err) => bail!("Failed to decode the AV1 data: {err:?}."),
+                }
+
+                sent = decoder.send_pending_data();
+            }
+            Err(err) => bail!("Failed to decode the AV1 data: {err:?}."),
+        }
+    }
+
+    loop {
+        match decoder.get_picture() {
+            Ok(picture) => pictures.push(picture),
+            Err(dav1d::Error::Again) => break,
+            Err(err) => bail!("Failed to decode the AV1 data: {err:?}."),
+        }
+    }
+
+    Ok(pictures)
+}
+
+/// Converts a decoded picture into an RGB8 image, if `luma_only` is set the chroma planes are ignored (Eg.: for alpha planes).
+#[cfg(feature = "av1-decode")]
+fn picture_to_rgb8(
+    picture: &dav1d::Picture,
+    matrix: YuvMatrix,
+    full_range: bool,
+    luma_only: bool,
+) -> Result<DecodedImage> {
+    let subsampling = match picture.pixel_layout() {
+        _ if luma_only => ChromaSubsampling::Monochrome,
+        PixelLayout::I400 => ChromaSubsampling::Monochrome,
+        PixelLayout::I420 => ChromaSubsampling::Cs420,
+        PixelLayout::I422 => ChromaSubsampling::Cs422,
+        PixelLayout::I444 => ChromaSubsampling::Cs444,
+    };
+
+    let y_plane = picture.plane(PlanarImageComponent::Y);
+    let (u_plane, v_plane) = if subsampling == ChromaSubsampling::Monochrome {
+        (None, None)
+    } else {
+        (
+            Some(picture.plane(PlanarImageComponent::U)),
+            Some(picture.plane(PlanarImageComponent::V)),
+        )
+    };
+
+    let (width, height) = (picture.width() as usize, picture.height() as usize);
+
+    let frame = YuvFrame {
+        planes: [
+            y_plane.as_ref(),
+            u_plane
+                .as_ref()
+                .map(|plane| plane.as_ref())
+                .unwrap_or_default(),
+            v_plane
+                .as_ref()
+                .map(|plane| plane.as_ref())
+                .unwrap_or_default(),
+        ],
+        strides: [
+            picture.stride(PlanarImageComponent::Y) as usize,
+            picture.stride(PlanarImageComponent::U) as usize,
+            picture.stride(PlanarImageComponent::V) as usize,
+        ],
+        width,
+        height,
+        bit_depth: picture.bit_depth() as u8,
+        subsampling,
+        matrix,
+        full_range,
+    };
+
+    Ok(DecodedImage {
+        pixels: yuv_to_rgb8(&frame)?,
+        width,
+        height,
+        format: PixelFormat::Rgb8,
+    })
+}
@@ -1,7 +1,125 @@
 //! Provides AV1 encoding for higher data efficiency via [`ravif`].
 
+use anyhow::{bail, Result};
 use image::GenericImageView;
-use ravif::{EncodedImage, Encoder, Img};
+use rav1e::prelude::PixelRange;
+use ravif::{AlphaColorMode, BitDepth, ColorModel, EncodedImage, Encoder, Img, MatrixCoefficients};
+
+use super::yuv::rgb10_to_444;
+
+/// The largest sample value of a 10 bit image.
+const MAX_10_BIT_SAMPLE: u16 = 1023;
+
+/// The color space the pixels are stored in inside the AVIF file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AvifColorSpace {
+    /// BT.601 YCbCr, this compresses better and is the most compatible.
+    #[default]
+    YCbCr,
+    /// The RGB channels are stored without conversion (as GBR), this results in larger files.
+    Rgb,
+}
+
+impl From<AvifColorSpace> for ColorModel {
+    fn from(color_space: AvifColorSpace) -> Self {
+        match color_space {
+            AvifColorSpace::YCbCr => Self::YCbCr,
+            AvifColorSpace::Rgb => Self::RGB,
+        }
+    }
+}
+
+/// A serializable configuration of an AVIF [`Encoder`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AvifEncoderConfig {
+    /// The quality of the color channels (`1.0..=100.0`).
+    pub quality: f32,
+    /// The quality of the alpha channel (`1.0..=100.0`), the alpha can usually be compressed more than the color.
+    pub alpha_quality: f32,
+    /// The speed preset (`1..=10`), higher is faster with worse compression.
+    pub speed: u8,
+    /// The color space the pixels are stored in.
+    pub color_space: AvifColorSpace,
+    /// The bit depth of the AV1 payload (`8` or `10`), `None` lets the encoder decide.
+    pub bit_depth: Option<u8>,
+    /// Whether the color channels are stored premultiplied with the alpha, this can reduce the size of images with large transparent areas.
+    pub premultiplied_alpha: bool,
+    /// The count of threads the encoder uses, `None` means the global [`rayon`](https://docs.rs/rayon) pool.
+    pub threads: Option<usize>,
+}
+
+impl Default for AvifEncoderConfig {
+    /// The defaults of [`Encoder::new`].
+    fn default() -> Self {
+        Self {
+            quality: 80.,
+            alpha_quality: 80.,
+            speed: 5,
+            color_space: AvifColorSpace::YCbCr,
+            bit_depth: None,
+            premultiplied_alpha: false,
+            threads: None,
+        }
+    }
+}
+
+impl AvifEncoderConfig {
+    ///
+    /// Creates an [`Encoder`] from the configuration.
+    ///
+    /// # Error
+    /// Returns an error if a setting is out of its range (the [`Encoder`] would panic).
+    ///
+    pub fn to_encoder(&self) -> Result<Encoder> {
+        if !(1. ..=100.).contains(&self.quality) || !(1. ..=100.).contains(&self.alpha_quality) {
+            bail!(
+                "Invalid quality: {} (alpha: {}), the quality must be between 1 and 100.",
+                self.quality,
+                self.alpha_quality
+            );
+        }
+
+        if !(1..=10).contains(&self.speed) {
+            bail!(
+                "Invalid speed: {}, the speed must be between 1 and 10.",
+                self.speed
+            );
+        }
+
+        if self
+            .bit_depth
+            .is_some_and(|bit_depth| bit_depth != 8 && bit_depth != 10)
+        {
+            bail!(
+                "Invalid bit depth: {:?}, only 8 and 10 bits are supported.",
+                self.bit_depth
+            );
+        }
+
+        if self.threads == Some(0) {
+            bail!("The thread count must not be 0.");
+        }
+
+        Ok(Encoder::new()
+            .with_quality(self.quality)
+            .with_alpha_quality(self.alpha_quality)
+            .with_speed(self.speed)
+            .with_internal_color_model(self.color_space.into())
+            .with_bit_depth(match self.bit_depth {
+                Some(8) => BitDepth::Eight,
+                Some(_) => BitDepth::Ten,
+                None => BitDepth::Auto,
+            })
+            .with_num_threads(self.threads)
+            .with_alpha_color_mode(if self.premultiplied_alpha {
+                AlphaColorMode::Premultiplied
+            } else {
+                AlphaColorMode::UnassociatedClean
+            }))
+    }
+}
 
 ///
 /// Encodes a **formatted** image with the AV1 format.
@@ -11,6 +129,7 @@ use ravif::{EncodedImage, Encoder, Img};
 /// # Behavior
 /// The **formatted** image is re-encoded with the settings the user passes in with the [`Encoder`] argument.
 /// The encoded image ([`ravif::EncodedImage`]) is returned from the image.
+/// If the image has an alpha channel it is preserved (fully opaque images are encoded without one).
 ///
 /// # Error
 /// Will return an error if the image format could not be guessed correctly or if the image had incorrect proerties (Eg.: Invalid size).
@@ -19,6 +138,19 @@ pub fn encode_image(encoder: Encoder, image: &[u8]) -> anyhow::Result<EncodedIma
     //Parse image from bytes
     let parsed_image = image::load_from_memory(image)?;
 
+    if parsed_image.color().has_alpha() {
+        let colors = parsed_image
+            .pixels()
+            .map(|(_, _, color)| ravif::RGBA8::new(color.0[0], color.0[1], color.0[2], color.0[3]))
+            .collect::<Vec<ravif::RGBA8>>();
+
+        return Ok(encoder.encode_rgba(Img::new(
+            colors.as_slice(),
+            parsed_image.width() as usize,
+            parsed_image.height() as usize,
+        ))?);
+    }
+
     //Iter over the pixels
     let colors = parsed_image
         .pixels()
@@ -63,3 +195,486 @@ pub fn encode_raw_image(
 
     Ok(encoded_image)
 }
+
+///
+/// Encodes a raw RGBA8 image with the AV1 format.
+///
+/// # Behavior
+/// The image is encoded with the settings the user passes in with the [`Encoder`] argument, the alpha channel is encoded with the [`Encoder`]'s alpha quality.
+/// The alpha is expected to be straight (not premultiplied), if the image is fully opaque the alpha channel is left out.
+///
+/// # Error
+/// Returns an error if the image's length does not match its size.
+///
+pub fn encode_raw_image_rgba(
+    encoder: Encoder,
+    image: &[u8],
+    width: usize,
+    height: usize,
+) -> Result<EncodedImage> {
+    check_image_size(image.len(), width, height, 4)?;
+
+    let colors = image
+        .chunks_exact(4)
+        .map(|chunk| ravif::RGBA8::new(chunk[0], chunk[1], chunk[2], chunk[3]))
+        .collect::<Vec<ravif::RGBA8>>();
+
+    Ok(encoder.encode_rgba(Img::new(colors.as_slice(), width, height))?)
+}
+
+///
+/// Encodes a raw 8 bit grayscale image with the AV1 format.
+///
+/// # Behavior
+/// The image has 1 byte per pixel, it is encoded as 8 bit full range YCbCr with neutral chroma (which compresses to almost nothing).
+/// The [`Encoder`]'s color space and bit depth are ignored.
+///
+/// # Error
+/// Returns an error if the image's length does not match its size.
+///
+pub fn encode_raw_image_grayscale(
+    encoder: Encoder,
+    image: &[u8],
+    width: usize,
+    height: usize,
+) -> Result<EncodedImage> {
+    check_image_size(image.len(), width, height, 1)?;
+
+    let planes = image.iter().map(|luma| [*luma, 128, 128]);
+
+    Ok(encoder.encode_raw_planes_8_bit(
+        width,
+        height,
+        planes,
+        None::<[_; 0]>,
+        PixelRange::Full,
+        MatrixCoefficients::BT601,
+    )?)
+}
+
+///
+/// Encodes a raw 10 bit RGB image with the AV1 format.
+///
+/// # Behavior
+/// The image has 3 samples per pixel, the samples must be in `0..=1023`.
+/// The image is encoded as 10 bit full range AV1 in the [`AvifColorSpace`] passed in, the [`Encoder`]'s color space and bit depth are ignored.
+///
+/// # Error
+/// Returns an error if the image's length does not match its size, or a sample is out of range.
+///
+pub fn encode_raw_image_10_bit(
+    encoder: Encoder,
+    image: &[u16],
+    width: usize,
+    height: usize,
+    color_space: AvifColorSpace,
+) -> Result<EncodedImage> {
+    encode_10_bit(encoder, image, width, height, color_space, None)
+}
+
+///
+/// Encodes a raw 10 bit RGBA image with the AV1 format.
+///
+/// # Behavior
+/// The image has 4 samples per pixel, the samples must be in `0..=1023`, the alpha is expected to be straight (not premultiplied).
+/// The encoder is created from the [`AvifEncoderConfig`], the image is stored in its [`AvifEncoderConfig::color_space`], and the color samples are premultiplied with the alpha if [`AvifEncoderConfig::premultiplied_alpha`] is set.
+/// Otherwise this behaves like [`encode_raw_image_10_bit`].
+///
+/// # Information
+/// This takes the [`AvifEncoderConfig`] instead of an [`Encoder`], as the raw planes have to be premultiplied here and an [`Encoder`] does not expose its alpha mode.
+///
+/// # Error
+/// Returns an error if the configuration is invalid, the image's length does not match its size, or a sample is out of range.
+///
+pub fn encode_raw_image_rgba_10_bit(
+    config: &AvifEncoderConfig,
+    image: &[u16],
+    width: usize,
+    height: usize,
+) -> Result<EncodedImage> {
+    encode_10_bit(
+        config.to_encoder()?,
+        image,
+        width,
+        height,
+        config.color_space,
+        Some(config.premultiplied_alpha),
+    )
+}
+
+/// Encodes a 10 bit RGB image, or an RGBA image if `premultiply` is set (to whether the color samples have to be premultiplied).
+fn encode_10_bit(
+    encoder: Encoder,
+    image: &[u16],
+    width: usize,
+    height: usize,
+    color_space: AvifColorSpace,
+    premultiply: Option<bool>,
+) -> Result<EncodedImage> {
+    let channels = if premultiply.is_some() { 4 } else { 3 };
+
+    check_image_size(image.len(), width, height, channels)?;
+
+    if let Some(sample) = image.iter().find(|sample| **sample > MAX_10_BIT_SAMPLE) {
+        bail!("Invalid 10 bit sample: {sample}, the samples must be between 0 and {MAX_10_BIT_SAMPLE}.");
+    }
+
+    let planes = image.chunks_exact(channels).map(|pixel| {
+        let (mut r, mut g, mut b) = (pixel[0], pixel[1], pixel[2]);
+
+        //The encoder expects premultiplied planes in the premultiplied alpha mode
+        if premultiply == Some(true) {
+            [r, g, b] = [r, g, b].map(|sample| premultiply_10_bit(sample, pixel[3]));
+        }
+
+        match color_space {
+            AvifColorSpace::YCbCr => rgb10_to_444([r, g, b]),
+            AvifColorSpace::Rgb => [g, b, r],
+        }
+    });
+
+    let matrix_coefficients = match color_space {
+        AvifColorSpace::YCbCr => MatrixCoefficients::BT601,
+        AvifColorSpace::Rgb => MatrixCoefficients::Identity,
+    };
+
+    //Fully opaque images are encoded without an alpha channel
+    let alpha = premultiply
+        .map(|_| image.chunks_exact(4).map(|pixel| pixel[3]))
+        .filter(|alpha| alpha.clone().any(|alpha| alpha != MAX_10_BIT_SAMPLE));
+
+    Ok(encoder.encode_raw_planes_10_bit(
+        width,
+        height,
+        planes,
+        alpha,
+        PixelRange::Full,
+        matrix_coefficients,
+    )?)
+}
+
+/// Multiplies a 10 bit color sample with a 10 bit alpha, rounding to the nearest sample.
+fn premultiply_10_bit(sample: u16, alpha: u16) -> u16 {
+    ((sample as u32 * alpha as u32 + MAX_10_BIT_SAMPLE as u32 / 2) / MAX_10_BIT_SAMPLE as u32)
+        as u16
+}
+
+/// Checks whether the length of an image matches its size.
+fn check_image_size(length: usize, width: usize, height: usize, channels: usize) -> Result<()> {
+    if width == 0 || height == 0 || length != width * height * channels {
+        bail!(
+            "Expected {} samples for a {width}x{height} image with {channels} channel(s), got {length}.",
+            width * height * channels
+        );
+    }
+
+    Ok(())
+}
+
+/// The settings of the quality search of a [`BudgetEncoder`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ByteBudgetConfig {
+    /// The lowest quality the search can pick (`1.0..=100.0`).
+    pub min_quality: f32,
+    /// The highest quality the search can pick (`1.0..=100.0`).
+    pub max_quality: f32,
+    /// The speed preset (`1..=10`) used while searching the quality, this should be fast as the image is encoded multiple times.
+    pub search_speed: u8,
+    /// The speed preset (`1..=10`) of the final encoding, slower presets compress better so the final image is usually smaller than the searched ones.
+    pub speed: u8,
+    /// The maximum count of encodings of an image (`1..`), this includes the try of the cached quality, the fallback to the lowest quality and the final encoding with the slower preset.
+    pub max_attempts: u32,
+    /// The search stops when the range of the possible qualities is narrower than this.
+    pub quality_tolerance: f32,
+}
+
+impl Default for ByteBudgetConfig {
+    fn default() -> Self {
+        Self {
+            min_quality: 1.,
+            max_quality: 100.,
+            search_speed: 10,
+            speed: 8,
+            max_attempts: 8,
+            quality_tolerance: 2.,
+        }
+    }
+}
+
+/// An image encoded by a [`BudgetEncoder`].
+#[derive(Clone)]
+pub struct BudgetEncodedImage {
+    /// The encoded image, its size is within the byte budget.
+    pub image: EncodedImage,
+    /// The quality the image was encoded with.
+    pub quality: f32,
+    /// The speed preset the image was encoded with.
+    pub speed: u8,
+    /// The count of encodings of the image, at most [`ByteBudgetConfig::max_attempts`].
+    pub attempts: u32,
+}
+
+impl std::fmt::Debug for BudgetEncodedImage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BudgetEncodedImage")
+            .field("size", &self.image.avif_file.len())
+            .field("quality", &self.quality)
+            .field("speed", &self.speed)
+            .field("attempts", &self.attempts)
+            .finish()
+    }
+}
+
+///
+/// An AVIF encoder which encodes the images with the highest quality fitting into a byte budget.
+///
+/// # Behavior
+/// The quality is searched with bisection, the last quality which fit into the budget is cached and the next search starts from it.
+/// The cached quality halves the search range with a single encoding, but the search can still take up to [`ByteBudgetConfig::max_attempts`] encodings.
+///
+/// # Information
+/// The other settings (Eg.: color space, alpha quality) are taken from the [`AvifEncoderConfig`], its quality and speed are overwritten by the search.
+///
+#[derive(Debug, Clone)]
+pub struct BudgetEncoder {
+    encoder_config: AvifEncoderConfig,
+    budget_config: ByteBudgetConfig,
+    //The last quality which fit into the budget
+    last_quality: Option<f32>,
+}
+
+impl BudgetEncoder {
+    ///
+    /// Creates a new [`BudgetEncoder`].
+    ///
+    /// # Error
+    /// Returns an error if the quality range, a speed preset or the maximum count of attempts is invalid.
+    ///
+    pub fn new(encoder_config: AvifEncoderConfig, budget_config: ByteBudgetConfig) -> Result<Self> {
+        if !(1. ..=100.).contains(&budget_config.min_quality)
+            || !(1. ..=100.).contains(&budget_config.max_quality)
+            || budget_config.min_quality > budget_config.max_quality
+        {
+            bail!(
+                "Invalid quality range: {}..={}.",
+                budget_config.min_quality,
+                budget_config.max_quality
+            );
+        }
+
+        if budget_config.max_attempts == 0 {
+            bail!("The maximum count of attempts must not be 0.");
+        }
+
+        //Validate the rest of the settings
+        for speed in [budget_config.search_speed, budget_config.speed] {
+            AvifEncoderConfig {
+                speed,
+                ..encoder_config
+            }
+            .to_encoder()?;
+        }
+
+        Ok(Self {
+            encoder_config,
+            budget_config,
+            last_quality: None,
+        })
+    }
+
+    ///
+    /// Encodes a raw RGB8 image into at most `max_bytes` bytes.
+    ///
+    /// # Behavior
+    /// Returns the image encoded with the highest quality found which fits into the budget.
+    ///
+    /// # Error
+    /// Returns an error if the image's length does not match its size, or the image does not fit into the budget even with the lowest quality.
+    ///
+    pub fn encode_raw_image(
+        &mut self,
+        image: &[u8],
+        width: usize,
+        height: usize,
+        max_bytes: usize,
+    ) -> Result<BudgetEncodedImage> {
+        check_image_size(image.len(), width, height, 3)?;
+
+        self.encode(max_bytes, |encoder| {
+            encode_raw_image(encoder, image, width, height)
+        })
+    }
+
+    ///
+    /// Encodes a raw RGBA8 image into at most `max_bytes` bytes.
+    ///
+    /// # Behavior
+    /// Returns the image encoded with the highest quality found which fits into the budget, the alpha channel is included in the budget.
+    ///
+    /// # Error
+    /// Returns an error if the image's length does not match its size, or the image does not fit into the budget even with the lowest quality.
+    ///
+    pub fn encode_raw_image_rgba(
+        &mut self,
+        image: &[u8],
+        width: usize,
+        height: usize,
+        max_bytes: usize,
+    ) -> Result<BudgetEncodedImage> {
+        check_image_size(image.len(), width, height, 4)?;
+
+        self.encode(max_bytes, |encoder| {
+            encode_raw_image_rgba(encoder, image, width, height)
+        })
+    }
+
+    /// Returns the last quality which fit into the budget, `None` if nothing was encoded yet.
+    pub fn last_quality(&self) -> Option<f32> {
+        self.last_quality
+    }
+
+    /// Clears the cached quality, so the next search starts from scratch (Eg.: after a scene change).
+    pub fn reset(&mut self) {
+        self.last_quality = None;
+    }
+
+    /// Searches the highest quality fitting into the budget.
+    fn encode(
+        &mut self,
+        max_bytes: usize,
+        encode: impl Fn(Encoder) -> Result<EncodedImage>,
+    ) -> Result<BudgetEncodedImage> {
+        let config = self.budget_config;
+        let slow_encoding = config.speed != config.search_speed;
+
+        let encode_with = |quality: f32, speed: u8| -> Result<EncodedImage> {
+            encode(
+                AvifEncoderConfig {
+                    quality,
+                    speed,
+                    ..self.encoder_config
+                }
+                .to_encoder()?,
+            )
+        };
+
+        let mut search = QualitySearch {
+            low: config.min_quality,
+            high: config.max_quality,
+            best: None,
+            min_tried: false,
+            max_bytes,
+            attempts: 0,
+        };
+
+        //The attempts kept back for the lowest quality fallback and the slower preset
+        let reserved = |search: &QualitySearch| {
+            (search.best.is_none() && !search.min_tried) as u32 + slow_encoding as u32
+        };
+
+        //Start from the cached quality
+        if let Some(last_quality) = self.last_quality {
+            if search.attempts + reserved(&search) < config.max_attempts {
+                let quality = last_quality.clamp(config.min_quality, config.max_quality);
+
+                search.record(quality, encode_with(quality, config.search_speed)?);
+            }
+        }
+
+        while search.attempts + reserved(&search) < config.max_attempts
+            && search.high - search.low > config.quality_tolerance
+        {
+            let quality = (search.low + search.high) / 2.;
+
+            search.record(quality, encode_with(quality, config.search_speed)?);
+        }
+
+        //The lowest quality is only tried if nothing fit
+        if search.best.is_none() && !search.min_tried && search.attempts < config.max_attempts {
+            search.record(
+                config.min_quality,
+                encode_with(config.min_quality, config.search_speed)?,
+            );
+        }
+
+        let attempts = search.attempts;
+
+        let Some((quality, searched)) = search.best else {
+            bail!("The image does not fit into {max_bytes} bytes, even with the lowest quality.");
+        };
+
+        self.last_quality = Some(quality);
+
+        let mut result = BudgetEncodedImage {
+            image: searched,
+            quality,
+            speed: config.search_speed,
+            attempts,
+        };
+
+        //Re-encode with the slower preset, it is only kept if it still fits
+        if slow_encoding && attempts < config.max_attempts {
+            let encoded = encode_with(quality, config.speed)?;
+
+            result.attempts += 1;
+
+            if encoded.avif_file.len() <= max_bytes {
+                result.image = encoded;
+                result.speed = config.speed;
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+/// The state of a quality bisection.
+struct QualitySearch {
+    //The highest quality which fit, or the lower bound of the search
+    low: f32,
+    //The lowest quality which did not fit, or the upper bound of the search
+    high: f32,
+    best: Option<(f32, EncodedImage)>,
+    //Whether the lowest quality was encoded
+    min_tried: bool,
+    max_bytes: usize,
+    //The count of encodings recorded
+    attempts: u32,
+}
+
+impl QualitySearch {
+    /// Narrows the range with the result of an encoding.
+    fn record(&mut self, quality: f32, encoded: EncodedImage) {
+        //Until something fits, the lower bound is the lowest quality
+        self.min_tried |= self.best.is_none() && quality <= self.low;
+        self.attempts += 1;
+
+        if encoded.avif_file.len() <= self.max_bytes {
+            self.low = quality;
+            self.best = Some((quality, encoded));
+        } else {
+            self.high = quality;
+        }
+    }
+}
+
+///
+/// Encodes a raw RGB8 image with the highest quality which fits into `max_bytes` bytes.
+///
+/// # Behavior
+/// This creates a one-off [`BudgetEncoder`] with the default [`ByteBudgetConfig`], streams should keep a [`BudgetEncoder`] instead, as it caches the quality between the frames.
+///
+/// # Error
+/// Returns an error if the image's length does not match its size, or the image does not fit into the budget even with the lowest quality.
+///
+pub fn encode_raw_image_with_budget(
+    encoder_config: AvifEncoderConfig,
+    image: &[u8],
+    width: usize,
+    height: usize,
+    max_bytes: usize,
+) -> Result<BudgetEncodedImage> {
+    BudgetEncoder::new(encoder_config, ByteBudgetConfig::default())?
+        .encode_raw_image(image, width, height, max_bytes)
+}
@@ -1,6 +1,13 @@
-//! Provides AV1 encoding for images.
+//! Provides AV1 encoding and decoding for images and video streams.
 //! [AV1](https://en.wikipedia.org/wiki/AV1) (AOMedia Video 1) is a high efficiency video codec. It was originally made to transmit video calls.
+pub mod container;
+pub mod decoding;
 pub mod encoding;
+pub mod scale;
+pub mod video;
+pub(crate) mod yuv;
 
 //Re-export the ravif crate.
-pub use ravif;
\ No newline at end of file
+pub use ravif;
+//Re-export the rav1e crate.
+pub use rav1e;
\ No newline at end of file
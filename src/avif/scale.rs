@@ -0,0 +1,274 @@
+//! Provides cropping and scaling of raw RGB8 frames, so the frames of [`crate::cam::Webcam::get_frame`] can be fitted to the resolution of a call before encoding.
+
+use anyhow::{bail, Result};
+use image::{
+    imageops::{self, FilterType},
+    RgbImage,
+};
+#[cfg(feature = "opencv")]
+use opencv::core::Size_;
+
+/// The filter used for resampling the frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ScaleFilter {
+    /// Nearest neighbor, the fastest but it produces aliasing.
+    Nearest,
+    /// Bilinear filtering, a good balance of speed and quality.
+    #[default]
+    Bilinear,
+    /// Cubic (Catmull-Rom) filtering, sharper than bilinear.
+    CatmullRom,
+    /// Lanczos filtering with a window of 3, the sharpest and slowest.
+    Lanczos3,
+}
+
+impl From<ScaleFilter> for FilterType {
+    fn from(filter: ScaleFilter) -> Self {
+        match filter {
+            ScaleFilter::Nearest => Self::Nearest,
+            ScaleFilter::Bilinear => Self::Triangle,
+            ScaleFilter::CatmullRom => Self::CatmullRom,
+            ScaleFilter::Lanczos3 => Self::Lanczos3,
+        }
+    }
+}
+
+/// How the frame is fitted into the target size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ScaleMode {
+    /// The frame is scaled to the target size, ignoring its aspect ratio.
+    Stretch,
+    /// The frame is scaled to fit inside the target size while preserving its aspect ratio, so one of its sides can be shorter than the target.
+    #[default]
+    Fit,
+    /// The frame is scaled to cover the target size while preserving its aspect ratio, then the overflowing sides are cropped (centered), so the output is always the target size.
+    Fill,
+}
+
+/// A rectangle of a frame in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CropRect {
+    /// The horizontal offset of the rectangle from the left edge.
+    pub x: usize,
+    /// The vertical offset of the rectangle from the top edge.
+    pub y: usize,
+    /// The width of the rectangle.
+    pub width: usize,
+    /// The height of the rectangle.
+    pub height: usize,
+}
+
+/// The settings of the scaling stage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ScaleConfig {
+    /// The target width in pixels.
+    pub width: usize,
+    /// The target height in pixels.
+    pub height: usize,
+    /// How the frame is fitted into the target size.
+    pub mode: ScaleMode,
+    /// The filter used for resampling.
+    pub filter: ScaleFilter,
+    /// The region of the frame which is kept before scaling, `None` keeps the whole frame.
+    pub crop: Option<CropRect>,
+}
+
+impl ScaleConfig {
+    /// Creates a [`ScaleConfig`] which fits the frames into the size with bilinear filtering.
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            mode: ScaleMode::Fit,
+            filter: ScaleFilter::Bilinear,
+            crop: None,
+        }
+    }
+
+    /// A 640x360 (360p) target.
+    pub fn p360() -> Self {
+        Self::new(640, 360)
+    }
+
+    /// A 1280x720 (720p) target.
+    pub fn p720() -> Self {
+        Self::new(1280, 720)
+    }
+
+    ///
+    /// Returns the size of the output frame for an input frame's size.
+    ///
+    /// # Behavior
+    /// The crop is applied before calculating the size, the returned sides are at least 1 pixel.
+    ///
+    pub fn output_size(&self, width: usize, height: usize) -> (usize, usize) {
+        let (width, height) = self
+            .crop
+            .map(|crop| (crop.width, crop.height))
+            .unwrap_or((width, height));
+
+        match self.mode {
+            ScaleMode::Stretch | ScaleMode::Fill => (self.width, self.height),
+            ScaleMode::Fit => {
+                let scale =
+                    (self.width as f64 / width as f64).min(self.height as f64 / height as f64);
+
+                (
+                    ((width as f64 * scale).round() as usize).clamp(1, self.width),
+                    ((height as f64 * scale).round() as usize).clamp(1, self.height),
+                )
+            }
+        }
+    }
+}
+
+///
+/// Crops a raw RGB8 image.
+///
+/// # Behavior
+/// Returns the pixels inside the [`CropRect`].
+///
+/// # Error
+/// Returns an error if the image's length does not match its size, or the rectangle is empty or out of the image's bounds.
+///
+pub fn crop_raw_image(
+    image: &[u8],
+    width: usize,
+    height: usize,
+    crop: CropRect,
+) -> Result<Vec<u8>> {
+    check_frame(image, width, height)?;
+
+    if crop.width == 0
+        || crop.height == 0
+        || crop.x + crop.width > width
+        || crop.y + crop.height > height
+    {
+        bail!("The crop rectangle ({crop:?}) is out of the bounds of the {width}x{height} image.");
+    }
+
+    Ok(image
+        .chunks_exact(width * 3)
+        .skip(crop.y)
+        .take(crop.height)
+        .flat_map(|row| &row[crop.x * 3..(crop.x + crop.width) * 3])
+        .copied()
+        .collect())
+}
+
+///
+/// Crops and scales a raw RGB8 image (Eg.: a frame returned by [`crate::cam::Webcam::get_frame`]).
+///
+/// # Behavior
+/// The image is cropped to [`ScaleConfig::crop`] first, then it is scaled into the target size according to [`ScaleConfig::mode`].
+/// Returns the scaled image and its width and height (See: [`ScaleConfig::output_size`]), which can be passed to the encoders (Eg.: [`super::encoding::encode_raw_image`]).
+/// If the image already has the output size it is only copied.
+///
+/// # Error
+/// Returns an error if the image's length does not match its size, the target size is `0`, or the crop rectangle is invalid.
+///
+pub fn scale_raw_image(
+    image: &[u8],
+    width: usize,
+    height: usize,
+    config: &ScaleConfig,
+) -> Result<(Vec<u8>, usize, usize)> {
+    check_frame(image, width, height)?;
+
+    if config.width == 0 || config.height == 0 {
+        bail!("Invalid target size: {}x{}.", config.width, config.height);
+    }
+
+    let (image, width, height) = match config.crop {
+        Some(crop) => (
+            crop_raw_image(image, width, height, crop)?,
+            crop.width,
+            crop.height,
+        ),
+        None => (image.to_vec(), width, height),
+    };
+
+    let (output_width, output_height) = config.output_size(width, height);
+
+    if (output_width, output_height) == (width, height) {
+        return Ok((image, width, height));
+    }
+
+    let Some(frame) = RgbImage::from_raw(width as u32, height as u32, image) else {
+        bail!("Failed to create an image buffer from the frame.");
+    };
+
+    let scaled = match config.mode {
+        ScaleMode::Stretch | ScaleMode::Fit => imageops::resize(
+            &frame,
+            output_width as u32,
+            output_height as u32,
+            config.filter.into(),
+        ),
+        ScaleMode::Fill => {
+            let scale =
+                (output_width as f64 / width as f64).max(output_height as f64 / height as f64);
+
+            //Scale to cover the target, then crop the center
+            let (cover_width, cover_height) = (
+                ((width as f64 * scale).ceil() as u32).max(output_width as u32),
+                ((height as f64 * scale).ceil() as u32).max(output_height as u32),
+            );
+
+            let covered = imageops::resize(&frame, cover_width, cover_height, config.filter.into());
+
+            imageops::crop_imm(
+                &covered,
+                (cover_width - output_width as u32) / 2,
+                (cover_height - output_height as u32) / 2,
+                output_width as u32,
+                output_height as u32,
+            )
+            .to_image()
+        }
+    };
+
+    Ok((scaled.into_raw(), output_width, output_height))
+}
+
+///
+/// Crops and scales a frame returned by [`crate::cam::Webcam::get_frame`].
+///
+/// # Behavior
+/// This is the same as [`scale_raw_image`], but it takes and returns the size of the frame as a [`Size_`].
+///
+/// # Error
+/// Returns an error if the frame's length does not match its size, the target size is `0`, or the crop rectangle is invalid.
+///
+#[cfg(feature = "opencv")]
+pub fn scale_frame(
+    image: &[u8],
+    size: Size_<i32>,
+    config: &ScaleConfig,
+) -> Result<(Vec<u8>, Size_<i32>)> {
+    if size.width < 0 || size.height < 0 {
+        bail!("Invalid frame size: {}x{}.", size.width, size.height);
+    }
+
+    let (scaled, width, height) =
+        scale_raw_image(image, size.width as usize, size.height as usize, config)?;
+
+    Ok((scaled, Size_::new(width as i32, height as i32)))
+}
+
+/// Checks whether the length of a frame matches its size.
+fn check_frame(image: &[u8], width: usize, height: usize) -> Result<()> {
+    if width == 0 || height == 0 || image.len() != width * height * 3 {
+        bail!(
+            "Expected {} bytes for a {width}x{height} RGB8 frame, got {}.",
+            width * height * 3,
+            image.len()
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,271 @@
+//! Provides streaming AV1 video encoding via [`rav1e`], so successive frames (Eg.: from [`crate::cam::Webcam::get_frame`]) are predicted from each other instead of being encoded as standalone AVIF images.
+
+use anyhow::{bail, Result};
+use rav1e::prelude::*;
+
+use super::yuv::rgb_to_yuv420;
+
+/// The settings of an [`Av1VideoEncoder`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VideoEncoderConfig {
+    /// The width of the frames in pixels.
+    pub width: usize,
+    /// The height of the frames in pixels.
+    pub height: usize,
+    /// The frame rate of the stream in frames per second.
+    pub frame_rate: u32,
+    /// The maximum distance between two keyframes in frames (the GOP size).
+    pub keyframe_interval: u64,
+    /// The target bitrate in bits per second, if this is `None` every frame is encoded with the `quantizer`.
+    pub bitrate: Option<u32>,
+    /// The base quantizer (`0..=255`) in constant quality mode, lower is better quality.
+    pub quantizer: u8,
+    /// The speed preset (`0..=10`), higher is faster with worse compression.
+    pub speed: u8,
+    /// The count of threads the encoder uses, `0` means the global [`rayon`](https://docs.rs/rayon) pool.
+    pub threads: usize,
+}
+
+impl Default for VideoEncoderConfig {
+    /// A 640x480 stream at 30 fps with a keyframe every 2 seconds, at 500 kbps and speed 9.
+    fn default() -> Self {
+        Self {
+            width: 640,
+            height: 480,
+            frame_rate: 30,
+            keyframe_interval: 60,
+            bitrate: Some(500_000),
+            quantizer: 100,
+            speed: 9,
+            threads: 0,
+        }
+    }
+}
+
+/// An encoded frame of an AV1 video stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EncodedVideoFrame {
+    /// The encoded temporal unit (low overhead bitstream format OBUs).
+    pub data: Vec<u8>,
+    /// The index of the input frame this packet shows.
+    pub frame_number: u64,
+    /// Whether the frame is a keyframe, the decoding can be started (or resynchronized) at keyframes.
+    pub keyframe: bool,
+}
+
+///
+/// A streaming AV1 video encoder.
+///
+/// # Behavior
+/// The encoder emits a keyframe at least every [`VideoEncoderConfig::keyframe_interval`] frames, the other frames are inter frames which reference the previous ones.
+/// The encoder runs in low latency mode (no frame reordering, minimal lookahead), it only holds back the last few frames, which are returned by the later calls or by [`Av1VideoEncoder::flush`].
+///
+/// # Information
+/// The frames are converted from RGB8 to full range BT.601 YUV 4:2:0 before encoding.
+///
+pub struct Av1VideoEncoder {
+    context: Context<u8>,
+    config: VideoEncoderConfig,
+    //Whether the next frame should be a keyframe
+    force_keyframe: bool,
+    //The count of frames sent into the encoder
+    frame_count: u64,
+}
+
+impl std::fmt::Debug for Av1VideoEncoder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Av1VideoEncoder")
+            .field("config", &self.config)
+            .field("force_keyframe", &self.force_keyframe)
+            .field("frame_count", &self.frame_count)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Av1VideoEncoder {
+    ///
+    /// Creates a new [`Av1VideoEncoder`] from a [`VideoEncoderConfig`].
+    ///
+    /// # Error
+    /// Returns an error if the configuration is invalid (Eg.: the frame size or the frame rate is `0`).
+    ///
+    pub fn new(config: VideoEncoderConfig) -> Result<Self> {
+        if config.width == 0 || config.height == 0 {
+            bail!("Invalid frame size: {}x{}.", config.width, config.height);
+        }
+
+        if config.frame_rate == 0 {
+            bail!("The frame rate must not be 0.");
+        }
+
+        let mut encoder_config = EncoderConfig::with_speed_preset(config.speed);
+
+        encoder_config.width = config.width;
+        encoder_config.height = config.height;
+        encoder_config.time_base = Rational::new(1, config.frame_rate as u64);
+        encoder_config.bit_depth = 8;
+        encoder_config.chroma_sampling = ChromaSampling::Cs420;
+        encoder_config.pixel_range = PixelRange::Full;
+        encoder_config.color_description = Some(ColorDescription {
+            color_primaries: ColorPrimaries::BT709,
+            transfer_characteristics: TransferCharacteristics::SRGB,
+            matrix_coefficients: MatrixCoefficients::BT601,
+        });
+        encoder_config.low_latency = true;
+        //Keyframes are only placed by the interval or on request
+        encoder_config.set_key_frame_interval(0, config.keyframe_interval.max(1));
+        encoder_config.speed_settings.scene_detection_mode = SceneDetectionSpeed::None;
+        encoder_config.speed_settings.rdo_lookahead_frames = 1;
+        encoder_config.quantizer = config.quantizer as usize;
+        encoder_config.bitrate = config
+            .bitrate
+            .map(|bitrate| bitrate.min(i32::MAX as u32) as i32)
+            .unwrap_or_default();
+
+        let context = Config::new()
+            .with_encoder_config(encoder_config)
+            .with_threads(config.threads)
+            .new_context()?;
+
+        Ok(Self {
+            context,
+            config,
+            force_keyframe: false,
+            frame_count: 0,
+        })
+    }
+
+    ///
+    /// Encodes an RGB8 frame.
+    ///
+    /// # Behavior
+    /// Returns the packets the encoder has finished, this is empty for the first few frames of the stream (while the encoder fills its lookahead), then it is usually a single packet.
+    /// If [`Av1VideoEncoder::force_keyframe`] was called before, the frame is encoded as a keyframe.
+    ///
+    /// # Error
+    /// Returns an error if the frame's size does not match the encoder's configuration, or the encoding failed.
+    ///
+    pub fn encode_frame(
+        &mut self,
+        image: &[u8],
+        width: usize,
+        height: usize,
+    ) -> Result<Vec<EncodedVideoFrame>> {
+        if width != self.config.width || height != self.config.height {
+            bail!(
+                "The frame's size ({width}x{height}) does not match the encoder's size ({}x{}).",
+                self.config.width,
+                self.config.height
+            );
+        }
+
+        if image.len() != width * height * 3 {
+            bail!(
+                "Expected {} bytes for a {width}x{height} RGB8 frame, got {}.",
+                width * height * 3,
+                image.len()
+            );
+        }
+
+        let mut frame = self.context.new_frame();
+
+        for (plane, data) in frame
+            .planes
+            .iter_mut()
+            .zip(rgb_to_yuv420(image, width, height))
+        {
+            let stride = if plane.cfg.xdec == 0 {
+                width
+            } else {
+                width.div_ceil(2)
+            };
+
+            plane.copy_from_raw_u8(&data, stride, 1);
+        }
+
+        let parameters = FrameParameters {
+            frame_type_override: if self.force_keyframe {
+                FrameTypeOverride::Key
+            } else {
+                FrameTypeOverride::No
+            },
+            ..Default::default()
+        };
+
+        self.context.send_frame((frame, parameters))?;
+
+        self.force_keyframe = false;
+        self.frame_count += 1;
+
+        self.receive_packets()
+    }
+
+    ///
+    /// Requests the next frame to be encoded as a keyframe.
+    ///
+    /// # Behavior
+    /// This should be called when a receiver lost packets or joined the stream, so it can start decoding again.
+    ///
+    pub fn force_keyframe(&mut self) {
+        self.force_keyframe = true;
+    }
+
+    ///
+    /// Flushes the encoder, this ends the stream.
+    ///
+    /// # Behavior
+    /// Returns the packets of the frames which were still in the encoder.
+    /// Frames can not be encoded after flushing, a new [`Av1VideoEncoder`] has to be created.
+    ///
+    /// # Error
+    /// Returns an error if the encoding failed.
+    ///
+    pub fn flush(&mut self) -> Result<Vec<EncodedVideoFrame>> {
+        self.context.flush();
+
+        self.receive_packets()
+    }
+
+    ///
+    /// Returns the AV1 codec configuration record (`av1C`) of the stream.
+    ///
+    /// # Information
+    /// This is needed for muxing the stream into a container (Eg.: MP4, WebM), the packets themselves contain the sequence header on keyframes.
+    ///
+    pub fn sequence_header(&self) -> Vec<u8> {
+        self.context.container_sequence_header()
+    }
+
+    /// Returns the [`VideoEncoderConfig`] the encoder was created with.
+    pub fn config(&self) -> &VideoEncoderConfig {
+        &self.config
+    }
+
+    /// Returns the count of frames sent into the encoder.
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    /// Drains every finished packet from the encoder.
+    fn receive_packets(&mut self) -> Result<Vec<EncodedVideoFrame>> {
+        let mut encoded_frames = vec![];
+
+        loop {
+            match self.context.receive_packet() {
+                Ok(packet) => encoded_frames.push(EncodedVideoFrame {
+                    data: packet.data,
+                    frame_number: packet.input_frameno,
+                    keyframe: packet.frame_type == FrameType::KEY,
+                }),
+                //A frame was processed without finishing a packet
+                Err(EncoderStatus::Encoded) => continue,
+                Err(EncoderStatus::NeedMoreData | EncoderStatus::LimitReached) => break,
+                Err(err) => bail!("Failed to encode the AV1 frame: {err}."),
+            }
+        }
+
+        Ok(encoded_frames)
+    }
+}
@@ -0,0 +1,60 @@
+//! Provides the conversions between the RGB frames of the crate and the planar YUV frames of the AV1 codec.
+
+///
+/// Converts an RGB8 image into full range BT.601 YUV 4:2:0 planes.
+///
+/// # Behavior
+/// Returns the Y, U and V planes, the chroma planes are half the size of the image (rounded up), every chroma sample is the average of a 2x2 block.
+///
+pub(crate) fn rgb_to_yuv420(image: &[u8], width: usize, height: usize) -> [Vec<u8>; 3] {
+    let chroma_width = width.div_ceil(2);
+    let chroma_height = height.div_ceil(2);
+
+    let mut y_plane = Vec::with_capacity(width * height);
+    //The sum of the chroma samples of every 2x2 block and the count of the pixels in it
+    let mut u_sums = vec![0i32; chroma_width * chroma_height];
+    let mut v_sums = vec![0i32; chroma_width * chroma_height];
+    let mut counts = vec![0i32; chroma_width * chroma_height];
+
+    for (idx, pixel) in image.chunks_exact(3).take(width * height).enumerate() {
+        let (r, g, b) = (pixel[0] as i32, pixel[1] as i32, pixel[2] as i32);
+
+        //Fixed point BT.601 coefficients (scaled by 2^16)
+        let y = (19595 * r + 38470 * g + 7471 * b + 32768) >> 16;
+        let u = ((-11059 * r - 21709 * g + 32768 * b + 32768) >> 16) + 128;
+        let v = ((32768 * r - 27439 * g - 5329 * b + 32768) >> 16) + 128;
+
+        y_plane.push(y.clamp(0, 255) as u8);
+
+        let chroma_idx = (idx / width / 2) * chroma_width + (idx % width) / 2;
+        u_sums[chroma_idx] += u;
+        v_sums[chroma_idx] += v;
+        counts[chroma_idx] += 1;
+    }
+
+    let average = |sums: Vec<i32>| {
+        sums.iter()
+            .zip(&counts)
+            .map(|(sum, count)| ((sum + count / 2) / (*count).max(1)).clamp(0, 255) as u8)
+            .collect::<Vec<u8>>()
+    };
+
+    [y_plane, average(u_sums), average(v_sums)]
+}
+
+///
+/// Converts a 10 bit RGB pixel into full range BT.601 YCbCr (for 4:4:4 images).
+///
+/// # Behavior
+/// The samples are expected to be in `0..=1023`, the returned samples are in the same range.
+///
+pub(crate) fn rgb10_to_444([r, g, b]: [u16; 3]) -> [u16; 3] {
+    let (r, g, b) = (r as i32, g as i32, b as i32);
+
+    //Same coefficients as the 8 bit conversion, with the chroma centered on 512
+    let y = (19595 * r + 38470 * g + 7471 * b + 32768) >> 16;
+    let u = ((-11059 * r - 21709 * g + 32768 * b + 32768) >> 16) + 512;
+    let v = ((32768 * r - 27439 * g - 5329 * b + 32768) >> 16) + 512;
+
+    [y, u, v].map(|sample| sample.clamp(0, 1023) as u16)
+}
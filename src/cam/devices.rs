@@ -0,0 +1,137 @@
+//! Provides the enumeration of the available cameras and their capabilities, to list them on device settings screens.
+
+use opencv::videoio::{VideoCapture, VideoCaptureTraitConst, CAP_ANY};
+
+use super::Webcam;
+
+/// The highest camera index [`list_cameras_def`] probes.
+pub const DEFAULT_MAX_CAMERA_INDEX: i32 = 10;
+
+/// The resolutions [`list_cameras`] requests from the cameras, to find out which ones they support.
+pub const COMMON_RESOLUTIONS: [(u32, u32); 8] = [
+    (320, 240),
+    (640, 360),
+    (640, 480),
+    (800, 600),
+    (1280, 720),
+    (1920, 1080),
+    (2560, 1440),
+    (3840, 2160),
+];
+
+/// The information of an available camera.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CameraInfo {
+    /// The index of the camera, which can be passed to [`Webcam::new`] (with the same api preference) or [`Webcam::new_def`].
+    pub index: i32,
+    /// The name of the backend api which opened the camera (Eg.: `V4L2`, `MSMF`).
+    pub backend: String,
+    /// The name of the camera, `None` if the backend does not expose it.
+    pub name: Option<String>,
+    /// The resolutions the camera supports from [`COMMON_RESOLUTIONS`], sorted by their pixel count.
+    pub resolutions: Vec<(u32, u32)>,
+}
+
+///
+/// Lists the available cameras.
+///
+/// # Behavior
+/// Probes the camera indices from `0` to `max_index` (inclusive) with the [api_preference](https://docs.rs/opencv/0.93.4/opencv/videoio/enum.VideoCaptureAPIs.html), indices which can not be opened are skipped.
+/// Every camera found is opened once to query its backend and supported resolutions, then it is released before probing the next index.
+///
+/// # Information
+/// Cameras which are already in use by another application (or another [`Webcam`]) might not be listed.
+/// Probing the resolutions takes some time for every camera, as the cameras have to switch between them.
+///
+pub fn list_cameras(api_preference: i32, max_index: i32) -> Vec<CameraInfo> {
+    (0..=max_index)
+        .filter_map(|index| probe_camera(index, api_preference))
+        .collect()
+}
+
+///
+/// Lists the available cameras with the default api preference ([`CAP_ANY`]).
+///
+/// # Behavior
+/// Probes the camera indices from `0` to [`DEFAULT_MAX_CAMERA_INDEX`], see [`list_cameras`].
+///
+pub fn list_cameras_def() -> Vec<CameraInfo> {
+    list_cameras(CAP_ANY, DEFAULT_MAX_CAMERA_INDEX)
+}
+
+impl Webcam {
+    ///
+    /// Finds the resolutions the camera supports out of the candidates.
+    ///
+    /// # Behavior
+    /// Requests every candidate resolution and records the resolution the camera switched to, as cameras pick their closest supported resolution.
+    /// Returns the resolutions found (deduplicated and sorted by their pixel count), then restores the resolution the camera had before.
+    ///
+    /// # Error
+    /// Returns an error if the camera's resolution could not be queried.
+    ///
+    pub fn supported_resolutions(
+        &mut self,
+        candidates: &[(u32, u32)],
+    ) -> anyhow::Result<Vec<(u32, u32)>> {
+        let original = (self.width()?, self.height()?);
+        let mut resolutions = vec![original];
+
+        for (width, height) in candidates {
+            //The camera switching to a different resolution is not an error here
+            let _ = self.set_resolution(*width, *height);
+
+            let actual = (self.width()?, self.height()?);
+
+            if actual.0 != 0 && actual.1 != 0 && !resolutions.contains(&actual) {
+                resolutions.push(actual);
+            }
+        }
+
+        let _ = self.set_resolution(original.0, original.1);
+
+        resolutions.sort_by_key(|(width, height)| (*width as u64 * *height as u64, *width));
+
+        Ok(resolutions)
+    }
+}
+
+/// Opens a camera index and queries its information, returns `None` if there is no camera at the index.
+fn probe_camera(index: i32, api_preference: i32) -> Option<CameraInfo> {
+    let video_capture = VideoCapture::new(index, api_preference).ok()?;
+
+    if !video_capture.is_opened().unwrap_or(false) {
+        return None;
+    }
+
+    let mut webcam = Webcam(video_capture);
+
+    let backend = webcam.get_backend_name().unwrap_or_default();
+    let name = camera_name(index, &backend);
+    let resolutions = webcam
+        .supported_resolutions(&COMMON_RESOLUTIONS)
+        .unwrap_or_default();
+
+    //Free the camera before the next index is opened
+    let _ = webcam.release();
+
+    Some(CameraInfo {
+        index,
+        backend,
+        name,
+        resolutions,
+    })
+}
+
+/// Reads the name of a camera, only V4L2 exposes it (through sysfs).
+fn camera_name(index: i32, backend: &str) -> Option<String> {
+    if !cfg!(target_os = "linux") || backend != "V4L2" {
+        return None;
+    }
+
+    std::fs::read_to_string(format!("/sys/class/video4linux/video{index}/name"))
+        .ok()
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
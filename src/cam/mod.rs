@@ -1,5 +1,9 @@
 //! Offers the ability to receive camera input.
 
+pub mod devices;
+pub mod properties;
+pub mod stream;
+
 use anyhow::bail;
 use opencv::{
     core::{Mat, MatTraitConst, MatTraitConstManual, Size_},
@@ -0,0 +1,285 @@
+//! Provides typed access to the capture properties (Eg.: resolution, frame rate, pixel format) of a [`Webcam`].
+
+use std::fmt::Display;
+
+use anyhow::{bail, Result};
+use opencv::videoio::{
+    VideoCaptureTrait, VideoCaptureTraitConst, CAP_PROP_AUTOFOCUS, CAP_PROP_EXPOSURE,
+    CAP_PROP_FOURCC, CAP_PROP_FPS, CAP_PROP_FRAME_HEIGHT, CAP_PROP_FRAME_WIDTH,
+};
+
+use super::Webcam;
+
+/// The largest difference between a requested and an applied frame rate, which still counts as applied.
+const FPS_TOLERANCE: f64 = 0.5;
+
+/// The largest difference between a requested and an applied exposure, which still counts as applied.
+const EXPOSURE_TOLERANCE: f64 = 0.01;
+
+/// A [FOURCC](https://en.wikipedia.org/wiki/FourCC) code, which identifies the pixel format the camera sends its frames in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FourCc(pub [u8; 4]);
+
+impl FourCc {
+    /// Motion JPEG, most cameras can only send high resolutions at high frame rates in this format.
+    pub const MJPG: Self = Self(*b"MJPG");
+    /// Uncompressed YUV 4:2:2.
+    pub const YUYV: Self = Self(*b"YUYV");
+    /// Uncompressed YUV 4:2:0.
+    pub const NV12: Self = Self(*b"NV12");
+    /// H.264 compressed frames.
+    pub const H264: Self = Self(*b"H264");
+
+    /// Creates a [`FourCc`] from the packed code of a capture property.
+    pub fn from_code(code: u32) -> Self {
+        Self(code.to_le_bytes())
+    }
+
+    /// Returns the packed code of the [`FourCc`], as it is passed to the capture properties.
+    pub fn code(&self) -> u32 {
+        u32::from_le_bytes(self.0)
+    }
+}
+
+impl Display for FourCc {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", String::from_utf8_lossy(&self.0))
+    }
+}
+
+impl Webcam {
+    ///
+    /// Gets the width of the frames the camera delivers.
+    ///
+    /// # Error
+    /// Returns an error if the backend could not be queried.
+    ///
+    pub fn width(&self) -> Result<u32> {
+        Ok(self.0.get(CAP_PROP_FRAME_WIDTH)? as u32)
+    }
+
+    ///
+    /// Gets the height of the frames the camera delivers.
+    ///
+    /// # Error
+    /// Returns an error if the backend could not be queried.
+    ///
+    pub fn height(&self) -> Result<u32> {
+        Ok(self.0.get(CAP_PROP_FRAME_HEIGHT)? as u32)
+    }
+
+    ///
+    /// Gets the frame rate of the camera.
+    ///
+    /// # Information
+    /// Some backends return `0` if they do not know the frame rate.
+    ///
+    /// # Error
+    /// Returns an error if the backend could not be queried.
+    ///
+    pub fn fps(&self) -> Result<f64> {
+        Ok(self.0.get(CAP_PROP_FPS)?)
+    }
+
+    ///
+    /// Gets the pixel format the camera sends its frames in.
+    ///
+    /// # Information
+    /// The frames returned by [`Webcam::get_frame`] are always RGB8, this is the format between the camera and the backend.
+    ///
+    /// # Error
+    /// Returns an error if the backend could not be queried.
+    ///
+    pub fn fourcc(&self) -> Result<FourCc> {
+        Ok(FourCc::from_code(self.0.get(CAP_PROP_FOURCC)? as u32))
+    }
+
+    ///
+    /// Gets the exposure of the camera.
+    ///
+    /// # Information
+    /// The unit of the exposure depends on the backend (Eg.: V4L2 uses 100µs units, MSMF uses log2 seconds).
+    ///
+    /// # Error
+    /// Returns an error if the backend could not be queried.
+    ///
+    pub fn exposure(&self) -> Result<f64> {
+        Ok(self.0.get(CAP_PROP_EXPOSURE)?)
+    }
+
+    ///
+    /// Gets whether the camera's auto-focus is enabled.
+    ///
+    /// # Error
+    /// Returns an error if the backend could not be queried.
+    ///
+    pub fn autofocus(&self) -> Result<bool> {
+        Ok(self.0.get(CAP_PROP_AUTOFOCUS)? != 0.)
+    }
+
+    ///
+    /// Requests a frame width from the camera.
+    ///
+    /// # Behavior
+    /// Cameras only support a list of resolutions, so the backend might pick a different one. Use [`Webcam::set_resolution`] to request both sides at once.
+    ///
+    /// # Error
+    /// Returns an error if the backend ignored the request, or picked a different width. The error contains the width the camera delivers.
+    ///
+    pub fn set_width(&mut self, width: u32) -> Result<()> {
+        self.set_property("width", CAP_PROP_FRAME_WIDTH, width as f64, 0.)
+    }
+
+    ///
+    /// Requests a frame height from the camera.
+    ///
+    /// # Behavior
+    /// Cameras only support a list of resolutions, so the backend might pick a different one. Use [`Webcam::set_resolution`] to request both sides at once.
+    ///
+    /// # Error
+    /// Returns an error if the backend ignored the request, or picked a different height. The error contains the height the camera delivers.
+    ///
+    pub fn set_height(&mut self, height: u32) -> Result<()> {
+        self.set_property("height", CAP_PROP_FRAME_HEIGHT, height as f64, 0.)
+    }
+
+    ///
+    /// Requests a resolution from the camera.
+    ///
+    /// # Behavior
+    /// Both sides are requested before checking what the camera applied, as some backends only switch the resolution once both sides are set.
+    ///
+    /// # Error
+    /// Returns an error if the backend ignored the request, or picked a different resolution. The error contains the resolution the camera delivers.
+    ///
+    pub fn set_resolution(&mut self, width: u32, height: u32) -> Result<()> {
+        let width_accepted = self.0.set(CAP_PROP_FRAME_WIDTH, width as f64)?;
+        let height_accepted = self.0.set(CAP_PROP_FRAME_HEIGHT, height as f64)?;
+
+        if !width_accepted || !height_accepted {
+            bail!(
+                "The {} backend ignored the requested resolution ({width}x{height}).",
+                self.get_backend_name()?
+            );
+        }
+
+        let (actual_width, actual_height) = (self.width()?, self.height()?);
+
+        if (actual_width, actual_height) != (width, height) {
+            bail!(
+                "The {} backend did not apply the requested resolution ({width}x{height}), the camera delivers {actual_width}x{actual_height}.",
+                self.get_backend_name()?
+            );
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Requests a frame rate from the camera.
+    ///
+    /// # Behavior
+    /// The frame rate is only available if the camera supports it at the current resolution and pixel format, so this should be set after them.
+    ///
+    /// # Error
+    /// Returns an error if the backend ignored the request, or picked a different frame rate. The error contains the frame rate the camera delivers.
+    ///
+    pub fn set_fps(&mut self, fps: f64) -> Result<()> {
+        self.set_property("frame rate", CAP_PROP_FPS, fps, FPS_TOLERANCE)
+    }
+
+    ///
+    /// Requests a pixel format from the camera (Eg.: [`FourCc::MJPG`] for high resolutions at high frame rates).
+    ///
+    /// # Error
+    /// Returns an error if the backend ignored the request, or the camera kept a different format. The error contains the format the camera delivers.
+    ///
+    pub fn set_fourcc(&mut self, fourcc: FourCc) -> Result<()> {
+        if !self.0.set(CAP_PROP_FOURCC, fourcc.code() as f64)? {
+            bail!(
+                "The {} backend ignored the requested pixel format ({fourcc}).",
+                self.get_backend_name()?
+            );
+        }
+
+        let actual = self.fourcc()?;
+
+        if actual != fourcc {
+            bail!(
+                "The {} backend did not apply the requested pixel format ({fourcc}), the camera delivers {actual}.",
+                self.get_backend_name()?
+            );
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Requests a manual exposure from the camera.
+    ///
+    /// # Information
+    /// The unit of the exposure depends on the backend (See: [`Webcam::exposure`]). Some backends need auto exposure to be disabled first.
+    ///
+    /// # Error
+    /// Returns an error if the backend ignored the request, or applied a different exposure. The error contains the exposure the camera uses.
+    ///
+    pub fn set_exposure(&mut self, exposure: f64) -> Result<()> {
+        self.set_property("exposure", CAP_PROP_EXPOSURE, exposure, EXPOSURE_TOLERANCE)
+    }
+
+    ///
+    /// Enables or disables the camera's auto-focus.
+    ///
+    /// # Error
+    /// Returns an error if the backend ignored the request, or the camera does not support switching the auto-focus.
+    ///
+    pub fn set_autofocus(&mut self, enabled: bool) -> Result<()> {
+        if !self
+            .0
+            .set(CAP_PROP_AUTOFOCUS, if enabled { 1. } else { 0. })?
+        {
+            bail!(
+                "The {} backend ignored the auto-focus request.",
+                self.get_backend_name()?
+            );
+        }
+
+        if self.autofocus()? != enabled {
+            bail!(
+                "The {} backend did not {} the auto-focus.",
+                self.get_backend_name()?,
+                if enabled { "enable" } else { "disable" }
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Sets a property and checks whether the backend applied it.
+    fn set_property(
+        &mut self,
+        name: &str,
+        property: i32,
+        value: f64,
+        tolerance: f64,
+    ) -> Result<()> {
+        if !self.0.set(property, value)? {
+            bail!(
+                "The {} backend ignored the requested {name} ({value}).",
+                self.get_backend_name()?
+            );
+        }
+
+        let actual = self.0.get(property)?;
+
+        if (actual - value).abs() > tolerance {
+            bail!(
+                "The {} backend did not apply the requested {name} ({value}), the camera uses {actual}.",
+                self.get_backend_name()?
+            );
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,164 @@
+//! Provides background capturing from a [`Webcam`], so the consumers (Eg.: encoders, UI) can take the newest frame at their own pace.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::Instant,
+};
+
+use anyhow::{anyhow, bail};
+use opencv::core::Size_;
+use tokio::sync::watch;
+
+use super::Webcam;
+
+/// A frame captured by a [`WebcamStream`].
+#[derive(Debug, Clone)]
+pub struct CapturedFrame {
+    /// The RGB8 bytes of the frame (See: [`Webcam::get_frame`]).
+    pub image: Vec<u8>,
+    /// The size of the frame.
+    pub size: Size_<i32>,
+    /// The index of the frame since the stream was started, gaps mean the consumer skipped the frames in between.
+    pub frame_number: u64,
+    /// The time the frame was captured at.
+    pub captured_at: Instant,
+}
+
+/// The receiving half of a [`WebcamStream`], it holds `None` until the first frame is captured.
+pub type FrameReceiver = watch::Receiver<Option<Arc<CapturedFrame>>>;
+
+///
+/// Captures frames from a [`Webcam`] on a background thread.
+///
+/// # Behavior
+/// The capture thread reads the frames (and converts them to RGB8) as fast as the camera delivers them, and publishes every frame through a [`tokio::sync::watch`] channel.
+/// The channel only holds the newest frame, so frames which were not taken before the next one arrived are dropped, instead of queueing up behind a slow consumer.
+/// The capture stops when the [`WebcamStream`] is dropped (or [`WebcamStream::stop`] is called), or when reading from the [`Webcam`] fails.
+///
+/// # Information
+/// Dropping the stream blocks until the capture thread has finished reading its current frame, this takes at most a frame interval.
+///
+#[derive(Debug)]
+pub struct WebcamStream {
+    receiver: FrameReceiver,
+    //Tells the capture thread to stop
+    stop_signal: Arc<AtomicBool>,
+    //The capture thread returns the webcam and the error which stopped it
+    handle: Option<JoinHandle<(Webcam, anyhow::Result<()>)>>,
+}
+
+impl WebcamStream {
+    ///
+    /// Starts capturing from the [`Webcam`] on a new thread.
+    ///
+    /// # Behavior
+    /// The [`Webcam`] is moved to the capture thread, it can be taken back with [`WebcamStream::stop`].
+    ///
+    /// # Error
+    /// Returns an error if the capture thread could not be spawned.
+    ///
+    pub fn new(mut webcam: Webcam) -> anyhow::Result<Self> {
+        let (sender, receiver) = watch::channel(None);
+        let stop_signal = Arc::new(AtomicBool::new(false));
+        let stop_signal_clone = stop_signal.clone();
+
+        let handle = std::thread::Builder::new()
+            .name("webcam-capture".to_string())
+            .spawn(move || {
+                let mut frame_number = 0;
+
+                while !stop_signal_clone.load(Ordering::Relaxed) {
+                    let (image, size) = match webcam.get_frame() {
+                        Ok(frame) => frame,
+                        Err(err) => return (webcam, Err(err)),
+                    };
+
+                    //An empty frame means the camera was disconnected
+                    if image.is_empty() {
+                        return (webcam, Err(anyhow!("The webcam returned an empty frame.")));
+                    }
+
+                    //Replaces the previous frame, even if nobody has seen it
+                    sender.send_replace(Some(Arc::new(CapturedFrame {
+                        image,
+                        size,
+                        frame_number,
+                        captured_at: Instant::now(),
+                    })));
+
+                    frame_number += 1;
+                }
+
+                (webcam, Ok(()))
+            })?;
+
+        Ok(Self {
+            receiver,
+            stop_signal,
+            handle: Some(handle),
+        })
+    }
+
+    ///
+    /// Creates a new receiver of the frames.
+    ///
+    /// # Behavior
+    /// Every receiver tracks which frame it has seen, so [`watch::Receiver::changed`] can be awaited for the next frame.
+    /// [`watch::Receiver::changed`] returns an error once the capture has stopped.
+    ///
+    pub fn subscribe(&self) -> FrameReceiver {
+        self.receiver.clone()
+    }
+
+    ///
+    /// Returns the newest frame.
+    ///
+    /// # Behavior
+    /// This does not block, it returns `None` if no frame was captured yet.
+    ///
+    pub fn latest_frame(&self) -> Option<Arc<CapturedFrame>> {
+        self.receiver.borrow().clone()
+    }
+
+    /// Returns whether the capture thread is still running.
+    pub fn is_running(&self) -> bool {
+        self.handle
+            .as_ref()
+            .is_some_and(|handle| !handle.is_finished())
+    }
+
+    ///
+    /// Stops the capture and returns the [`Webcam`].
+    ///
+    /// # Behavior
+    /// Blocks until the capture thread has finished, the receivers are notified that the stream has ended.
+    /// Returns the [`Webcam`] with the error which stopped the capture thread before (Eg.: the camera was disconnected), so the [`Webcam`] is kept even if the capture failed.
+    ///
+    /// # Error
+    /// Returns an error if the capture thread panicked, the [`Webcam`] is lost in this case.
+    ///
+    pub fn stop(mut self) -> anyhow::Result<(Webcam, anyhow::Result<()>)> {
+        let Some(stopped) = self.join() else {
+            bail!("The capture thread panicked.");
+        };
+
+        Ok(stopped)
+    }
+
+    /// Signals the capture thread to stop and waits for it.
+    fn join(&mut self) -> Option<(Webcam, anyhow::Result<()>)> {
+        self.stop_signal.store(true, Ordering::Relaxed);
+
+        self.handle.take()?.join().ok()
+    }
+}
+
+impl Drop for WebcamStream {
+    fn drop(&mut self) {
+        self.join();
+    }
+}
@@ -0,0 +1,71 @@
+//! Enables raw sample decoding from FLAC.
+
+use std::io::Cursor;
+
+use anyhow::{bail, Result};
+use claxon::frame::FrameReader;
+
+use crate::io::{EncoderType, SoundPacket};
+
+///
+/// Decodes a FLAC [`SoundPacket`] into raw samples.
+///
+/// # Behavior
+/// Returns the interleaved samples of the packet, every FLAC frame is independent so the packets can be decoded in any order.
+///
+/// # Error
+/// Returns an error if the [`SoundPacket`] was not encoded with FLAC, or it is corrupted (Eg.: its CRC does not match).
+///
+pub fn decode_sample_set_size_flac(sound_packet: SoundPacket) -> Result<Vec<f32>> {
+    let EncoderType::Flac(bits_per_sample) = sound_packet.encoder_type else {
+        bail!(
+            "Can not decode a {:?} packet with the FLAC decoder.",
+            sound_packet.encoder_type
+        );
+    };
+
+    if !(1..=32).contains(&bits_per_sample) {
+        bail!("Invalid bit depth: {bits_per_sample}.");
+    }
+
+    let mut reader = FrameReader::new(Cursor::new(&sound_packet.bytes));
+
+    let Some(block) = reader.read_next_or_eof(vec![])? else {
+        bail!("The FLAC packet does not contain a frame.");
+    };
+
+    if block.channels() != sound_packet.channels {
+        bail!(
+            "The FLAC frame has {} channels, but the packet has {}.",
+            block.channels(),
+            sound_packet.channels
+        );
+    }
+
+    let scale = ((1i64 << (bits_per_sample - 1)) - 1) as f32;
+    let mut samples = Vec::with_capacity((block.duration() * block.channels()) as usize);
+
+    for index in 0..block.duration() {
+        for channel in 0..block.channels() {
+            samples.push(block.sample(channel, index) as f32 / scale);
+        }
+    }
+
+    Ok(samples)
+}
+
+///
+/// Decodes a list of FLAC [`SoundPacket`]-s, into one raw sample.
+///
+/// # Error
+/// Returns an error if a [`SoundPacket`] was not encoded with FLAC, or it is corrupted.
+///
+pub fn decode_samples_flac(sound_packets: Vec<SoundPacket>) -> Result<Vec<f32>> {
+    let mut samples = vec![];
+
+    for sound_packet in sound_packets {
+        samples.extend(decode_sample_set_size_flac(sound_packet)?);
+    }
+
+    Ok(samples)
+}
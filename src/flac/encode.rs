@@ -0,0 +1,740 @@
+//! Enables lossless raw sample encoding to FLAC.
+
+use anyhow::{bail, Result};
+
+use crate::io::{EncoderType, SoundPacket};
+
+use super::{DEFAULT_BLOCK_SIZE, MAX_BLOCK_SIZE, SUPPORTED_BITS_PER_SAMPLE};
+
+/// The highest fixed predictor order defined by FLAC.
+const MAX_FIXED_ORDER: usize = 4;
+
+/// The highest residual partition order tried by the encoder.
+const MAX_PARTITION_ORDER: u32 = 8;
+
+/// The highest Rice parameter of the 5 bit parameter residual coding method.
+const MAX_RICE_PARAMETER: u32 = 30;
+
+//Subframe headers (zero padding, type, no wasted bits)
+const SUBFRAME_CONSTANT: u64 = 0x00;
+const SUBFRAME_VERBATIM: u64 = 0x02;
+const SUBFRAME_FIXED: u64 = 0x10;
+
+///
+/// A lossless FLAC encoder which can be fed with audio in pieces.
+///
+/// # Behavior
+/// Every [`SoundPacket`] created by the encoder contains a single FLAC frame, which can be decoded on its own.
+/// Samples which do not make up a complete block are buffered until the next [`FlacEncoder::push`], the remaining samples are encoded as a shorter block by [`FlacEncoder::flush`].
+/// The samples are quantized to the encoder's bit depth, this is the only lossy step, the quantized samples are restored exactly by the decoder.
+///
+/// # Information
+/// The frames use fixed linear predictors with Rice coded residuals, stereo frames pick the best of independent, left/side, right/side and mid/side coding.
+///
+#[derive(Debug)]
+pub struct FlacEncoder {
+    sample_rate: u32,
+    channels: u8,
+    bits_per_sample: u8,
+    //Samples per channel in a block
+    block_size: usize,
+    //The index of the next frame
+    frame_number: u64,
+    //The interleaved samples which did not make up a complete block yet
+    leftover: Vec<f32>,
+}
+
+impl FlacEncoder {
+    ///
+    /// Creates a new [`FlacEncoder`] with the [`DEFAULT_BLOCK_SIZE`].
+    ///
+    /// # Error
+    /// Returns an error if the channel count is not `1..=8`, the bit depth is not supported or the sample rate is invalid.
+    ///
+    pub fn new(sample_rate: u32, channels: u8, bits_per_sample: u8) -> Result<Self> {
+        Self::with_block_size(sample_rate, channels, bits_per_sample, DEFAULT_BLOCK_SIZE)
+    }
+
+    ///
+    /// Creates a new [`FlacEncoder`] with the given block size (samples per channel in a frame).
+    ///
+    /// # Error
+    /// Returns an error if the channel count is not `1..=8`, the bit depth is not supported, the sample rate is invalid or the block size is not `16..=65535`.
+    ///
+    pub fn with_block_size(
+        sample_rate: u32,
+        channels: u8,
+        bits_per_sample: u8,
+        block_size: usize,
+    ) -> Result<Self> {
+        if !(1..=8).contains(&channels) {
+            bail!("Invalid channel count: {channels}, FLAC supports 1 to 8 channels.");
+        }
+
+        if !SUPPORTED_BITS_PER_SAMPLE.contains(&bits_per_sample) {
+            bail!(
+                "Unsupported bit depth: {bits_per_sample}, the supported bit depths are {SUPPORTED_BITS_PER_SAMPLE:?}."
+            );
+        }
+
+        if sample_rate == 0 || sample_rate >= 1 << 20 {
+            bail!("Invalid sample rate: {sample_rate}.");
+        }
+
+        if !(16..=MAX_BLOCK_SIZE).contains(&block_size) {
+            bail!(
+                "Invalid block size: {block_size}, FLAC supports 16 to {MAX_BLOCK_SIZE} samples."
+            );
+        }
+
+        Ok(Self {
+            sample_rate,
+            channels,
+            bits_per_sample,
+            block_size,
+            frame_number: 0,
+            leftover: Vec::with_capacity(block_size * channels as usize),
+        })
+    }
+
+    ///
+    /// Pushes raw samples (f32) into the encoder, and encodes every completed block.
+    ///
+    /// # Behavior
+    /// The samples are interleaved, this is the format [`crate::io::record`] captures in.
+    /// Returns the [`SoundPacket`]-s of the blocks completed by the samples, this can be empty if the samples did not complete a block.
+    ///
+    /// # Error
+    /// Returns an error if some kind of error occured during the encoding process.
+    ///
+    pub fn push(&mut self, samples: &[f32]) -> Result<Vec<SoundPacket>> {
+        let samples_per_block = self.block_size * self.channels as usize;
+        let mut sound_packets = vec![];
+
+        self.leftover.extend_from_slice(samples);
+
+        let complete_blocks = self.leftover.len() / samples_per_block;
+
+        for index in 0..complete_blocks {
+            let start = index * samples_per_block;
+
+            let sound_packet = self.encode_block_at(start..start + samples_per_block)?;
+
+            sound_packets.push(sound_packet);
+        }
+
+        self.leftover.drain(..complete_blocks * samples_per_block);
+
+        Ok(sound_packets)
+    }
+
+    ///
+    /// Encodes the buffered samples into a (shorter) block.
+    ///
+    /// # Behavior
+    /// Returns `None` if there weren't enough buffered samples for a frame (a single sample per channel).
+    /// Unlike lossy codecs the block is not padded, so the decoded audio has the exact same length as the input.
+    ///
+    /// # Error
+    /// Returns an error if some kind of error occured during the encoding process.
+    ///
+    pub fn flush(&mut self) -> Result<Option<SoundPacket>> {
+        let channels = self.channels as usize;
+        let samples = self.leftover.len() / channels * channels;
+
+        if samples == 0 {
+            self.leftover.clear();
+
+            return Ok(None);
+        }
+
+        let sound_packet = self.encode_block_at(0..samples)?;
+
+        self.leftover.clear();
+
+        Ok(Some(sound_packet))
+    }
+
+    ///
+    /// Encodes a block of interleaved samples into a [`SoundPacket`] right away.
+    ///
+    /// # Behavior
+    /// The buffered samples are not touched, the block gets the next frame number.
+    /// The block must have the encoder's block size, as every frame header declares a fixed block size stream. Only the last block of a stream can be shorter, which is encoded by [`FlacEncoder::flush`].
+    ///
+    /// # Error
+    /// Returns an error if the sample count does not match the block size and the channel count.
+    ///
+    pub fn encode_block(&mut self, samples: &[f32]) -> Result<SoundPacket> {
+        let samples_per_block = self.block_size * self.channels as usize;
+
+        if samples.len() != samples_per_block {
+            bail!(
+                "Expected {samples_per_block} samples for a block of {} samples per channel, got {}.",
+                self.block_size,
+                samples.len()
+            );
+        }
+
+        self.encode_samples(samples)
+    }
+
+    ///
+    /// Encodes a block of interleaved samples, the block can be shorter than the block size.
+    ///
+    /// # Error
+    /// Returns an error if the sample count is not a multiple of the channel count, or the block is longer than 65535 samples per channel.
+    ///
+    fn encode_samples(&mut self, samples: &[f32]) -> Result<SoundPacket> {
+        let channels = self.channels as usize;
+
+        if samples.is_empty() || !samples.len().is_multiple_of(channels) {
+            bail!(
+                "The sample count ({}) is not a non-zero multiple of the channel count ({channels}).",
+                samples.len()
+            );
+        }
+
+        if samples.len() / channels > MAX_BLOCK_SIZE {
+            bail!("The block is longer than {MAX_BLOCK_SIZE} samples per channel.");
+        }
+
+        let scale = ((1i64 << (self.bits_per_sample - 1)) - 1) as f32;
+        let min = -(1i64 << (self.bits_per_sample - 1));
+        let max = (1i64 << (self.bits_per_sample - 1)) - 1;
+
+        //Quantize and deinterleave the samples
+        let block_size = samples.len() / channels;
+        let mut channel_samples = vec![Vec::with_capacity(block_size); channels];
+
+        for frame in samples.chunks_exact(channels) {
+            for (channel, sample) in frame.iter().enumerate() {
+                channel_samples[channel].push(((*sample * scale).round() as i64).clamp(min, max));
+            }
+        }
+
+        let bytes = encode_frame(
+            &channel_samples,
+            self.sample_rate,
+            self.bits_per_sample,
+            self.frame_number,
+        );
+
+        self.frame_number += 1;
+
+        Ok(SoundPacket {
+            encoder_type: EncoderType::Flac(self.bits_per_sample),
+            sample_rate: self.sample_rate,
+            channels: self.channels as u32,
+            bytes: bytes.into(),
+            samples_per_frame: samples.len() as u64,
+            dtx: false,
+            channel_mapping: None,
+        })
+    }
+
+    /// Returns the count of the buffered samples, which did not make up a complete block yet.
+    pub fn buffered_samples(&self) -> usize {
+        self.leftover.len()
+    }
+
+    /// Returns the block size (samples per channel in a frame).
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Returns the sample rate the encoder was created with.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Returns the channel count the encoder was created with.
+    pub fn channels(&self) -> u8 {
+        self.channels
+    }
+
+    /// Returns the bit depth the samples are quantized to.
+    pub fn bits_per_sample(&self) -> u8 {
+        self.bits_per_sample
+    }
+
+    /// Encodes a range of the buffered samples.
+    fn encode_block_at(&mut self, range: std::ops::Range<usize>) -> Result<SoundPacket> {
+        let block = std::mem::take(&mut self.leftover);
+
+        let sound_packet = self.encode_samples(&block[range]);
+
+        self.leftover = block;
+
+        sound_packet
+    }
+}
+
+///
+/// Encodes raw samples (f32) into a list of FLAC [`SoundPacket`]-s.
+///
+/// # Behavior
+/// The samples are interleaved, they are cut into blocks of [`DEFAULT_BLOCK_SIZE`], the last block is shorter instead of padded.
+///
+/// # Error
+/// Returns an error if the format is not supported by FLAC.
+///
+pub fn encode_samples_flac(
+    samples: &[f32],
+    sample_rate: u32,
+    channels: u8,
+    bits_per_sample: u8,
+) -> Result<Vec<SoundPacket>> {
+    let mut encoder = FlacEncoder::new(sample_rate, channels, bits_per_sample)?;
+
+    let mut sound_packets = encoder.push(samples)?;
+
+    sound_packets.extend(encoder.flush()?);
+
+    Ok(sound_packets)
+}
+
+/// The stereo decorrelation of a frame, the values are the channel assignments of the frame header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChannelAssignment {
+    Independent,
+    LeftSide = 0b1000,
+    RightSide = 0b1001,
+    MidSide = 0b1010,
+}
+
+/// Encodes a complete frame from the deinterleaved, quantized samples.
+fn encode_frame(
+    channel_samples: &[Vec<i64>],
+    sample_rate: u32,
+    bits_per_sample: u8,
+    frame_number: u64,
+) -> Vec<u8> {
+    let block_size = channel_samples[0].len();
+    let bits_per_sample = bits_per_sample as u32;
+
+    //Pick the smallest stereo decorrelation
+    let (assignment, subframes) = if channel_samples.len() == 2 {
+        let (left, right) = (&channel_samples[0], &channel_samples[1]);
+        let side: Vec<i64> = left.iter().zip(right).map(|(l, r)| l - r).collect();
+        let mid: Vec<i64> = left.iter().zip(right).map(|(l, r)| (l + r) >> 1).collect();
+
+        let left_subframe = encode_subframe(left, bits_per_sample);
+        let right_subframe = encode_subframe(right, bits_per_sample);
+        let side_subframe = encode_subframe(&side, bits_per_sample + 1);
+        let mid_subframe = encode_subframe(&mid, bits_per_sample);
+
+        let mut best = (
+            ChannelAssignment::Independent,
+            [&left_subframe, &right_subframe],
+        );
+
+        for candidate in [
+            (
+                ChannelAssignment::LeftSide,
+                [&left_subframe, &side_subframe],
+            ),
+            (
+                ChannelAssignment::RightSide,
+                [&side_subframe, &right_subframe],
+            ),
+            (ChannelAssignment::MidSide, [&mid_subframe, &side_subframe]),
+        ] {
+            if candidate.1[0].len() + candidate.1[1].len() < best.1[0].len() + best.1[1].len() {
+                best = candidate;
+            }
+        }
+
+        (best.0, best.1.map(Clone::clone).to_vec())
+    } else {
+        (
+            ChannelAssignment::Independent,
+            channel_samples
+                .iter()
+                .map(|samples| encode_subframe(samples, bits_per_sample))
+                .collect(),
+        )
+    };
+
+    let mut writer = BitWriter::default();
+
+    //Frame header: sync code, reserved bit, fixed blocking strategy
+    writer.write(0b1111_1111_1111_1000, 16);
+
+    let (block_size_code, block_size_extra) = match block_size {
+        192 => (0b0001, None),
+        576 | 1152 | 2304 | 4608 => (2 + (block_size / 576).trailing_zeros(), None),
+        256 | 512 | 1024 | 2048 | 4096 | 8192 | 16384 | 32768 => {
+            (8 + (block_size / 256).trailing_zeros(), None)
+        }
+        1..=256 => (0b0110, Some((block_size as u64 - 1, 8))),
+        _ => (0b0111, Some((block_size as u64 - 1, 16))),
+    };
+    writer.write(block_size_code as u64, 4);
+
+    let (sample_rate_code, sample_rate_extra) = match sample_rate {
+        88200 => (0b0001, None),
+        176400 => (0b0010, None),
+        192000 => (0b0011, None),
+        8000 => (0b0100, None),
+        16000 => (0b0101, None),
+        22050 => (0b0110, None),
+        24000 => (0b0111, None),
+        32000 => (0b1000, None),
+        44100 => (0b1001, None),
+        48000 => (0b1010, None),
+        96000 => (0b1011, None),
+        rate if rate % 1000 == 0 && rate / 1000 <= 255 => (0b1100, Some((rate as u64 / 1000, 8))),
+        rate if rate <= 65535 => (0b1101, Some((rate as u64, 16))),
+        rate if rate % 10 == 0 && rate / 10 <= 65535 => (0b1110, Some((rate as u64 / 10, 16))),
+        //The sample rate is only stored in the STREAMINFO block
+        _ => (0b0000, None),
+    };
+    writer.write(sample_rate_code, 4);
+
+    let channel_code = match assignment {
+        ChannelAssignment::Independent => channel_samples.len() as u64 - 1,
+        assignment => assignment as u64,
+    };
+    writer.write(channel_code, 4);
+
+    let sample_size_code = match bits_per_sample {
+        8 => 0b001,
+        12 => 0b010,
+        16 => 0b100,
+        20 => 0b101,
+        _ => 0b110,
+    };
+    writer.write(sample_size_code, 3);
+    writer.write(0, 1);
+
+    writer.write_utf8(frame_number);
+
+    if let Some((value, bits)) = block_size_extra {
+        writer.write(value, bits);
+    }
+
+    if let Some((value, bits)) = sample_rate_extra {
+        writer.write(value, bits);
+    }
+
+    let header_crc = crc8(writer.bytes());
+    writer.write(header_crc as u64, 8);
+
+    for subframe in subframes {
+        writer.append(&subframe);
+    }
+
+    writer.align();
+
+    let frame_crc = crc16(writer.bytes());
+    writer.write(frame_crc as u64, 16);
+
+    writer.into_bytes()
+}
+
+/// An encoded subframe, which is not byte aligned.
+#[derive(Debug, Clone)]
+struct Subframe {
+    writer: BitWriter,
+}
+
+impl Subframe {
+    /// Returns the length of the subframe in bits.
+    fn len(&self) -> usize {
+        self.writer.len()
+    }
+}
+
+/// Encodes a channel into the smallest of a constant, verbatim or fixed predictor subframe.
+fn encode_subframe(samples: &[i64], bits_per_sample: u32) -> Subframe {
+    let mut writer = BitWriter::default();
+
+    //Constant subframe
+    if samples.iter().all(|sample| *sample == samples[0]) {
+        writer.write(SUBFRAME_CONSTANT, 8);
+        writer.write_signed(samples[0], bits_per_sample);
+
+        return Subframe { writer };
+    }
+
+    //Verbatim subframe
+    let verbatim_len = 8 + samples.len() * bits_per_sample as usize;
+
+    //Fixed predictor subframes
+    let best_fixed = (0..=MAX_FIXED_ORDER.min(samples.len() - 1))
+        .map(|order| {
+            let residual = fixed_residual(samples, order);
+            let (partition_order, parameters, len) =
+                rice_partitions(&residual, samples.len(), order);
+
+            (order, residual, partition_order, parameters, len)
+        })
+        .min_by_key(|(order, _, _, _, len)| 8 + order * bits_per_sample as usize + 6 + len);
+
+    match best_fixed {
+        Some((order, residual, partition_order, parameters, len))
+            if 8 + order * bits_per_sample as usize + 6 + len < verbatim_len =>
+        {
+            writer.write(SUBFRAME_FIXED | ((order as u64) << 1), 8);
+
+            for sample in &samples[..order] {
+                writer.write_signed(*sample, bits_per_sample);
+            }
+
+            write_residual(
+                &mut writer,
+                &residual,
+                partition_order,
+                &parameters,
+                samples.len(),
+                order,
+            );
+        }
+        _ => {
+            writer.write(SUBFRAME_VERBATIM, 8);
+
+            for sample in samples {
+                writer.write_signed(*sample, bits_per_sample);
+            }
+        }
+    }
+
+    Subframe { writer }
+}
+
+/// Returns the residual of a fixed predictor, the first `order` samples (warm-up) are not included.
+fn fixed_residual(samples: &[i64], order: usize) -> Vec<i64> {
+    (order..samples.len())
+        .map(|index| {
+            let s = |offset: usize| samples[index - offset];
+
+            samples[index]
+                - match order {
+                    0 => 0,
+                    1 => s(1),
+                    2 => 2 * s(1) - s(2),
+                    3 => 3 * s(1) - 3 * s(2) + s(3),
+                    _ => 4 * s(1) - 6 * s(2) + 4 * s(3) - s(4),
+                }
+        })
+        .collect()
+}
+
+/// Maps a signed residual onto an unsigned value for Rice coding.
+fn fold(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+/// Returns the length in bits of a Rice coded partition, and its best parameter.
+fn rice_partition(residual: &[i64]) -> (u32, usize) {
+    let sum: u64 = residual.iter().map(|value| fold(*value)).sum();
+    let mean = sum / residual.len().max(1) as u64;
+
+    //The best parameter is near log2 of the mean
+    let estimate = 64 - mean.leading_zeros();
+
+    (estimate.saturating_sub(1)..=(estimate + 1).min(MAX_RICE_PARAMETER))
+        .map(|parameter| {
+            let len = residual
+                .iter()
+                .map(|value| (fold(*value) >> parameter) as usize + 1 + parameter as usize)
+                .sum::<usize>();
+
+            (parameter, len)
+        })
+        .min_by_key(|(_, len)| *len)
+        .unwrap_or((0, 0))
+}
+
+/// Searches the best partition order, returns the order, the Rice parameter of every partition and the length of the residual in bits.
+fn rice_partitions(residual: &[i64], block_size: usize, order: usize) -> (u32, Vec<u32>, usize) {
+    let mut best: Option<(u32, Vec<u32>, usize)> = None;
+
+    for partition_order in 0..=MAX_PARTITION_ORDER {
+        let partitions = 1 << partition_order;
+
+        //Every partition must have the same size, and the first one must be longer than the warm-up
+        if !block_size.is_multiple_of(partitions) || block_size / partitions <= order {
+            break;
+        }
+
+        let mut parameters = Vec::with_capacity(partitions);
+        //Coding method and partition order
+        let mut len = 6;
+        let mut start = 0;
+
+        for partition in 0..partitions {
+            let end = (partition + 1) * (block_size / partitions) - order;
+            let (parameter, partition_len) = rice_partition(&residual[start..end]);
+
+            parameters.push(parameter);
+            len += 5 + partition_len;
+            start = end;
+        }
+
+        if best.as_ref().is_none_or(|(_, _, best_len)| len < *best_len) {
+            best = Some((partition_order, parameters, len));
+        }
+    }
+
+    best.unwrap_or((0, vec![0], usize::MAX / 2))
+}
+
+/// Writes a residual with the 5 bit Rice parameter coding method.
+fn write_residual(
+    writer: &mut BitWriter,
+    residual: &[i64],
+    partition_order: u32,
+    parameters: &[u32],
+    block_size: usize,
+    order: usize,
+) {
+    writer.write(0b01, 2);
+    writer.write(partition_order as u64, 4);
+
+    let partitions = 1 << partition_order;
+    let mut start = 0;
+
+    for (partition, parameter) in parameters.iter().enumerate().take(partitions) {
+        let end = (partition + 1) * (block_size / partitions) - order;
+
+        writer.write(*parameter as u64, 5);
+
+        for value in &residual[start..end] {
+            let value = fold(*value);
+
+            writer.write_unary(value >> parameter);
+            writer.write(value & ((1 << parameter) - 1), *parameter);
+        }
+
+        start = end;
+    }
+}
+
+/// A big endian bit writer.
+#[derive(Debug, Clone, Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    //The bits not yet written into `bytes`, aligned to the right
+    buffer: u64,
+    buffered: u32,
+}
+
+impl BitWriter {
+    /// Writes the lowest `bits` bits of `value` (`bits` <= 32).
+    fn write(&mut self, value: u64, bits: u32) {
+        if bits == 0 {
+            return;
+        }
+
+        self.buffer = (self.buffer << bits) | (value & ((1 << bits) - 1));
+        self.buffered += bits;
+
+        while self.buffered >= 8 {
+            self.buffered -= 8;
+            self.bytes.push((self.buffer >> self.buffered) as u8);
+        }
+    }
+
+    /// Writes a signed value in two's complement with `bits` bits.
+    fn write_signed(&mut self, value: i64, bits: u32) {
+        self.write(value as u64, bits);
+    }
+
+    /// Writes a value in unary coding (zeros terminated by a one).
+    fn write_unary(&mut self, mut value: u64) {
+        while value >= 32 {
+            self.write(0, 32);
+            value -= 32;
+        }
+
+        self.write(1, value as u32 + 1);
+    }
+
+    /// Writes a value with the UTF-8 like coding of the frame numbers.
+    fn write_utf8(&mut self, value: u64) {
+        if value < 0x80 {
+            self.write(value, 8);
+
+            return;
+        }
+
+        //Count of continuation bytes
+        let continuation = (1..=6)
+            .find(|bytes| value < 1 << (6 + 5 * bytes))
+            .unwrap_or(6);
+
+        let lead_marker = (0xFF00u64 >> (continuation + 1)) & 0xFF;
+        self.write(lead_marker | (value >> (6 * continuation)), 8);
+
+        for byte in (0..continuation).rev() {
+            self.write(0x80 | ((value >> (6 * byte)) & 0x3F), 8);
+        }
+    }
+
+    /// Appends the bits of another writer.
+    fn append(&mut self, other: &Subframe) {
+        for byte in &other.writer.bytes {
+            self.write(*byte as u64, 8);
+        }
+
+        self.write(other.writer.buffer, other.writer.buffered);
+    }
+
+    /// Pads the stream with zeros to a byte boundary.
+    fn align(&mut self) {
+        if self.buffered > 0 {
+            self.write(0, 8 - self.buffered);
+        }
+    }
+
+    /// Returns the complete bytes written so far.
+    fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Returns the length of the written bits.
+    fn len(&self) -> usize {
+        self.bytes.len() * 8 + self.buffered as usize
+    }
+
+    /// Returns the written bytes, the stream must be aligned.
+    fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// The CRC-8 of the frame header (polynomial `x^8 + x^2 + x + 1`).
+fn crc8(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0, |mut crc, byte| {
+        crc ^= byte;
+
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            };
+        }
+
+        crc
+    })
+}
+
+/// The CRC-16 of the frame (polynomial `x^16 + x^15 + x^2 + 1`).
+fn crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0, |mut crc, byte| {
+        crc ^= (*byte as u16) << 8;
+
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x8005
+            } else {
+                crc << 1
+            };
+        }
+
+        crc
+    })
+}
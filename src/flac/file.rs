@@ -0,0 +1,297 @@
+//! Enables saving and loading [`SoundPacket`]-s as standalone FLAC (`.flac`) files.
+
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Cursor, Read, Write},
+    path::Path,
+};
+
+use anyhow::{bail, Result};
+use bytes::Bytes;
+use claxon::frame::FrameReader;
+
+use crate::io::{EncoderType, SoundPacket};
+
+use super::SUPPORTED_BITS_PER_SAMPLE;
+
+/// The marker at the start of every FLAC stream.
+const FLAC_MARKER: &[u8; 4] = b"fLaC";
+
+/// The metadata block type of `STREAMINFO`.
+const BLOCK_STREAMINFO: u8 = 0;
+
+/// The length of the `STREAMINFO` block's body.
+const STREAMINFO_LENGTH: usize = 34;
+
+/// The `STREAMINFO` metadata block of a FLAC stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FlacStreamInfo {
+    /// The smallest block size (samples per channel) in the stream, excluding the last block.
+    pub min_block_size: u16,
+    /// The largest block size (samples per channel) in the stream.
+    pub max_block_size: u16,
+    /// The size of the smallest frame in bytes, `0` if it is unknown.
+    pub min_frame_size: u32,
+    /// The size of the largest frame in bytes, `0` if it is unknown.
+    pub max_frame_size: u32,
+    /// The sample rate of the stream.
+    pub sample_rate: u32,
+    /// The channel count of the stream.
+    pub channels: u8,
+    /// The bit depth of the stream.
+    pub bits_per_sample: u8,
+    /// The count of samples per channel in the stream, `0` if it is unknown.
+    pub total_samples: u64,
+    /// The MD5 checksum of the decoded samples, all zeros if it is unknown (this crate does not compute it).
+    pub md5: [u8; 16],
+}
+
+impl FlacStreamInfo {
+    ///
+    /// Creates the [`FlacStreamInfo`] describing a list of FLAC [`SoundPacket`]-s.
+    ///
+    /// # Error
+    /// Returns an error if the list is empty, a [`SoundPacket`] was not encoded with FLAC or the packets' formats differ.
+    ///
+    pub fn from_sound_packets(sound_packets: &[SoundPacket]) -> Result<Self> {
+        let Some(first_packet) = sound_packets.first() else {
+            bail!("There are no packets to describe.");
+        };
+
+        let EncoderType::Flac(bits_per_sample) = first_packet.encoder_type else {
+            bail!(
+                "Can not write a {:?} packet into a FLAC file.",
+                first_packet.encoder_type
+            );
+        };
+
+        let mut block_sizes = Vec::with_capacity(sound_packets.len());
+
+        for sound_packet in sound_packets {
+            if sound_packet.encoder_type != first_packet.encoder_type
+                || sound_packet.sample_rate != first_packet.sample_rate
+                || sound_packet.channels != first_packet.channels
+            {
+                bail!("The packets of a FLAC file must have the same format.");
+            }
+
+            block_sizes.push(sound_packet.samples_per_frame / sound_packet.channels.max(1) as u64);
+        }
+
+        //The last block is allowed to be shorter
+        let full_blocks = &block_sizes[..(block_sizes.len() - 1).max(1)];
+
+        Ok(Self {
+            min_block_size: full_blocks.iter().min().copied().unwrap_or_default() as u16,
+            max_block_size: block_sizes.iter().max().copied().unwrap_or_default() as u16,
+            min_frame_size: sound_packets
+                .iter()
+                .map(|sound_packet| sound_packet.bytes.len() as u32)
+                .min()
+                .unwrap_or_default(),
+            max_frame_size: sound_packets
+                .iter()
+                .map(|sound_packet| sound_packet.bytes.len() as u32)
+                .max()
+                .unwrap_or_default(),
+            sample_rate: first_packet.sample_rate,
+            channels: first_packet.channels as u8,
+            bits_per_sample,
+            total_samples: block_sizes.iter().sum(),
+            md5: [0; 16],
+        })
+    }
+
+    /// Returns the body of the `STREAMINFO` block.
+    pub fn to_bytes(&self) -> [u8; STREAMINFO_LENGTH] {
+        let mut bytes = [0; STREAMINFO_LENGTH];
+
+        bytes[0..2].copy_from_slice(&self.min_block_size.to_be_bytes());
+        bytes[2..4].copy_from_slice(&self.max_block_size.to_be_bytes());
+        bytes[4..7].copy_from_slice(&self.min_frame_size.to_be_bytes()[1..]);
+        bytes[7..10].copy_from_slice(&self.max_frame_size.to_be_bytes()[1..]);
+
+        //Sample rate (20 bits), channels - 1 (3 bits), bits per sample - 1 (5 bits), total samples (36 bits)
+        let packed = (self.sample_rate as u64 & 0xF_FFFF) << 44
+            | ((self.channels as u64 - 1) & 0x7) << 41
+            | ((self.bits_per_sample as u64 - 1) & 0x1F) << 36
+            | (self.total_samples & 0xF_FFFF_FFFF);
+        bytes[10..18].copy_from_slice(&packed.to_be_bytes());
+
+        bytes[18..].copy_from_slice(&self.md5);
+
+        bytes
+    }
+
+    ///
+    /// Parses the body of a `STREAMINFO` block.
+    ///
+    /// # Error
+    /// Returns an error if the block is too short.
+    ///
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < STREAMINFO_LENGTH {
+            bail!("The STREAMINFO block is too short: {} bytes.", bytes.len());
+        }
+
+        let packed = u64::from_be_bytes(bytes[10..18].try_into()?);
+
+        Ok(Self {
+            min_block_size: u16::from_be_bytes([bytes[0], bytes[1]]),
+            max_block_size: u16::from_be_bytes([bytes[2], bytes[3]]),
+            min_frame_size: u32::from_be_bytes([0, bytes[4], bytes[5], bytes[6]]),
+            max_frame_size: u32::from_be_bytes([0, bytes[7], bytes[8], bytes[9]]),
+            sample_rate: (packed >> 44) as u32,
+            channels: ((packed >> 41) & 0x7) as u8 + 1,
+            bits_per_sample: ((packed >> 36) & 0x1F) as u8 + 1,
+            total_samples: packed & 0xF_FFFF_FFFF,
+            md5: bytes[18..STREAMINFO_LENGTH].try_into()?,
+        })
+    }
+}
+
+///
+/// Writes a list of FLAC [`SoundPacket`]-s as a FLAC stream into a writer.
+///
+/// # Behavior
+/// The stream contains a single `STREAMINFO` metadata block followed by the frames.
+///
+/// # Error
+/// Returns an error if the packets could not be described (see [`FlacStreamInfo::from_sound_packets`]) or the writer failed.
+///
+pub fn write_flac<W: Write>(writer: &mut W, sound_packets: &[SoundPacket]) -> Result<()> {
+    let stream_info = FlacStreamInfo::from_sound_packets(sound_packets)?;
+
+    writer.write_all(FLAC_MARKER)?;
+
+    //Last metadata block flag, block type and length
+    writer.write_all(&[0x80 | BLOCK_STREAMINFO, 0, 0, STREAMINFO_LENGTH as u8])?;
+    writer.write_all(&stream_info.to_bytes())?;
+
+    for sound_packet in sound_packets {
+        writer.write_all(&sound_packet.bytes)?;
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
+
+///
+/// Reads a FLAC stream from a reader into a list of [`SoundPacket`]-s.
+///
+/// # Behavior
+/// Every frame of the stream becomes a [`SoundPacket`], the metadata blocks other than `STREAMINFO` are skipped.
+///
+/// # Error
+/// Returns an error if the reader failed, the stream is not a valid FLAC stream or its bit depth is not supported.
+///
+pub fn read_flac<R: Read>(reader: &mut R) -> Result<(FlacStreamInfo, Vec<SoundPacket>)> {
+    let mut buffer = vec![];
+    reader.read_to_end(&mut buffer)?;
+
+    //The packets share the memory of the stream
+    let bytes = Bytes::from(buffer);
+
+    if !bytes.starts_with(FLAC_MARKER) {
+        bail!("Invalid FLAC stream marker.");
+    }
+
+    let mut position = FLAC_MARKER.len();
+    let mut stream_info = None;
+
+    //Read the metadata blocks
+    loop {
+        let Some(header) = bytes.get(position..position + 4) else {
+            bail!("The FLAC stream ended inside the metadata.");
+        };
+
+        let is_last = header[0] & 0x80 != 0;
+        let block_type = header[0] & 0x7F;
+        let length = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+
+        position += 4;
+
+        let Some(body) = bytes.get(position..position + length) else {
+            bail!("The FLAC stream ended inside a metadata block.");
+        };
+
+        if block_type == BLOCK_STREAMINFO {
+            stream_info = Some(FlacStreamInfo::from_bytes(body)?);
+        }
+
+        position += length;
+
+        if is_last {
+            break;
+        }
+    }
+
+    let Some(stream_info) = stream_info else {
+        bail!("The FLAC stream does not have a STREAMINFO block.");
+    };
+
+    if !SUPPORTED_BITS_PER_SAMPLE.contains(&stream_info.bits_per_sample) {
+        bail!(
+            "Unsupported bit depth: {}, the supported bit depths are {SUPPORTED_BITS_PER_SAMPLE:?}.",
+            stream_info.bits_per_sample
+        );
+    }
+
+    //Split the frames at the positions where the frame reader stopped
+    let mut cursor = Cursor::new(&bytes[position..]);
+    let mut sound_packets = vec![];
+    let mut buffer = vec![];
+
+    loop {
+        let start = cursor.position() as usize;
+
+        let Some(block) = FrameReader::new(&mut cursor).read_next_or_eof(buffer)? else {
+            break;
+        };
+
+        let end = cursor.position() as usize;
+
+        sound_packets.push(SoundPacket {
+            encoder_type: EncoderType::Flac(stream_info.bits_per_sample),
+            sample_rate: stream_info.sample_rate,
+            channels: block.channels(),
+            bytes: bytes.slice(position + start..position + end),
+            samples_per_frame: (block.duration() * block.channels()) as u64,
+            dtx: false,
+            channel_mapping: None,
+        });
+
+        buffer = block.into_buffer();
+    }
+
+    Ok((stream_info, sound_packets))
+}
+
+///
+/// Writes a list of FLAC [`SoundPacket`]-s into a FLAC (`.flac`) file.
+///
+/// # Behavior
+/// Creates (or truncates) the file at `path`.
+///
+/// # Error
+/// Returns an error if the list is empty, the file could not be written or the packets are not FLAC packets of the same format.
+///
+pub fn write_flac_file(path: impl AsRef<Path>, sound_packets: &[SoundPacket]) -> Result<()> {
+    write_flac(&mut BufWriter::new(File::create(path)?), sound_packets)
+}
+
+///
+/// Reads a FLAC (`.flac`) file into a list of [`SoundPacket`]-s.
+///
+/// # Behavior
+/// Returns the [`FlacStreamInfo`] of the file alongside the packets.
+/// Only files whose frame headers contain the bit depth can be read, this is true for the files written by this crate and most encoders.
+///
+/// # Error
+/// Returns an error if the file could not be read or if it is not a valid FLAC file.
+///
+pub fn read_flac_file(path: impl AsRef<Path>) -> Result<(FlacStreamInfo, Vec<SoundPacket>)> {
+    read_flac(&mut BufReader::new(File::open(path)?))
+}
@@ -0,0 +1,17 @@
+//! This feature allows lossless FLAC encoding and decoding, for archiving captured audio without quality loss (Eg.: recordings which are edited later).
+
+pub mod decode;
+pub mod encode;
+pub mod file;
+
+/// Re-export the claxon crate, which is used for decoding.
+pub use claxon;
+
+/// The default block size (samples per channel in a frame) of the encoder.
+pub const DEFAULT_BLOCK_SIZE: usize = 4096;
+
+/// The largest block size a FLAC frame can have.
+pub const MAX_BLOCK_SIZE: usize = 65535;
+
+/// The bit depths which can be encoded and decoded without a `STREAMINFO` block.
+pub const SUPPORTED_BITS_PER_SAMPLE: [u8; 5] = [8, 12, 16, 20, 24];
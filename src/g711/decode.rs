@@ -0,0 +1,55 @@
+//! Enables raw sample decoding from G.711.
+
+use anyhow::{bail, Result};
+
+use crate::io::{EncoderType, SoundPacket};
+
+use super::law::decode_sample;
+
+///
+/// Decodes a G.711 [`SoundPacket`] into raw samples.
+///
+/// # Behavior
+/// Returns the 8kHz mono samples of the packet, G.711 is stateless so the packets can be decoded in any order.
+///
+/// # Error
+/// Returns an error if the [`SoundPacket`] was not encoded with G.711, or its metadata does not match its bytes.
+///
+pub fn decode_sample_set_size_g711(sound_packet: SoundPacket) -> Result<Vec<f32>> {
+    let EncoderType::G711(law) = sound_packet.encoder_type else {
+        bail!(
+            "Can not decode a {:?} packet with the G.711 decoder.",
+            sound_packet.encoder_type
+        );
+    };
+
+    if sound_packet.samples_per_frame != sound_packet.bytes.len() as u64 {
+        bail!(
+            "Invalid samples per frame ({}) for a G.711 packet of {} bytes.",
+            sound_packet.samples_per_frame,
+            sound_packet.bytes.len()
+        );
+    }
+
+    Ok(sound_packet
+        .bytes
+        .iter()
+        .map(|byte| decode_sample(law, *byte))
+        .collect())
+}
+
+///
+/// Decodes a list of G.711 [`SoundPacket`]-s, into one raw sample.
+///
+/// # Error
+/// Returns an error if a [`SoundPacket`] was not encoded with G.711, or it is corrupted.
+///
+pub fn decode_samples_g711(sound_packets: Vec<SoundPacket>) -> Result<Vec<f32>> {
+    let mut samples = vec![];
+
+    for sound_packet in sound_packets {
+        samples.extend(decode_sample_set_size_g711(sound_packet)?);
+    }
+
+    Ok(samples)
+}
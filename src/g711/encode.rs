@@ -0,0 +1,145 @@
+//! Enables raw sample encoding to G.711.
+
+use anyhow::{bail, Result};
+
+use crate::io::{resample::resample, EncoderType, G711Law, SoundPacket};
+
+use super::{law::encode_sample, G711_SAMPLE_RATE};
+
+/// The cutoff frequency (in Hz) of the anti-aliasing filter, the upper edge of the telephony band.
+pub const ANTI_ALIAS_CUTOFF: f32 = 3400.;
+
+/// The width (in Hz) of the anti-aliasing filter's transition band, the filter fully attenuates the frequencies above `ANTI_ALIAS_CUTOFF + ANTI_ALIAS_TRANSITION / 2` (the 8kHz Nyquist frequency).
+const ANTI_ALIAS_TRANSITION: f32 = 1200.;
+
+///
+/// Encode a frame of raw samples with G.711.
+///
+/// # Behavior
+/// The samples must be 8kHz mono samples, every sample is encoded into a single byte.
+/// Every frame duration is valid, the usual telephony frame is 20 ms (160 samples).
+///
+/// # Error
+/// Returns an error if the frame is empty.
+///
+pub fn encode_sample_set_size_g711(law: G711Law, samples: &[f32]) -> Result<SoundPacket> {
+    if samples.is_empty() {
+        bail!("Can not encode an empty G.711 frame.");
+    }
+
+    Ok(SoundPacket {
+        encoder_type: EncoderType::G711(law),
+        sample_rate: G711_SAMPLE_RATE,
+        channels: 1,
+        bytes: samples
+            .iter()
+            .map(|sample| encode_sample(law, *sample))
+            .collect(),
+        samples_per_frame: samples.len() as u64,
+        dtx: false,
+        channel_mapping: None,
+    })
+}
+
+///
+/// Encodes raw samples (f32) into a list of G.711 [`SoundPacket`]-s.
+///
+/// # Behavior
+/// The samples are converted to 8kHz mono first (if they are not already), then they are cut into frames of `frame_duration`.
+/// Samples of a higher sample rate are low-pass filtered at [`ANTI_ALIAS_CUTOFF`] before the conversion, so the frequencies above 4kHz don't fold back into the telephony band.
+/// The last frame is padded with silence.
+///
+/// # Error
+/// Returns an error if the samples could not be converted, or the frame duration is shorter than a single sample.
+///
+pub fn encode_samples_g711(
+    law: G711Law,
+    samples: &[f32],
+    sample_rate: u32,
+    channels: u16,
+    frame_duration: std::time::Duration,
+) -> Result<Vec<SoundPacket>> {
+    let samples_per_frame =
+        (G711_SAMPLE_RATE as u128 * frame_duration.as_micros() / 1_000_000) as usize;
+
+    if samples_per_frame == 0 {
+        bail!("The frame duration ({frame_duration:?}) is shorter than a single G.711 sample.");
+    }
+
+    let samples = if sample_rate > G711_SAMPLE_RATE {
+        let mono_samples = resample(samples, sample_rate, channels, sample_rate, 1)?;
+
+        resample(
+            &low_pass(&mono_samples, sample_rate),
+            sample_rate,
+            1,
+            G711_SAMPLE_RATE,
+            1,
+        )?
+    } else {
+        resample(samples, sample_rate, channels, G711_SAMPLE_RATE, 1)?
+    };
+    let mut sound_packets = vec![];
+
+    for sample_chunk in samples.chunks(samples_per_frame) {
+        let sound_packet = if sample_chunk.len() < samples_per_frame {
+            let mut padded_frame = sample_chunk.to_vec();
+            padded_frame.resize(samples_per_frame, 0.);
+
+            encode_sample_set_size_g711(law, &padded_frame)?
+        } else {
+            encode_sample_set_size_g711(law, sample_chunk)?
+        };
+
+        sound_packets.push(sound_packet);
+    }
+
+    Ok(sound_packets)
+}
+
+///
+/// Low-pass filters mono samples at [`ANTI_ALIAS_CUTOFF`].
+///
+/// # Behavior
+/// The filter is a Blackman windowed-sinc FIR, its length is scaled with the sample rate to keep the transition band the same.
+/// The filter is linear phase and its delay is compensated, so the filtered samples stay aligned with the input.
+///
+fn low_pass(samples: &[f32], sample_rate: u32) -> Vec<f32> {
+    //The Blackman window needs 5.5 / transition width (relative to the sample rate) taps, the count is kept odd so the delay is a whole sample
+    let half_length = (5.5 * sample_rate as f32 / ANTI_ALIAS_TRANSITION / 2.).ceil() as usize;
+    let length = half_length * 2 + 1;
+    let cutoff = ANTI_ALIAS_CUTOFF / sample_rate as f32;
+
+    let mut taps: Vec<f32> = (0..length)
+        .map(|index| {
+            let offset = index as f32 - half_length as f32;
+            let sinc = if offset == 0. {
+                2. * cutoff
+            } else {
+                (2. * std::f32::consts::PI * cutoff * offset).sin()
+                    / (std::f32::consts::PI * offset)
+            };
+            let phase = 2. * std::f32::consts::PI * index as f32 / (length - 1) as f32;
+
+            sinc * (0.42 - 0.5 * phase.cos() + 0.08 * (2. * phase).cos())
+        })
+        .collect();
+
+    //Normalize the taps for unity gain at 0Hz
+    let sum: f32 = taps.iter().sum();
+    taps.iter_mut().for_each(|tap| *tap /= sum);
+
+    (0..samples.len())
+        .map(|index| {
+            //The samples outside of the input are treated as silence
+            let first = index.saturating_sub(half_length);
+            let last = (index + half_length).min(samples.len() - 1);
+
+            samples[first..=last]
+                .iter()
+                .zip(&taps[first + half_length - index..])
+                .map(|(sample, tap)| sample * tap)
+                .sum()
+        })
+        .collect()
+}
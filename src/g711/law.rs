@@ -0,0 +1,115 @@
+//! Provides the per sample G.711 companding functions (ITU-T G.711), and the conversions from and to the crate's f32 samples.
+
+use crate::io::G711Law;
+
+/// The bias added to the magnitude before μ-law companding.
+const MU_LAW_BIAS: i32 = 0x84;
+
+/// The largest magnitude μ-law can represent before adding the bias.
+const MU_LAW_CLIP: i32 = 32635;
+
+/// Converts a 16 bit linear sample into a μ-law byte.
+pub fn encode_mu_law(sample: i16) -> u8 {
+    let mut magnitude = sample as i32;
+    let sign = if magnitude < 0 {
+        magnitude = -magnitude;
+        0x80
+    } else {
+        0
+    };
+
+    let magnitude = magnitude.min(MU_LAW_CLIP) + MU_LAW_BIAS;
+
+    //The position of the highest set bit above the mantissa
+    let exponent = match magnitude >> 7 {
+        0 => 0,
+        segment => 31 - (segment as u32).leading_zeros() as i32,
+    };
+    let mantissa = (magnitude >> (exponent + 3)) & 0x0F;
+
+    !(sign | (exponent << 4) | mantissa) as u8
+}
+
+/// Converts a μ-law byte into a 16 bit linear sample.
+pub fn decode_mu_law(byte: u8) -> i16 {
+    let byte = !byte;
+    let exponent = ((byte >> 4) & 0x07) as i32;
+    let mantissa = (byte & 0x0F) as i32;
+
+    let magnitude = (((mantissa << 3) + MU_LAW_BIAS) << exponent) - MU_LAW_BIAS;
+
+    if byte & 0x80 != 0 {
+        -magnitude as i16
+    } else {
+        magnitude as i16
+    }
+}
+
+/// Converts a 16 bit linear sample into an A-law byte.
+pub fn encode_a_law(sample: i16) -> u8 {
+    //A-law works on 13 bit samples
+    let sample = (sample >> 3) as i32;
+
+    let (magnitude, mask) = if sample >= 0 {
+        (sample, 0xD5)
+    } else {
+        (-sample - 1, 0x55)
+    };
+
+    //The index of the first segment whose end is not below the magnitude
+    let segment = match magnitude >> 5 {
+        0 => 0,
+        segment => 32 - (segment as u32).leading_zeros() as i32,
+    };
+
+    if segment >= 8 {
+        return (0x7F ^ mask) as u8;
+    }
+
+    let mantissa = if segment < 2 {
+        (magnitude >> 1) & 0x0F
+    } else {
+        (magnitude >> segment) & 0x0F
+    };
+
+    (((segment << 4) | mantissa) ^ mask) as u8
+}
+
+/// Converts an A-law byte into a 16 bit linear sample.
+pub fn decode_a_law(byte: u8) -> i16 {
+    let byte = byte ^ 0x55;
+    let segment = ((byte & 0x70) >> 4) as i32;
+    let mut magnitude = ((byte & 0x0F) as i32) << 4;
+
+    match segment {
+        0 => magnitude += 8,
+        1 => magnitude += 0x108,
+        _ => magnitude = (magnitude + 0x108) << (segment - 1),
+    }
+
+    if byte & 0x80 != 0 {
+        magnitude as i16
+    } else {
+        -magnitude as i16
+    }
+}
+
+/// Converts an f32 sample (`-1.0..=1.0`) into a G.711 byte, the sample is clamped.
+pub fn encode_sample(law: G711Law, sample: f32) -> u8 {
+    let sample = (sample.clamp(-1., 1.) * i16::MAX as f32).round() as i16;
+
+    match law {
+        G711Law::MuLaw => encode_mu_law(sample),
+        G711Law::ALaw => encode_a_law(sample),
+    }
+}
+
+/// Converts a G.711 byte into an f32 sample (`-1.0..=1.0`).
+pub fn decode_sample(law: G711Law, byte: u8) -> f32 {
+    let sample = match law {
+        G711Law::MuLaw => decode_mu_law(byte),
+        G711Law::ALaw => decode_a_law(byte),
+    };
+
+    sample as f32 / i16::MAX as f32
+}
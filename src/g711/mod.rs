@@ -0,0 +1,8 @@
+//! This feature allows G.711 (μ-law/A-law) encoding and decoding, for interoperability with telephony networks (Eg.: PSTN gateways).
+
+pub mod decode;
+pub mod encode;
+pub mod law;
+
+/// The sample rate of every G.711 stream.
+pub const G711_SAMPLE_RATE: u32 = 8000;
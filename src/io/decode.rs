@@ -0,0 +1,239 @@
+//! Offers decoding of [`SoundPacket`]-s regardless of the codec they were encoded with.
+
+use anyhow::{bail, Result};
+
+use super::{resample::Resampler, EncoderType, SoundPacket};
+
+#[cfg(feature = "opus")]
+use super::ChannelMapping;
+#[cfg(feature = "opus")]
+use crate::opus::{
+    decode::{create_opus_decoder_for_packet, decode_sample_set_size_opus},
+    multistream::{
+        create_opus_multistream_decoder_for_packet, decode_sample_set_size_opus_multistream,
+        OpusMultistreamDecoder,
+    },
+};
+
+/// The format of the packets an [`opus`](crate::opus) decoder was created for, the decoder is recreated if it changes.
+#[cfg(feature = "opus")]
+#[derive(Debug, Clone, PartialEq)]
+struct OpusStreamFormat {
+    sample_rate: u32,
+    channels: u32,
+    channel_mapping: Option<ChannelMapping>,
+}
+
+/// The state of the [`opus`](crate::opus) decoder, which is kept between packets.
+#[cfg(feature = "opus")]
+#[derive(Debug)]
+enum OpusDecoderState {
+    Single(opus::Decoder),
+    Multistream(OpusMultistreamDecoder),
+}
+
+///
+/// Decodes [`SoundPacket`]-s of any [`EncoderType`] into the same sample rate and channel count.
+///
+/// # Behavior
+/// The packets are dispatched to the decoder of their [`EncoderType`], then the decoded samples are converted with a [`Resampler`] to the output format.
+/// The [`opus`](crate::opus) decoder keeps its state between packets, it is only recreated if the format of the packets changes (Eg.: a call switches to another codec and back).
+/// Discontinuous transmission (DTX) packets are decoded into comfort noise.
+///
+/// # Information
+/// The [`Resampler`] is flushed and recreated when the sample rate or the channel count of the packets changes, so no samples are lost at the switch.
+/// The last input frame of the stream is held back until [`SoundPacketDecoder::flush`] is called.
+/// Decoding a codec needs its feature to be enabled (`opus`, `g711` or `flac`), packets of a disabled codec return an error.
+///
+#[derive(Debug)]
+pub struct SoundPacketDecoder {
+    sample_rate: u32,
+    channels: u16,
+    #[cfg(feature = "opus")]
+    opus_decoder: Option<(OpusStreamFormat, OpusDecoderState)>,
+    //The sample rate and channel count of the previous packet, and the resampler converting from it
+    resampler: Option<((u32, u32), Resampler)>,
+}
+
+impl SoundPacketDecoder {
+    ///
+    /// Creates a new [`SoundPacketDecoder`], which outputs samples of `sample_rate` and `channels`.
+    ///
+    /// # Error
+    /// Returns an error if the sample rate or the channel count is zero.
+    ///
+    pub fn new(sample_rate: u32, channels: u16) -> Result<Self> {
+        if sample_rate == 0 || channels == 0 {
+            bail!("The output sample rate and channel count must be non-zero.");
+        }
+
+        Ok(Self {
+            sample_rate,
+            channels,
+            #[cfg(feature = "opus")]
+            opus_decoder: None,
+            resampler: None,
+        })
+    }
+
+    ///
+    /// Decodes a [`SoundPacket`] and appends its samples (in the output format) to `output`.
+    ///
+    /// # Behavior
+    /// Returns the count of the appended samples, this includes the held back samples of the previous format if the format changed.
+    ///
+    /// # Error
+    /// Returns an error if the packet could not be decoded with the decoder of its [`EncoderType`], or its codec's feature is not enabled.
+    ///
+    pub fn decode_into(
+        &mut self,
+        sound_packet: SoundPacket,
+        output: &mut Vec<f32>,
+    ) -> Result<usize> {
+        let packet_format = (sound_packet.sample_rate, sound_packet.channels);
+
+        let samples = match sound_packet.encoder_type {
+            #[cfg(feature = "opus")]
+            EncoderType::Opus(fec) => self.decode_opus(sound_packet, fec)?,
+            #[cfg(feature = "g711")]
+            EncoderType::G711(_) => crate::g711::decode::decode_sample_set_size_g711(sound_packet)?,
+            #[cfg(feature = "flac")]
+            EncoderType::Flac(_) => crate::flac::decode::decode_sample_set_size_flac(sound_packet)?,
+            #[allow(unreachable_patterns)]
+            encoder_type => {
+                bail!(
+                    "Can not decode a {encoder_type:?} packet, the codec's feature is not enabled."
+                )
+            }
+        };
+
+        let start = output.len();
+
+        let resampler = match &mut self.resampler {
+            Some((format, resampler)) if *format == packet_format => resampler,
+            resampler => {
+                let channels = u16::try_from(packet_format.1)?;
+
+                //Release the end of the previous format before switching
+                if let Some((_, previous_resampler)) = resampler {
+                    previous_resampler.flush(output);
+                }
+
+                &mut resampler
+                    .insert((
+                        packet_format,
+                        Resampler::new(packet_format.0, channels, self.sample_rate, self.channels)?,
+                    ))
+                    .1
+            }
+        };
+
+        resampler.process(&samples, output);
+
+        Ok(output.len() - start)
+    }
+
+    ///
+    /// Appends the samples the [`SoundPacketDecoder`] held back to `output`, this should be called at the end of the stream.
+    ///
+    /// # Behavior
+    /// Returns the count of the appended samples. Check out [`Resampler::flush`] for more information.
+    ///
+    pub fn flush(&mut self, output: &mut Vec<f32>) -> usize {
+        let start = output.len();
+
+        if let Some((_, resampler)) = &mut self.resampler {
+            resampler.flush(output);
+        }
+
+        output.len() - start
+    }
+
+    ///
+    /// Decodes a [`SoundPacket`] into samples of the output format.
+    ///
+    /// # Behavior
+    /// Works the same way as [`SoundPacketDecoder::decode_into`].
+    ///
+    /// # Error
+    /// Returns an error if the packet could not be decoded with the decoder of its [`EncoderType`], or its codec's feature is not enabled.
+    ///
+    pub fn decode(&mut self, sound_packet: SoundPacket) -> Result<Vec<f32>> {
+        let mut samples = vec![];
+
+        self.decode_into(sound_packet, &mut samples)?;
+
+        Ok(samples)
+    }
+
+    /// Returns the output sample rate of the [`SoundPacketDecoder`].
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Returns the output channel count of the [`SoundPacketDecoder`].
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    /// Decodes an [`opus`](crate::opus) packet, with the decoder matching the packet's format.
+    #[cfg(feature = "opus")]
+    fn decode_opus(&mut self, sound_packet: SoundPacket, fec: bool) -> Result<Vec<f32>> {
+        let format = OpusStreamFormat {
+            sample_rate: sound_packet.sample_rate,
+            channels: sound_packet.channels,
+            channel_mapping: sound_packet.channel_mapping.clone(),
+        };
+
+        let decoder = match &mut self.opus_decoder {
+            Some((decoder_format, decoder)) if *decoder_format == format => decoder,
+            decoder => {
+                //Mono and stereo packets don't need the multistream decoder
+                let state = if format.channel_mapping.is_some() {
+                    OpusDecoderState::Multistream(create_opus_multistream_decoder_for_packet(
+                        &sound_packet,
+                    )?)
+                } else {
+                    OpusDecoderState::Single(create_opus_decoder_for_packet(&sound_packet)?)
+                };
+
+                &mut decoder.insert((format, state)).1
+            }
+        };
+
+        match decoder {
+            OpusDecoderState::Single(decoder) => {
+                decode_sample_set_size_opus(decoder, sound_packet, fec)
+            }
+            OpusDecoderState::Multistream(decoder) => {
+                decode_sample_set_size_opus_multistream(decoder, sound_packet, fec)
+            }
+        }
+    }
+}
+
+///
+/// Decodes a list of [`SoundPacket`]-s of any [`EncoderType`], into one raw sample of `sample_rate` and `channels`.
+///
+/// # Behavior
+/// The packets can be encoded with different codecs (Eg.: a call which fell back from [`opus`](crate::opus) to G.711), check out [`SoundPacketDecoder`] for more information.
+///
+/// # Error
+/// Returns an error if the output format is invalid, or a [`SoundPacket`] could not be decoded.
+///
+pub fn decode_samples(
+    sound_packets: Vec<SoundPacket>,
+    sample_rate: u32,
+    channels: u16,
+) -> Result<Vec<f32>> {
+    let mut decoder = SoundPacketDecoder::new(sample_rate, channels)?;
+    let mut samples = vec![];
+
+    for sound_packet in sound_packets {
+        decoder.decode_into(sound_packet, &mut samples)?;
+    }
+
+    decoder.flush(&mut samples);
+
+    Ok(samples)
+}
@@ -0,0 +1,242 @@
+//! Offers lock-free volume, mute and fade controls for audio streams.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc, Weak,
+    },
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use parking_lot::Mutex;
+
+/// The default duration of a gain ramp going from silence to full volume.
+pub const DEFAULT_FADE_DURATION: Duration = Duration::from_millis(20);
+
+///
+/// Shared gain settings of an audio stream.
+///
+/// # Behavior
+/// The [`GainControl`] can be cloned and sent between threads, every clone refers to the same settings.
+/// Changes take effect at the next device callback, the audio is ramped towards the new gain over the fade duration to avoid clicks.
+/// The settings are stored in atomics, so neither the UI nor the device callback ever waits for a lock.
+/// A single [`GainControl`] can drive multiple streams (Eg.: a recording and a playback stream), every [`GainRamp`] keeps its own current gain.
+///
+#[derive(Debug, Clone)]
+pub struct GainControl {
+    state: Arc<GainState>,
+}
+
+/// The atomic state shared between the [`GainControl`]-s and the [`GainRamp`]-s.
+#[derive(Debug)]
+struct GainState {
+    //f32 bits
+    volume: AtomicU32,
+    muted: AtomicBool,
+    faded_out: AtomicBool,
+    //Microseconds
+    fade_duration: AtomicU32,
+    //The current gain of every ramp (f32 bits), the gain their last callback ended with
+    //The lock is only taken when a ramp is created and when the current gain is read, never in the device callback
+    ramps: Mutex<Vec<Weak<AtomicU32>>>,
+}
+
+impl Default for GainControl {
+    fn default() -> Self {
+        Self::new(1.)
+    }
+}
+
+impl GainControl {
+    /// Creates a new [`GainControl`] with the given volume (linear, `1.0` is unity gain) and the [`DEFAULT_FADE_DURATION`].
+    pub fn new(volume: f32) -> Self {
+        Self {
+            state: Arc::new(GainState {
+                volume: AtomicU32::new(volume.max(0.).to_bits()),
+                muted: AtomicBool::new(false),
+                faded_out: AtomicBool::new(false),
+                fade_duration: AtomicU32::new(DEFAULT_FADE_DURATION.as_micros() as u32),
+                ramps: Mutex::new(Vec::new()),
+            }),
+        }
+    }
+
+    /// Sets the volume of the stream (linear, `1.0` is unity gain). Negative values are clamped to `0.0`.
+    pub fn set_volume(&self, volume: f32) {
+        self.state
+            .volume
+            .store(volume.max(0.).to_bits(), Ordering::Relaxed);
+    }
+
+    /// Returns the volume of the stream.
+    pub fn volume(&self) -> f32 {
+        f32::from_bits(self.state.volume.load(Ordering::Relaxed))
+    }
+
+    /// Mutes or unmutes the stream. The volume is kept while the stream is muted.
+    pub fn set_muted(&self, muted: bool) {
+        self.state.muted.store(muted, Ordering::Relaxed);
+    }
+
+    /// Returns whether the stream is muted.
+    pub fn is_muted(&self) -> bool {
+        self.state.muted.load(Ordering::Relaxed)
+    }
+
+    /// Sets how long a ramp from silence to full volume takes, this is used for every gain change.
+    pub fn set_fade_duration(&self, fade_duration: Duration) {
+        self.state.fade_duration.store(
+            fade_duration.as_micros().min(u32::MAX as u128) as u32,
+            Ordering::Relaxed,
+        );
+    }
+
+    /// Returns how long a ramp from silence to full volume takes.
+    pub fn fade_duration(&self) -> Duration {
+        Duration::from_micros(self.state.fade_duration.load(Ordering::Relaxed) as u64)
+    }
+
+    /// Fades the stream in to its volume.
+    pub fn fade_in(&self) {
+        self.state.faded_out.store(false, Ordering::Relaxed);
+    }
+
+    /// Fades the stream out to silence, the stream keeps running.
+    pub fn fade_out(&self) {
+        self.state.faded_out.store(true, Ordering::Relaxed);
+    }
+
+    ///
+    /// Fades the stream out and blocks until the fade has finished.
+    ///
+    /// # Behavior
+    /// This should be called before stopping or dropping a stream, so that the audio does not end with a click.
+    /// The function returns after twice the fade duration even if the stream did not reach silence (Eg.: the stream was paused).
+    /// If the [`GainControl`] drives multiple streams, the function waits for every stream to reach silence.
+    ///
+    pub fn fade_out_blocking(&self) {
+        self.fade_out();
+
+        let deadline = Instant::now() + self.fade_duration() * 2;
+
+        while self.current_gain() != 0. && Instant::now() < deadline {
+            sleep(Duration::from_millis(1));
+        }
+    }
+
+    ///
+    /// Returns the gain the stream's last device callback ended with.
+    ///
+    /// # Behavior
+    /// If the [`GainControl`] drives multiple streams, the highest current gain of the streams is returned.
+    /// Returns `0.0` if there are no streams (Eg.: every stream was dropped).
+    ///
+    pub fn current_gain(&self) -> f32 {
+        let mut ramps = self.state.ramps.lock();
+
+        //Forget the ramps of the dropped streams
+        ramps.retain(|ramp| ramp.strong_count() > 0);
+
+        ramps
+            .iter()
+            .filter_map(Weak::upgrade)
+            .map(|current| f32::from_bits(current.load(Ordering::Relaxed)))
+            .fold(0., f32::max)
+    }
+
+    /// Returns the gain the stream is ramping towards.
+    pub fn target_gain(&self) -> f32 {
+        if self.is_muted() || self.state.faded_out.load(Ordering::Relaxed) {
+            0.
+        } else {
+            self.volume()
+        }
+    }
+}
+
+///
+/// The per stream state of a gain ramp, this lives inside of the device callback.
+///
+/// # Behavior
+/// Every stream starts from silence, so a [`GainRamp`] fades in automaticly when the stream starts.
+/// Every [`GainRamp`] publishes its own current gain, so multiple streams can be driven by the same [`GainControl`].
+///
+#[derive(Debug)]
+pub struct GainRamp {
+    control: GainControl,
+    sample_rate: u32,
+    current: f32,
+    //The published current gain (f32 bits), see: `GainControl::current_gain`
+    published: Arc<AtomicU32>,
+}
+
+impl GainRamp {
+    /// Creates a new [`GainRamp`] for a stream with the given sample rate.
+    pub fn new(control: GainControl, sample_rate: u32) -> Self {
+        let published = Arc::new(AtomicU32::new(0f32.to_bits()));
+
+        control.state.ramps.lock().push(Arc::downgrade(&published));
+
+        Self {
+            control,
+            sample_rate,
+            current: 0.,
+            published,
+        }
+    }
+
+    ///
+    /// Applies the gain to the interleaved `samples`.
+    ///
+    /// # Behavior
+    /// The gain is moved towards the [`GainControl::target_gain`] frame by frame, every channel of a frame gets the same gain.
+    ///
+    pub fn apply(&mut self, samples: &mut [f32], channels: usize) {
+        let target = self.control.target_gain();
+        let step = self.step();
+
+        for frame in samples.chunks_mut(channels.max(1)) {
+            let gain = self.next_gain(target, step);
+
+            for sample in frame {
+                *sample *= gain;
+            }
+        }
+
+        self.store_current();
+    }
+
+    /// Returns the linear gain change of a single frame.
+    pub(crate) fn step(&self) -> f32 {
+        let fade_frames = self.control.fade_duration().as_secs_f32() * self.sample_rate as f32;
+
+        if fade_frames < 1. {
+            f32::INFINITY
+        } else {
+            1. / fade_frames
+        }
+    }
+
+    /// Moves the gain one frame closer to the `target`, returns the gain of the frame.
+    pub(crate) fn next_gain(&mut self, target: f32, step: f32) -> f32 {
+        if self.current < target {
+            self.current = (self.current + step).min(target);
+        } else if self.current > target {
+            self.current = (self.current - step).max(target);
+        }
+
+        self.current
+    }
+
+    /// Publishes the current gain to the [`GainControl`].
+    pub(crate) fn store_current(&self) {
+        self.published
+            .store(self.current.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Returns the [`GainControl`] of this ramp.
+    pub fn control(&self) -> &GainControl {
+        &self.control
+    }
+}
@@ -5,9 +5,14 @@ use ::cpal::{
     traits::{DeviceTrait, HostTrait},
     DefaultStreamConfigError, Device, Host, SupportedStreamConfig,
 };
+use bytes::Bytes;
 
+#[cfg(any(feature = "opus", feature = "g711", feature = "flac"))]
+pub mod decode;
+pub mod gain;
 pub mod playback;
 pub mod record;
+pub mod resample;
 
 /// Wrapper type for differentiating [`OutputDevice`] from [`InputDevice`] granted the user passes them in right when creating an [`AudioDevice`].
 pub type OutputDevice = Device;
@@ -18,6 +23,9 @@ pub type InputDevice = Device;
 //Re-export the cpal crate.
 pub use cpal;
 
+//Re-export the bytes crate.
+pub use bytes;
+
 /// The host's audio input and output devices.
 #[allow(missing_debug_implementations)]
 pub struct HostDevice {
@@ -64,17 +72,35 @@ pub fn get_audio_device(device: Host) -> HostDevice {
 
 /// Shows the encoder type of the packet.
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
-#[derive(Debug, deepsize::DeepSizeOf)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, deepsize::DeepSizeOf)]
+#[non_exhaustive]
 pub enum EncoderType {
     /// The encoder of this packet was [`opus`].
     /// The inner value contains whether.
     Opus(bool),
+    /// The encoder of this packet was G.711 (8kHz mono telephony audio).
+    /// The inner value contains the companding law.
+    G711(G711Law),
+    /// The encoder of this packet was FLAC (lossless).
+    /// The inner value contains the bit depth the samples were quantized to.
+    Flac(u8),
+}
+
+/// The companding law of a G.711 encoded packet.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, deepsize::DeepSizeOf)]
+pub enum G711Law {
+    /// μ-law (PCMU), used in North America and Japan.
+    MuLaw,
+    /// A-law (PCMA), used in Europe and most other countries.
+    ALaw,
 }
 
 /// The encoded sound packet.
 /// Contains useful information about the encoded packet.
-#[derive(Debug, deepsize::DeepSizeOf)]
+#[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub struct SoundPacket {
     /// The Encoder's type which this [`SoundPacket`] got encoded with.
     pub encoder_type: EncoderType,
@@ -83,8 +109,78 @@ pub struct SoundPacket {
     /// The channel count of the encoded packet.
     pub channels: u32,
     /// The bytes of the encoded sound packet.
-    pub bytes: Vec<u8>,
+    /// [`Bytes`] is reference counted, so the packet can be cloned and forwarded (Eg.: to every participant of a call) without copying its payload.
+    pub bytes: Bytes,
     /// The count of samples per frame.
     pub samples_per_frame: u64,
+    /// Whether the packet is a discontinuous transmission (DTX) frame, which only signals silence.
+    /// DTX frames don't have to be sent, the receiver generates comfort noise in their place (Eg.: with [`crate::opus::decode::decode_missing_frame_opus`]).
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub dtx: bool,
+    /// The channel mapping of a multistream packet (Eg.: surround sound), this is `None` for mono and stereo packets.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub channel_mapping: Option<ChannelMapping>,
+}
+
+impl SoundPacket {
+    ///
+    /// Creates a [`SoundPacket`] which is not a DTX frame and has no channel mapping.
+    ///
+    /// # Information
+    /// New fields may be added to [`SoundPacket`], so packets can only be created with this function outside of this crate.
+    /// The optional fields can be set after creating the packet (Eg.: `sound_packet.dtx = true`).
+    ///
+    pub fn new(
+        encoder_type: EncoderType,
+        sample_rate: u32,
+        channels: u32,
+        bytes: Bytes,
+        samples_per_frame: u64,
+    ) -> Self {
+        Self {
+            encoder_type,
+            sample_rate,
+            channels,
+            bytes,
+            samples_per_frame,
+            dtx: false,
+            channel_mapping: None,
+        }
+    }
+}
+
+//The payload is counted by its length, even if it is shared with other packets
+impl deepsize::DeepSizeOf for SoundPacket {
+    fn deep_size_of_children(&self, context: &mut deepsize::Context) -> usize {
+        self.bytes.len() + self.channel_mapping.deep_size_of_children(context)
+    }
+}
+
+/// Describes how the channels of a multistream packet are coded.
+/// A multistream packet contains multiple streams, a coupled stream codes two channels (Eg.: front left and right), an uncoupled stream codes a single channel.
+#[derive(Debug, Clone, PartialEq, Eq, deepsize::DeepSizeOf)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChannelMapping {
+    /// The channel mapping family, this defines the meaning (Eg.: position) of the channels.
+    /// `0`: mono or stereo, `1`: surround layouts in Vorbis channel order, `255`: undefined (Eg.: discrete microphones).
+    pub family: u8,
+    /// The total count of streams.
+    pub streams: u8,
+    /// The count of coupled (two channel) streams, these are the first streams of the packet.
+    pub coupled_streams: u8,
+    /// The index of the decoded channel for every output channel, `255` means the channel is silent.
+    pub mapping: Vec<u8>,
+}
+
+impl ChannelMapping {
+    /// Returns the output channel count.
+    pub fn channels(&self) -> u32 {
+        self.mapping.len() as u32
+    }
+
+    /// Returns the count of channels coded in the streams.
+    pub fn coded_channels(&self) -> u32 {
+        self.streams as u32 + self.coupled_streams as u32
+    }
 }
 
@@ -1,9 +1,25 @@
 //! Offers playback capabilities via being a middleware on [`cpal`].
 
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
 use anyhow::Result;
-use cpal::{traits::DeviceTrait, BufferSize, SizedSample, Stream, StreamConfig, StreamError};
+use cpal::{
+    traits::DeviceTrait, BufferSize, SampleFormat, SizedSample, Stream, StreamConfig, StreamError,
+    SupportedBufferSize,
+};
+use parking_lot::Mutex;
 
-use super::OutputDevice;
+use super::{
+    gain::{GainControl, GainRamp},
+    resample::Resampler,
+    OutputDevice,
+};
 
 ///
 /// Plays back audio from an [`Iterator`] to an [`OutputDevice`].
@@ -15,10 +31,38 @@ use super::OutputDevice;
 /// # Error
 /// The `error_callback` is called when an error occurs while streaming to the output.
 ///
-pub fn stream_audio<T, E, S>(
+pub fn stream_audio<T, E, S>(device: OutputDevice, error_callback: E, samples: S) -> Result<Stream>
+where
+    //This is the type of the `Sample`-s we are streaming to the [`OutputDevice`]
+    T: SizedSample + Send + Sync + cpal::FromSample<f32> + 'static,
+    //The iterator for writing the samples to the output / data buffer
+    S: Iterator<Item = T> + Send + 'static + Clone,
+    //Error callback is called when the output_stream encounters an error
+    E: FnMut(StreamError) + Send + 'static,
+{
+    //Get the `StreamConfig` from the default output device.
+    let config = default_output_stream_config(&device)?;
+
+    stream_audio_with_config(device, config, error_callback, samples)
+}
+
+///
+/// Plays back audio from an [`Iterator`] to an [`OutputDevice`] with a caller provided [`StreamConfig`].
+///
+/// # Behavior
+/// The samples are expected to match the `config`'s sample rate and channel count, see [`negotiate_output_config`] for choosing one.
+/// The [`Stream`] returned by this function will not play automaticly, you will have to call [`cpal::traits::StreamTrait::play`] to start playing.
+/// If the ongoing [`Stream`] is dropped the audio stream will stop.
+///
+/// # Error
+/// The `error_callback` is called when an error occurs while streaming to the output.
+/// Returns an error if the device does not support the `config`.
+///
+pub fn stream_audio_with_config<T, E, S>(
     device: OutputDevice,
+    config: StreamConfig,
     error_callback: E,
-    mut samples: S,
+    samples: S,
 ) -> Result<Stream>
 where
     //This is the type of the `Sample`-s we are streaming to the [`OutputDevice`]
@@ -28,29 +72,129 @@ where
     //Error callback is called when the output_stream encounters an error
     E: FnMut(StreamError) + Send + 'static,
 {
-    //Get supported config
-    let supported_config = device.default_output_config()?;
+    build_sample_stream(device, config, error_callback, samples, |_| {})
+}
+
+///
+/// Plays back audio from an [`Iterator`] to an [`OutputDevice`] with a [`GainControl`] applied.
+///
+/// # Behavior
+/// Works the same way as [`stream_audio`], but the volume and mute state of the [`GainControl`] are applied to the samples immediately, without clicks.
+/// The [`Stream`] fades in when it starts playing, call [`GainControl::fade_out_blocking`] before dropping the [`Stream`] to stop without a click.
+///
+/// # Error
+/// The `error_callback` is called when an error occurs while streaming to the output.
+/// Returns an error if the output device's default config could not be fetched or if the [`Stream`] could not be built.
+///
+pub fn stream_audio_with_gain<T, E, S>(
+    device: OutputDevice,
+    gain: GainControl,
+    error_callback: E,
+    samples: S,
+) -> Result<Stream>
+where
+    //This is the type of the `Sample`-s we are streaming to the [`OutputDevice`]
+    T: SizedSample + Send + Sync + cpal::FromSample<f32> + 'static,
+    //The gain is applied to the samples as `f32`-s
+    f32: cpal::FromSample<T>,
+    //The iterator for writing the samples to the output / data buffer
+    S: Iterator<Item = T> + Send + 'static + Clone,
+    //Error callback is called when the output_stream encounters an error
+    E: FnMut(StreamError) + Send + 'static,
+{
+    //Get the `StreamConfig` from the default output device.
+    let config = default_output_stream_config(&device)?;
 
-    //Get cahnnel count
-    let channel_count = supported_config.channels();
+    stream_audio_with_config_and_gain(device, config, gain, error_callback, samples)
+}
 
-    //Create data `Stream` and return it
-    let stream: Stream = device.build_output_stream(
-        //Get the `StreamConfig` from the default input and output devices.
-        &StreamConfig {
-            channels: channel_count,
-            sample_rate: supported_config.sample_rate(),
-            buffer_size: {
-                match supported_config.buffer_size() {
-                    cpal::SupportedBufferSize::Range { min, max: _ } => BufferSize::Fixed(*min),
-                    cpal::SupportedBufferSize::Unknown => BufferSize::Default,
+///
+/// Plays back audio from an [`Iterator`] to an [`OutputDevice`] with a caller provided [`StreamConfig`] and a [`GainControl`] applied.
+///
+/// # Behavior
+/// Works the same way as [`stream_audio_with_config`], but the volume and mute state of the [`GainControl`] are applied to the samples immediately, without clicks.
+/// The [`Stream`] fades in when it starts playing, call [`GainControl::fade_out_blocking`] before dropping the [`Stream`] to stop without a click.
+///
+/// # Error
+/// The `error_callback` is called when an error occurs while streaming to the output.
+/// Returns an error if the device does not support the `config`.
+///
+pub fn stream_audio_with_config_and_gain<T, E, S>(
+    device: OutputDevice,
+    config: StreamConfig,
+    gain: GainControl,
+    error_callback: E,
+    samples: S,
+) -> Result<Stream>
+where
+    //This is the type of the `Sample`-s we are streaming to the [`OutputDevice`]
+    T: SizedSample + Send + Sync + cpal::FromSample<f32> + 'static,
+    //The gain is applied to the samples as `f32`-s
+    f32: cpal::FromSample<T>,
+    //The iterator for writing the samples to the output / data buffer
+    S: Iterator<Item = T> + Send + 'static + Clone,
+    //Error callback is called when the output_stream encounters an error
+    E: FnMut(StreamError) + Send + 'static,
+{
+    let channels = config.channels as usize;
+    let mut gain_ramp = GainRamp::new(gain.clone(), config.sample_rate.0);
+
+    //Start the playback with a fade-in
+    gain.fade_in();
+
+    //Apply the gain to the written samples
+    build_sample_stream(
+        device,
+        config,
+        error_callback,
+        samples,
+        move |data: &mut [T]| {
+            let target = gain_ramp.control().target_gain();
+            let step = gain_ramp.step();
+
+            for frame in data.chunks_mut(channels.max(1)) {
+                let gain = gain_ramp.next_gain(target, step);
+
+                for sample in frame {
+                    *sample = T::from_sample(sample.to_sample::<f32>() * gain);
                 }
-            },
+            }
+
+            gain_ramp.store_current();
         },
+    )
+}
+
+///
+/// Builds an output [`Stream`] which plays the `samples`, every written buffer is passed to `transform` before it is played (Eg.: to apply a gain).
+///
+/// # Behavior
+/// Silence is written once the `samples` run out.
+///
+/// # Error
+/// The `error_callback` is called when an error occurs while streaming to the output.
+/// Returns an error if the device does not support the `config`.
+///
+fn build_sample_stream<T, E, S, F>(
+    device: OutputDevice,
+    config: StreamConfig,
+    error_callback: E,
+    mut samples: S,
+    mut transform: F,
+) -> Result<Stream>
+where
+    T: SizedSample + Send + Sync + cpal::FromSample<f32> + 'static,
+    S: Iterator<Item = T> + Send + 'static,
+    E: FnMut(StreamError) + Send + 'static,
+    F: FnMut(&mut [T]) + Send + 'static,
+{
+    //Create data `Stream` and return it
+    let stream: Stream = device.build_output_stream(
+        &config,
         //Data writer callback, write the samples to the frames through this
         move |data: &mut [T], _info: &cpal::OutputCallbackInfo| {
             //Write the samples to the data buffer
-            for frame in data {
+            for frame in data.iter_mut() {
                 *frame = if let Some(sample) = samples.next() {
                     //Write the sample to the frame
                     sample
@@ -59,6 +203,8 @@ where
                     T::from_sample(0.0)
                 };
             }
+
+            transform(data);
         },
         //If an error occurs while writing the data this function will be called
         error_callback,
@@ -69,3 +215,507 @@ where
     //Return the `Stream` handle
     Ok(stream)
 }
+
+///
+/// Creates a playback [`Stream`] which is fed through a [`PlaybackHandle`].
+///
+/// # Behavior
+/// Unlike [`stream_audio`], the samples are not fixed at the creation of the [`Stream`], they can be pushed into the returned [`PlaybackHandle`] at any time (Eg.: receive → decode → play loop).
+/// The [`PlaybackHandle`] holds a preallocated queue which can hold `capacity` samples (interleaved), the device callback only drains this queue, it never allocates or locks.
+/// If the queue runs dry, silence is written to the output and the underrun counter of the [`PlaybackHandle`] is incremented (once samples were pushed).
+/// The volume of the [`Stream`] can be controlled with [`PlaybackHandle::gain`], the [`Stream`] fades in when it starts playing.
+/// The [`Stream`] returned by this function will not play automaticly, you will have to call [`cpal::traits::StreamTrait::play`] to start playing.
+///
+/// # Error
+/// The `error_callback` is called when an error occurs while streaming to the output.
+/// Returns an error if the output device's default config could not be fetched or if the [`Stream`] could not be built.
+///
+pub fn stream_audio_queue<T, E>(
+    device: OutputDevice,
+    error_callback: E,
+    capacity: usize,
+) -> Result<(Stream, PlaybackHandle)>
+where
+    //This is the type of the `Sample`-s we are streaming to the [`OutputDevice`]
+    T: SizedSample + Send + Sync + cpal::FromSample<f32> + 'static,
+    //Error callback is called when the output_stream encounters an error
+    E: FnMut(StreamError) + Send + 'static,
+{
+    //Get the `StreamConfig` from the default output device.
+    let config = default_output_stream_config(&device)?;
+
+    stream_audio_queue_with_config::<T, E>(device, config, error_callback, capacity)
+}
+
+///
+/// Creates a playback [`Stream`] which is fed through a [`PlaybackHandle`], with a caller provided [`StreamConfig`].
+///
+/// # Behavior
+/// Works the same way as [`stream_audio_queue`], but the [`Stream`] is built with the `config` instead of the device's default config.
+/// The pushed samples are expected to match the `config`'s sample rate and channel count, see [`negotiate_output_config`] for choosing one.
+///
+/// # Error
+/// The `error_callback` is called when an error occurs while streaming to the output.
+/// Returns an error if the device does not support the `config`.
+///
+pub fn stream_audio_queue_with_config<T, E>(
+    device: OutputDevice,
+    config: StreamConfig,
+    error_callback: E,
+    capacity: usize,
+) -> Result<(Stream, PlaybackHandle)>
+where
+    //This is the type of the `Sample`-s we are streaming to the [`OutputDevice`]
+    T: SizedSample + Send + Sync + cpal::FromSample<f32> + 'static,
+    //Error callback is called when the output_stream encounters an error
+    E: FnMut(StreamError) + Send + 'static,
+{
+    let handle = PlaybackHandle::new(capacity, config.sample_rate.0, config.channels);
+    let queue = handle.queue.clone();
+    let channels = config.channels as usize;
+    let mut gain_ramp = GainRamp::new(handle.gain.clone(), config.sample_rate.0);
+
+    let stream: Stream = device.build_output_stream(
+        &config,
+        //Data writer callback, drain the queue into the data buffer
+        move |data: &mut [T], _info: &cpal::OutputCallbackInfo| {
+            queue.drain_into(data, &mut gain_ramp, channels);
+        },
+        //If an error occurs while writing the data this function will be called
+        error_callback,
+        //Timeout
+        None,
+    )?;
+
+    Ok((stream, handle))
+}
+
+///
+/// Creates a [`StreamConfig`] from the `device`'s default output config with a buffer size matching the `latency` target.
+///
+/// # Behavior
+/// The buffer size is calculated from the `latency` and the default sample rate, then clamped into the range the device supports.
+/// If the device does not report its supported buffer sizes [`BufferSize::Default`] is used.
+///
+/// # Error
+/// Returns an error if the default output config could not be fetched.
+///
+pub fn output_config_for_latency(device: &OutputDevice, latency: Duration) -> Result<StreamConfig> {
+    let supported_config = device.default_output_config()?;
+
+    Ok(StreamConfig {
+        channels: supported_config.channels(),
+        sample_rate: supported_config.sample_rate(),
+        buffer_size: buffer_size_for_latency(
+            supported_config.buffer_size(),
+            supported_config.sample_rate().0,
+            latency,
+        ),
+    })
+}
+
+/// The result of [`negotiate_output_config`].
+#[derive(Debug, Clone)]
+pub struct NegotiatedOutputConfig {
+    /// The [`StreamConfig`] the [`Stream`] should be built with.
+    pub config: StreamConfig,
+    /// The sample format the device prefers for this config.
+    pub sample_format: SampleFormat,
+    /// The sample rate of the audio which will be played back (Eg.: the decoder's sample rate).
+    pub source_sample_rate: u32,
+    /// The channel count of the audio which will be played back (Eg.: the decoder's channel count).
+    pub source_channels: u16,
+}
+
+impl NegotiatedOutputConfig {
+    /// Returns whether the source audio has to be converted before it can be played back with [`Self::config`].
+    pub fn needs_resampling(&self) -> bool {
+        self.source_sample_rate != self.config.sample_rate.0
+            || self.source_channels != self.config.channels
+    }
+
+    ///
+    /// Creates a [`Resampler`] converting the source audio into the negotiated config.
+    ///
+    /// # Error
+    /// Returns an error if the source sample rate or channel count is zero.
+    ///
+    pub fn resampler(&self) -> Result<Resampler> {
+        Resampler::new(
+            self.source_sample_rate,
+            self.source_channels,
+            self.config.sample_rate.0,
+            self.config.channels,
+        )
+    }
+}
+
+///
+/// Chooses the best supported output config of the `device` for audio with the given `sample_rate` and `channels` (Eg.: the codec's).
+///
+/// # Behavior
+/// The supported configs are ranked in the following order:
+///     * configs supporting both the sample rate and the channel count.
+///     * configs supporting the sample rate.
+///     * configs supporting the channel count.
+///     * [`SampleFormat::F32`] configs are preferred over others.
+///
+/// If the sample rate is not supported the closest supported sample rate is used, and [`NegotiatedOutputConfig::needs_resampling`] will return `true`.
+/// If a `latency` target is passed in, the buffer size is chosen to match it, otherwise [`BufferSize::Default`] is used.
+///
+/// # Error
+/// Returns an error if the device's supported configs could not be queried or if it has none.
+///
+pub fn negotiate_output_config(
+    device: &OutputDevice,
+    sample_rate: u32,
+    channels: u16,
+    latency: Option<Duration>,
+) -> Result<NegotiatedOutputConfig> {
+    let best_config = device
+        .supported_output_configs()?
+        .max_by_key(|config| {
+            let rate_supported = config.min_sample_rate().0 <= sample_rate
+                && sample_rate <= config.max_sample_rate().0;
+            let channels_supported = config.channels() == channels;
+
+            (
+                rate_supported && channels_supported,
+                rate_supported,
+                channels_supported,
+                config.sample_format() == SampleFormat::F32,
+            )
+        })
+        .ok_or_else(|| {
+            anyhow::Error::msg("The device does not have any supported output configs.")
+        })?;
+
+    //Use the closest sample rate the config supports
+    let chosen_rate = sample_rate.clamp(
+        best_config.min_sample_rate().0,
+        best_config.max_sample_rate().0,
+    );
+
+    let buffer_size = match latency {
+        Some(latency) => buffer_size_for_latency(best_config.buffer_size(), chosen_rate, latency),
+        None => BufferSize::Default,
+    };
+
+    Ok(NegotiatedOutputConfig {
+        config: StreamConfig {
+            channels: best_config.channels(),
+            sample_rate: cpal::SampleRate(chosen_rate),
+            buffer_size,
+        },
+        sample_format: best_config.sample_format(),
+        source_sample_rate: sample_rate,
+        source_channels: channels,
+    })
+}
+
+/// Calculates the buffer size (in frames) for a latency target, clamped into the supported range.
+fn buffer_size_for_latency(
+    supported: &SupportedBufferSize,
+    sample_rate: u32,
+    latency: Duration,
+) -> BufferSize {
+    match supported {
+        SupportedBufferSize::Range { min, max } => {
+            let frames = (latency.as_secs_f64() * sample_rate as f64).round() as u32;
+
+            BufferSize::Fixed(frames.clamp(*min, *max))
+        }
+        SupportedBufferSize::Unknown => BufferSize::Default,
+    }
+}
+
+///
+/// Creates the [`StreamConfig`] from the `device`'s default output config, with the host's default buffer size.
+///
+/// # Information
+/// The smallest supported buffer size is not used, it makes the stream glitch and underrun on many devices.
+/// Use [`output_config_for_latency`] or [`negotiate_output_config`] to choose the buffer size.
+///
+fn default_output_stream_config(device: &OutputDevice) -> Result<StreamConfig> {
+    //Get supported config
+    let supported_config = device.default_output_config()?;
+
+    Ok(StreamConfig {
+        channels: supported_config.channels(),
+        sample_rate: supported_config.sample_rate(),
+        buffer_size: BufferSize::Default,
+    })
+}
+
+/// A handle to the sample queue of a [`Stream`] created with [`stream_audio_queue`].
+/// The handle can be cloned and sent between threads, every clone refers to the same queue.
+#[derive(Debug, Clone)]
+pub struct PlaybackHandle {
+    queue: Arc<PlaybackQueue>,
+    gain: GainControl,
+    sample_rate: u32,
+    channels: u16,
+}
+
+impl PlaybackHandle {
+    /// Creates a new [`PlaybackHandle`] with a preallocated queue of `capacity` samples.
+    fn new(capacity: usize, sample_rate: u32, channels: u16) -> Self {
+        Self {
+            queue: Arc::new(PlaybackQueue {
+                ring: SampleRing::with_capacity(capacity),
+                underruns: AtomicU64::new(0),
+                overflows: AtomicU64::new(0),
+                fed: AtomicBool::new(false),
+            }),
+            gain: GainControl::default(),
+            sample_rate,
+            channels,
+        }
+    }
+
+    ///
+    /// Pushes interleaved samples to the end of the playback queue.
+    ///
+    /// # Behavior
+    /// The samples must match the channel count and sample rate of the [`Stream`] (See: [`Self::channels`] and [`Self::sample_rate`]).
+    /// If the queue does not have enough free space, the oldest samples are overwritten, this keeps the latency of a live stream bounded.
+    /// Overwritten samples are counted in [`Self::overflows`].
+    ///
+    pub fn push(&self, samples: &[f32]) {
+        let overwritten = self.queue.ring.push(samples);
+
+        if !samples.is_empty() {
+            self.queue.fed.store(true, Ordering::Release);
+        }
+
+        if overwritten != 0 {
+            self.queue
+                .overflows
+                .fetch_add(overwritten as u64, Ordering::Relaxed);
+        }
+    }
+
+    /// Removes every queued sample, the [`Stream`] will play silence until new samples are pushed.
+    /// The silence is not counted as an underrun.
+    pub fn flush(&self) {
+        self.queue.fed.store(false, Ordering::Release);
+        self.queue.ring.clear();
+    }
+
+    /// Returns the count of samples waiting in the queue.
+    pub fn queue_depth(&self) -> usize {
+        self.queue.ring.len()
+    }
+
+    /// Returns the playback duration of the samples waiting in the queue.
+    pub fn queue_duration(&self) -> Duration {
+        let frames = self.queue_depth() / self.channels.max(1) as usize;
+
+        Duration::from_secs_f64(frames as f64 / self.sample_rate as f64)
+    }
+
+    /// Returns the count of samples the queue can hold.
+    pub fn capacity(&self) -> usize {
+        self.queue.ring.capacity()
+    }
+
+    ///
+    /// Returns how many times the device requested samples while the queue did not contain enough of them.
+    ///
+    /// # Behavior
+    /// The device requesting samples before the first [`Self::push`] (or after a [`Self::flush`]) is not counted, as nothing was expected to play yet.
+    ///
+    pub fn underruns(&self) -> u64 {
+        self.queue.underruns.load(Ordering::Relaxed)
+    }
+
+    /// Returns the count of samples which got overwritten because the queue was full.
+    pub fn overflows(&self) -> u64 {
+        self.queue.overflows.load(Ordering::Relaxed)
+    }
+
+    /// Returns the [`GainControl`] of the [`Stream`], this can be used to change the volume, mute or fade the [`Stream`].
+    pub fn gain(&self) -> &GainControl {
+        &self.gain
+    }
+
+    /// The sample rate of the [`Stream`] this handle feeds.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// The channel count of the [`Stream`] this handle feeds.
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+}
+
+/// The shared state between a [`PlaybackHandle`] and the device callback.
+#[derive(Debug)]
+struct PlaybackQueue {
+    ring: SampleRing,
+    underruns: AtomicU64,
+    overflows: AtomicU64,
+    //Whether samples were pushed since the creation or the last flush
+    fed: AtomicBool,
+}
+
+impl PlaybackQueue {
+    /// Writes the queued samples into the device's buffer with the gain applied, the missing samples are filled with silence.
+    fn drain_into<T>(&self, data: &mut [T], gain_ramp: &mut GainRamp, channels: usize)
+    where
+        T: SizedSample + cpal::FromSample<f32>,
+    {
+        let target = gain_ramp.control().target_gain();
+        let step = gain_ramp.step();
+        let mut underrun = false;
+
+        for frame in data.chunks_mut(channels.max(1)) {
+            let gain = gain_ramp.next_gain(target, step);
+
+            for sample in frame {
+                *sample = if let Some(queued) = self.ring.pop() {
+                    T::from_sample(queued * gain)
+                } else {
+                    //If there arent any samples left, write silence
+                    underrun = true;
+                    T::from_sample(0.0)
+                };
+            }
+        }
+
+        gain_ramp.store_current();
+
+        if underrun && self.fed.load(Ordering::Acquire) {
+            self.underruns.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+///
+/// A fixed size ring buffer of samples, it never reallocates after creation.
+///
+/// # Behavior
+/// The consumer (the device callback) is lock-free, it only advances the read position with compare-and-swap.
+/// The producers ([`PlaybackHandle`] clones) are serialized by a lock among themselves, which the consumer never takes.
+/// When the ring is full, the producer drops the oldest samples by advancing the read position the same way, so a sample is only popped if the read position did not move while it was read.
+///
+/// # Information
+/// The positions only grow (wrapping), the slot of a position is the position modulo the capacity.
+/// The samples are stored as the bits of the [`f32`]-s.
+///
+#[derive(Debug)]
+struct SampleRing {
+    buffer: Box<[AtomicU32]>,
+    //The position of the oldest sample
+    read: AtomicUsize,
+    //The position after the newest sample, only the producer holding the lock moves it
+    write: AtomicUsize,
+    //Serializes the producers
+    producer: Mutex<()>,
+}
+
+impl SampleRing {
+    fn with_capacity(capacity: usize) -> Self {
+        Self {
+            buffer: (0..capacity).map(|_| AtomicU32::new(0)).collect(),
+            read: AtomicUsize::new(0),
+            write: AtomicUsize::new(0),
+            producer: Mutex::new(()),
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        self.buffer.len()
+    }
+
+    fn len(&self) -> usize {
+        //The read position is loaded first, so it can not be ahead of the write position
+        let read = self.read.load(Ordering::Acquire);
+        let write = self.write.load(Ordering::Acquire);
+
+        write.wrapping_sub(read).min(self.capacity())
+    }
+
+    fn clear(&self) {
+        let _producer = self.producer.lock();
+
+        //The write position can not move while the lock is held
+        self.read
+            .store(self.write.load(Ordering::Relaxed), Ordering::Release);
+    }
+
+    /// Pushes the samples into the ring, returns the count of the overwritten samples.
+    fn push(&self, samples: &[f32]) -> usize {
+        let capacity = self.capacity();
+
+        if capacity == 0 {
+            return samples.len();
+        }
+
+        //Only the last `capacity` samples could fit anyway
+        let skipped = samples.len().saturating_sub(capacity);
+        let samples = &samples[skipped..];
+
+        let _producer = self.producer.lock();
+        let write = self.write.load(Ordering::Relaxed);
+
+        //Drop the oldest samples to make room, the consumer might pop some of them meanwhile
+        let overwritten = loop {
+            let read = self.read.load(Ordering::Acquire);
+            let overwritten = (write.wrapping_sub(read) + samples.len()).saturating_sub(capacity);
+
+            if overwritten == 0
+                || self
+                    .read
+                    .compare_exchange(
+                        read,
+                        read.wrapping_add(overwritten),
+                        Ordering::AcqRel,
+                        Ordering::Acquire,
+                    )
+                    .is_ok()
+            {
+                break overwritten;
+            }
+        };
+
+        for (offset, sample) in samples.iter().enumerate() {
+            self.buffer[write.wrapping_add(offset) % capacity]
+                .store(sample.to_bits(), Ordering::Relaxed);
+        }
+
+        //Publish the samples to the consumer
+        self.write
+            .store(write.wrapping_add(samples.len()), Ordering::Release);
+
+        skipped + overwritten
+    }
+
+    /// Pops the oldest sample from the ring.
+    fn pop(&self) -> Option<f32> {
+        loop {
+            let read = self.read.load(Ordering::Acquire);
+
+            if read == self.write.load(Ordering::Acquire) {
+                return None;
+            }
+
+            let sample = self.buffer[read % self.capacity()].load(Ordering::Relaxed);
+
+            //If the producer dropped this sample meanwhile, the slot might have been overwritten
+            if self
+                .read
+                .compare_exchange(
+                    read,
+                    read.wrapping_add(1),
+                    Ordering::AcqRel,
+                    Ordering::Relaxed,
+                )
+                .is_ok()
+            {
+                return Some(f32::from_bits(sample));
+            }
+        }
+    }
+}
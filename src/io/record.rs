@@ -8,7 +8,10 @@ use cpal::{
 };
 use parking_lot::Mutex;
 
-use super::InputDevice;
+use super::{
+    gain::{GainControl, GainRamp},
+    InputDevice,
+};
 
 ///
 /// Records audio until the user interrupts it.
@@ -30,32 +33,16 @@ pub fn record_audio_with_interrupt<E>(
 where
     E: FnMut(StreamError) + Send + 'static,
 {
-    let buffer_handle: Arc<parking_lot::lock_api::Mutex<parking_lot::RawMutex, VecDeque<f32>>> =
-        Arc::new(parking_lot::Mutex::new(VecDeque::new()));
-    let buffer_handle_clone = buffer_handle.clone();
-
-    let _: JoinHandle<anyhow::Result<()>> = std::thread::spawn(move || {
-        let stream: cpal::Stream = input_device.build_input_stream(
-            &config,
-            move |data: &[f32], _info: &cpal::InputCallbackInfo| {
-                let mut buffer_handle = buffer_handle_clone.lock();
-                for sample in data.iter() {
-                    buffer_handle.push_back(*sample);
-                }
-            },
-            err_callback,
-            None,
-        )?;
+    let buffer_handle = Arc::new(Mutex::new(VecDeque::new()));
 
-        //Start stream
-        stream.play()?;
-
-        //Wait for interrupt
-        interrupt.blocking_recv()?;
-
-        //Return from thread
-        Ok(())
-    });
+    spawn_recording(
+        input_device,
+        RecordUntil::Interrupt(interrupt),
+        None,
+        err_callback,
+        config,
+        buffer_handle.clone(),
+    );
 
     Ok(buffer_handle)
 }
@@ -80,17 +67,155 @@ pub fn record_audio_with_duration<E>(
 where
     E: FnMut(StreamError) + Send + 'static,
 {
-    let buffer_handle: Arc<parking_lot::lock_api::Mutex<parking_lot::RawMutex, Vec<f32>>> =
-        Arc::new(parking_lot::Mutex::new(Vec::new()));
-    let buffer_handle_clone = buffer_handle.clone();
+    let buffer_handle = Arc::new(Mutex::new(Vec::new()));
+
+    spawn_recording(
+        input_device,
+        RecordUntil::Duration(duration),
+        None,
+        err_callback,
+        config,
+        buffer_handle.clone(),
+    );
+
+    Ok(buffer_handle)
+}
+
+///
+/// Records audio with a [`GainControl`] applied for a set duration.
+///
+/// # Behavior
+/// The recording thread is automaticly started at the creation of the (Input)[`cpal::Stream`].
+/// Records audio for the `duration` (Pushes the samples into the [`Arc<Mutex<Vec<f32>>>`]).
+/// The volume and mute state of the [`GainControl`] are applied to the recorded samples immediately, without clicks.
+/// The recording fades in when it starts, and fades out at the end of the `duration` before the (Input)[`cpal::Stream`] is stopped.
+/// The [`Sync`] buffer is returned and can be accessed.
+///
+/// # Error
+/// The `err_callback` callback is called if an error occurs in the [`DeviceTrait::build_input_stream`] whilst recording.
+/// The function returns an error if an error occured outside of the [`DeviceTrait::build_input_stream`] function.
+pub fn record_audio_with_duration_and_gain<E>(
+    input_device: InputDevice,
+    duration: Duration,
+    gain: GainControl,
+    err_callback: E,
+    config: StreamConfig,
+) -> anyhow::Result<Arc<Mutex<Vec<f32>>>>
+where
+    E: FnMut(StreamError) + Send + 'static,
+{
+    let buffer_handle = Arc::new(Mutex::new(Vec::new()));
+
+    spawn_recording(
+        input_device,
+        RecordUntil::Duration(duration),
+        Some(gain),
+        err_callback,
+        config,
+        buffer_handle.clone(),
+    );
+
+    Ok(buffer_handle)
+}
+
+///
+/// Records audio with a [`GainControl`] applied until the user interrupts it.
+///
+/// # Behavior
+/// The recording thread is automaticly started at the creation of the (Input)[`cpal::Stream`].
+/// Records audio until (Pushes the samples into the [`Arc<Mutex<VecDeque<f32>>>`]) the [`tokio::sync::oneshot::Receiver`] receives a message.
+/// The volume and mute state of the [`GainControl`] are applied to the recorded samples immediately, without clicks.
+/// The recording fades in when it starts, and fades out when it's interrupted before the (Input)[`cpal::Stream`] is stopped.
+/// The [`Sync`] buffer is returned and can be accessed.
+///
+/// # Error
+/// The `err_callback` callback is called if an error occurs in the [`DeviceTrait::build_input_stream`] whilst recording.
+/// The function returns an error if an error occured outside of the [`DeviceTrait::build_input_stream`] function.
+pub fn record_audio_with_gain<E>(
+    input_device: InputDevice,
+    interrupt: tokio::sync::oneshot::Receiver<()>,
+    gain: GainControl,
+    err_callback: E,
+    config: StreamConfig,
+) -> anyhow::Result<Arc<Mutex<VecDeque<f32>>>>
+where
+    E: FnMut(StreamError) + Send + 'static,
+{
+    let buffer_handle = Arc::new(Mutex::new(VecDeque::new()));
+
+    spawn_recording(
+        input_device,
+        RecordUntil::Interrupt(interrupt),
+        Some(gain),
+        err_callback,
+        config,
+        buffer_handle.clone(),
+    );
+
+    Ok(buffer_handle)
+}
+
+/// Decides when a recording thread stops recording.
+enum RecordUntil {
+    /// Records until the [`tokio::sync::oneshot::Receiver`] receives a message.
+    Interrupt(tokio::sync::oneshot::Receiver<()>),
+    /// Records for the duration.
+    Duration(Duration),
+}
+
+///
+/// Spawns the recording thread, which pushes the samples into the `buffer_handle`.
+///
+/// # Behavior
+/// If there is a [`GainControl`], it is applied to the samples, the recording fades in when it starts and fades out before the (Input)[`cpal::Stream`] is stopped.
+/// The fade-out is part of the recording's duration.
+///
+fn spawn_recording<E, B>(
+    input_device: InputDevice,
+    until: RecordUntil,
+    gain: Option<GainControl>,
+    err_callback: E,
+    config: StreamConfig,
+    buffer_handle: Arc<Mutex<B>>,
+) where
+    E: FnMut(StreamError) + Send + 'static,
+    B: Extend<f32> + Send + 'static,
+{
+    let channels = config.channels as usize;
+    let mut gain_ramp = gain
+        .clone()
+        .map(|gain| GainRamp::new(gain, config.sample_rate.0));
+
+    //Start the recording with a fade-in
+    if let Some(gain) = &gain {
+        gain.fade_in();
+    }
+
+    let fade_duration = gain
+        .as_ref()
+        .map(GainControl::fade_duration)
+        .unwrap_or_default();
 
     let _: JoinHandle<anyhow::Result<()>> = std::thread::spawn(move || {
         let stream: cpal::Stream = input_device.build_input_stream(
             &config,
             move |data: &[f32], _info: &cpal::InputCallbackInfo| {
-                let mut buffer_handle = buffer_handle_clone.lock();
-                for sample in data.iter() {
-                    buffer_handle.push(*sample);
+                let mut buffer_handle = buffer_handle.lock();
+
+                match &mut gain_ramp {
+                    Some(gain_ramp) => {
+                        let target = gain_ramp.control().target_gain();
+                        let step = gain_ramp.step();
+
+                        buffer_handle.extend(data.chunks(channels.max(1)).flat_map(|frame| {
+                            let gain = gain_ramp.next_gain(target, step);
+
+                            frame.iter().map(move |sample| *sample * gain)
+                        }));
+
+                        gain_ramp.store_current();
+                    }
+                    None => buffer_handle.extend(data.iter().copied()),
                 }
             },
             err_callback,
@@ -100,12 +225,19 @@ where
         //Start stream
         stream.play()?;
 
-        //Sleep the thread
-        sleep(duration);
+        match until {
+            //Wait for interrupt
+            RecordUntil::Interrupt(interrupt) => interrupt.blocking_recv()?,
+            //Sleep the thread, the fade-out is part of the duration
+            RecordUntil::Duration(duration) => sleep(duration.saturating_sub(fade_duration)),
+        }
+
+        //Fade out before stopping the stream
+        if let Some(gain) = &gain {
+            gain.fade_out_blocking();
+        }
 
         //Return from thread
         Ok(())
     });
-
-    Ok(buffer_handle)
 }
@@ -0,0 +1,187 @@
+//! Offers sample rate and channel count conversion for interleaved samples.
+
+///
+/// A streaming, linear interpolating resampler for interleaved [`f32`] samples.
+///
+/// # Behavior
+/// Converts samples from `from_rate` to `to_rate`, and remixes them from `from_channels` to `to_channels`.
+/// The state is kept between [`Resampler::process`] calls, so audio can be fed in chunks (Eg.: one decoded packet at a time) without introducing discontinuities.
+///
+/// # Information
+/// Channel conversion works the following way:
+///     * mono → multiple channels: the sample is duplicated into every channel.
+///     * multiple channels → mono: the channels are averaged.
+///     * otherwise: the shared channels are copied, the extra channels are dropped or filled with silence.
+///
+#[derive(Debug, Clone)]
+pub struct Resampler {
+    from_rate: u32,
+    to_rate: u32,
+    from_channels: usize,
+    to_channels: usize,
+    //The position of the next output frame in the `scratch` buffer (in frames)
+    position: f64,
+    //The last (remixed) input frame of the previous chunk
+    previous: Vec<f32>,
+    //Reused buffer containing `previous` and the remixed input of the current chunk
+    scratch: Vec<f32>,
+}
+
+impl Resampler {
+    ///
+    /// Creates a new [`Resampler`].
+    ///
+    /// # Error
+    /// Returns an error if any of the arguments are zero.
+    ///
+    pub fn new(
+        from_rate: u32,
+        from_channels: u16,
+        to_rate: u32,
+        to_channels: u16,
+    ) -> anyhow::Result<Self> {
+        if from_rate == 0 || to_rate == 0 || from_channels == 0 || to_channels == 0 {
+            anyhow::bail!("Sample rates and channel counts must be non-zero.");
+        }
+
+        Ok(Self {
+            from_rate,
+            to_rate,
+            from_channels: from_channels as usize,
+            to_channels: to_channels as usize,
+            position: 1.,
+            previous: vec![0.; to_channels as usize],
+            scratch: Vec::new(),
+        })
+    }
+
+    /// Returns whether the [`Resampler`] leaves the samples untouched.
+    pub fn is_passthrough(&self) -> bool {
+        self.from_rate == self.to_rate && self.from_channels == self.to_channels
+    }
+
+    ///
+    /// Resamples the interleaved `input` and appends the result to `output`.
+    ///
+    /// # Behavior
+    /// Incomplete frames at the end of the `input` are ignored.
+    /// The [`Resampler`] might hold back one input frame to interpolate with the next chunk, this causes a delay of one input frame at most.
+    /// The held back samples are released by [`Resampler::flush`].
+    ///
+    pub fn process(&mut self, input: &[f32], output: &mut Vec<f32>) {
+        let frame_count = input.len() / self.from_channels;
+
+        if self.is_passthrough() {
+            output.extend_from_slice(&input[..frame_count * self.from_channels]);
+            return;
+        }
+
+        //Remix the input into the scratch buffer, prefixed with the last frame of the previous chunk
+        self.scratch.clear();
+        self.scratch.extend_from_slice(&self.previous);
+
+        for frame in input.chunks_exact(self.from_channels) {
+            remix_frame(frame, self.to_channels, &mut self.scratch);
+        }
+
+        let step = self.from_rate as f64 / self.to_rate as f64;
+
+        //Interpolate between the frames surrounding the position
+        while self.position < frame_count as f64 {
+            let index = self.position.floor() as usize;
+            let fraction = (self.position - index as f64) as f32;
+
+            let current = &self.scratch[index * self.to_channels..(index + 1) * self.to_channels];
+            let next =
+                &self.scratch[(index + 1) * self.to_channels..(index + 2) * self.to_channels];
+
+            output.extend(
+                current
+                    .iter()
+                    .zip(next)
+                    .map(|(current, next)| current + (next - current) * fraction),
+            );
+
+            self.position += step;
+        }
+
+        //Store the last frame for the next chunk
+        self.position -= frame_count as f64;
+        self.previous.copy_from_slice(
+            &self.scratch[frame_count * self.to_channels..(frame_count + 1) * self.to_channels],
+        );
+    }
+
+    ///
+    /// Appends the samples the [`Resampler`] held back to `output`, this should be called at the end of the input.
+    ///
+    /// # Behavior
+    /// The frames after the last input frame are not known, so the last input frame is repeated for the output frames which would interpolate with them.
+    /// The [`Resampler`] is reset afterwards, so it can be reused for a new input.
+    ///
+    pub fn flush(&mut self, output: &mut Vec<f32>) {
+        let step = self.from_rate as f64 / self.to_rate as f64;
+
+        if !self.is_passthrough() {
+            //The position accumulates rounding errors, a frame at the position of the (unknown) next input frame is not held back
+            while self.position < 1. - step * 1e-6 {
+                output.extend_from_slice(&self.previous);
+
+                self.position += step;
+            }
+        }
+
+        self.reset();
+    }
+
+    /// Resets the state of the [`Resampler`], this should be called when the input is discontinuous.
+    pub fn reset(&mut self) {
+        self.position = 1.;
+        self.previous.fill(0.);
+    }
+}
+
+///
+/// Resamples a whole buffer of interleaved samples.
+///
+/// # Behavior
+/// Creates a [`Resampler`], feeds the whole buffer into it, then flushes it, so the end of the buffer is not held back. Check out [`Resampler`] for more information.
+///
+/// # Error
+/// Returns an error if any of the sample rates or channel counts are zero.
+///
+pub fn resample(
+    samples: &[f32],
+    from_rate: u32,
+    from_channels: u16,
+    to_rate: u32,
+    to_channels: u16,
+) -> anyhow::Result<Vec<f32>> {
+    let mut resampler = Resampler::new(from_rate, from_channels, to_rate, to_channels)?;
+
+    let mut output = Vec::with_capacity(
+        (samples.len() as u64 * to_rate as u64 * to_channels as u64
+            / (from_rate as u64 * from_channels as u64)) as usize
+            + to_channels as usize,
+    );
+
+    resampler.process(samples, &mut output);
+    resampler.flush(&mut output);
+
+    Ok(output)
+}
+
+/// Converts a frame into `to_channels` channels and appends it to the `output`.
+fn remix_frame(frame: &[f32], to_channels: usize, output: &mut Vec<f32>) {
+    if frame.len() == to_channels {
+        output.extend_from_slice(frame);
+    } else if frame.len() == 1 {
+        output.extend(std::iter::repeat_n(frame[0], to_channels));
+    } else if to_channels == 1 {
+        output.push(frame.iter().sum::<f32>() / frame.len() as f32);
+    } else {
+        for channel in 0..to_channels {
+            output.push(frame.get(channel).copied().unwrap_or(0.));
+        }
+    }
+}
@@ -36,6 +36,12 @@ mod tests;
 #[cfg(feature = "opus")]
 pub mod opus;
 
+#[cfg(feature = "g711")]
+pub mod g711;
+
+#[cfg(feature = "flac")]
+pub mod flac;
+
 #[cfg(feature = "opencv")]
 pub mod cam;
 
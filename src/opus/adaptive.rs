@@ -0,0 +1,265 @@
+//! Provides an adaptive bitrate controller, which tunes a live [`OpusEncoder`] to the network conditions.
+
+use std::time::Duration;
+
+use anyhow::Result;
+
+use super::encoder::{OpusBitrate, OpusEncoder};
+
+/// A network feedback report from the receiver (Eg.: from RTCP receiver reports or a transport-wide congestion controller).
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NetworkFeedback {
+    /// The fraction of packets lost since the last report (`0.0..=1.0`).
+    pub packet_loss: f32,
+    /// The measured round-trip time.
+    pub rtt: Duration,
+    /// The estimated available bandwidth in bits per second, if the transport provides one.
+    pub bandwidth_estimate: Option<u32>,
+}
+
+///
+/// The settings of an [`AdaptiveBitrateController`].
+///
+/// # Behavior
+/// The loss and RTT thresholds come in pairs, the gap between the two values of a pair is the hysteresis, so the controller does not flap around a single threshold.
+/// The [`Default`] implementation is tuned for voice calls.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct AdaptiveBitrateConfig {
+    /// The lowest bitrate the controller may set, in bits per second.
+    pub min_bitrate: u32,
+    /// The highest bitrate the controller may set, in bits per second.
+    pub max_bitrate: u32,
+    /// The bitrate the controller starts with, in bits per second.
+    pub start_bitrate: u32,
+    /// The bitrate is multiplied by `1.0 + increase_step` when the network has been stable for long enough.
+    pub increase_step: f32,
+    /// The bitrate is multiplied by this value when the network is congested.
+    pub decrease_factor: f32,
+    /// The bitrate is decreased if the (smoothed) packet loss is above this fraction.
+    pub congested_loss: f32,
+    /// The bitrate may only be increased if the (smoothed) packet loss is below this fraction.
+    pub stable_loss: f32,
+    /// The bitrate is decreased if the (smoothed) round-trip time is above this duration.
+    pub congested_rtt: Duration,
+    /// The bitrate may only be increased if the (smoothed) round-trip time is below this duration.
+    pub stable_rtt: Duration,
+    /// The count of consecutive stable reports needed before the bitrate is increased.
+    pub stable_reports: u32,
+    /// In-band FEC is enabled if the (smoothed) packet loss reaches this fraction.
+    pub fec_enable_loss: f32,
+    /// In-band FEC is disabled if the (smoothed) packet loss falls below this fraction.
+    pub fec_disable_loss: f32,
+    /// The fraction of the bandwidth estimate the bitrate may use, the rest is left for headers and other traffic.
+    pub bandwidth_headroom: f32,
+    /// The weight of the newest report in the smoothed loss and RTT (`0.0..=1.0`), lower values react slower.
+    pub smoothing: f32,
+}
+
+impl Default for AdaptiveBitrateConfig {
+    fn default() -> Self {
+        Self {
+            min_bitrate: 8000,
+            max_bitrate: 64000,
+            start_bitrate: 32000,
+            increase_step: 0.08,
+            decrease_factor: 0.8,
+            congested_loss: 0.1,
+            stable_loss: 0.02,
+            congested_rtt: Duration::from_millis(400),
+            stable_rtt: Duration::from_millis(250),
+            stable_reports: 3,
+            fec_enable_loss: 0.03,
+            fec_disable_loss: 0.01,
+            bandwidth_headroom: 0.85,
+            smoothing: 0.3,
+        }
+    }
+}
+
+/// The change an [`AdaptiveBitrateController`] made to the bitrate after a report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BitrateAdjustment {
+    /// The bitrate was increased.
+    Increased,
+    /// The bitrate was decreased.
+    Decreased,
+    /// The bitrate did not change.
+    Held,
+}
+
+///
+/// Adjusts the bitrate, in-band FEC and the expected packet loss of an [`OpusEncoder`] based on [`NetworkFeedback`].
+///
+/// # Behavior
+/// The packet loss and round-trip time of the reports are smoothed, so a single bad report does not cause a drop in quality.
+/// The bitrate is decreased multiplicatively as soon as the network is congested, and only increased slowly after [`AdaptiveBitrateConfig::stable_reports`] stable reports.
+/// Between the stable and the congested thresholds the bitrate is held.
+/// The bitrate is always capped by the bandwidth estimate (if there is one) and [`AdaptiveBitrateConfig::max_bitrate`].
+///
+/// # Information
+/// The controller does not own the encoder, call [`AdaptiveBitrateController::update_encoder`] with every report, or [`AdaptiveBitrateController::update`] and [`AdaptiveBitrateController::apply`] separately.
+///
+#[derive(Debug, Clone)]
+pub struct AdaptiveBitrateController {
+    config: AdaptiveBitrateConfig,
+    bitrate: u32,
+    inband_fec: bool,
+    packet_loss_perc: u8,
+    smoothed_loss: Option<f32>,
+    smoothed_rtt: Option<Duration>,
+    //Consecutive stable reports since the last change
+    stable_count: u32,
+}
+
+impl AdaptiveBitrateController {
+    /// Creates a new [`AdaptiveBitrateController`], starting at [`AdaptiveBitrateConfig::start_bitrate`] without FEC.
+    pub fn new(config: AdaptiveBitrateConfig) -> Self {
+        Self {
+            bitrate: config.start_bitrate.clamp(
+                config.min_bitrate,
+                config.max_bitrate.max(config.min_bitrate),
+            ),
+            config,
+            inband_fec: false,
+            packet_loss_perc: 0,
+            smoothed_loss: None,
+            smoothed_rtt: None,
+            stable_count: 0,
+        }
+    }
+
+    ///
+    /// Processes a [`NetworkFeedback`] report, and updates the target settings.
+    ///
+    /// # Behavior
+    /// Returns how the bitrate changed, the new settings can be read with [`AdaptiveBitrateController::bitrate`], [`AdaptiveBitrateController::inband_fec`] and [`AdaptiveBitrateController::packet_loss_perc`].
+    /// The encoder is not touched, see: [`AdaptiveBitrateController::update_encoder`].
+    ///
+    pub fn update(&mut self, feedback: &NetworkFeedback) -> BitrateAdjustment {
+        let config = self.config;
+        let weight = config.smoothing.clamp(0., 1.);
+
+        let loss = feedback.packet_loss.clamp(0., 1.);
+        let loss = match self.smoothed_loss {
+            Some(smoothed_loss) => smoothed_loss + (loss - smoothed_loss) * weight,
+            None => loss,
+        };
+        let rtt = match self.smoothed_rtt {
+            Some(smoothed_rtt) => smoothed_rtt.mul_f32(1. - weight) + feedback.rtt.mul_f32(weight),
+            None => feedback.rtt,
+        };
+
+        self.smoothed_loss = Some(loss);
+        self.smoothed_rtt = Some(rtt);
+
+        let previous_bitrate = self.bitrate;
+
+        if loss > config.congested_loss || rtt > config.congested_rtt {
+            self.stable_count = 0;
+            self.bitrate = (self.bitrate as f32 * config.decrease_factor) as u32;
+        } else if loss < config.stable_loss && rtt < config.stable_rtt {
+            self.stable_count += 1;
+
+            if self.stable_count >= config.stable_reports {
+                self.stable_count = 0;
+                self.bitrate = (self.bitrate as f32 * (1. + config.increase_step)).ceil() as u32;
+            }
+        } else {
+            //Between the thresholds, hold the current bitrate
+            self.stable_count = 0;
+        }
+
+        let mut max_bitrate = config.max_bitrate;
+
+        if let Some(bandwidth_estimate) = feedback.bandwidth_estimate {
+            max_bitrate =
+                max_bitrate.min((bandwidth_estimate as f32 * config.bandwidth_headroom) as u32);
+        }
+
+        self.bitrate = self.bitrate.min(max_bitrate).max(config.min_bitrate);
+
+        if loss >= config.fec_enable_loss {
+            self.inband_fec = true;
+        } else if loss < config.fec_disable_loss {
+            self.inband_fec = false;
+        }
+
+        self.packet_loss_perc = (loss * 100.).ceil() as u8;
+
+        match self.bitrate.cmp(&previous_bitrate) {
+            std::cmp::Ordering::Greater => BitrateAdjustment::Increased,
+            std::cmp::Ordering::Less => BitrateAdjustment::Decreased,
+            std::cmp::Ordering::Equal => BitrateAdjustment::Held,
+        }
+    }
+
+    ///
+    /// Applies the current target settings to an [`OpusEncoder`].
+    ///
+    /// # Error
+    /// Returns an error if the encoder rejected a setting.
+    ///
+    pub fn apply(&self, encoder: &mut OpusEncoder) -> Result<()> {
+        encoder.set_bitrate(OpusBitrate::Bits(self.bitrate as i32))?;
+        encoder.set_inband_fec(self.inband_fec)?;
+        encoder.set_packet_loss_perc(self.packet_loss_perc)?;
+
+        Ok(())
+    }
+
+    ///
+    /// Processes a [`NetworkFeedback`] report, and applies the new settings to the live [`OpusEncoder`].
+    ///
+    /// # Behavior
+    /// See: [`AdaptiveBitrateController::update`] and [`AdaptiveBitrateController::apply`].
+    ///
+    /// # Error
+    /// Returns an error if the encoder rejected a setting.
+    ///
+    pub fn update_encoder(
+        &mut self,
+        encoder: &mut OpusEncoder,
+        feedback: &NetworkFeedback,
+    ) -> Result<BitrateAdjustment> {
+        let adjustment = self.update(feedback);
+
+        self.apply(encoder)?;
+
+        Ok(adjustment)
+    }
+
+    /// Returns the target bitrate in bits per second.
+    pub fn bitrate(&self) -> u32 {
+        self.bitrate
+    }
+
+    /// Returns whether in-band FEC should be enabled.
+    pub fn inband_fec(&self) -> bool {
+        self.inband_fec
+    }
+
+    /// Returns the expected packet loss percentage the encoder should be tuned for.
+    pub fn packet_loss_perc(&self) -> u8 {
+        self.packet_loss_perc
+    }
+
+    /// Returns the smoothed packet loss fraction, `None` if there weren't any reports yet.
+    pub fn smoothed_loss(&self) -> Option<f32> {
+        self.smoothed_loss
+    }
+
+    /// Returns the smoothed round-trip time, `None` if there weren't any reports yet.
+    pub fn smoothed_rtt(&self) -> Option<Duration> {
+        self.smoothed_rtt
+    }
+
+    /// Returns the settings of the controller.
+    pub fn config(&self) -> &AdaptiveBitrateConfig {
+        &self.config
+    }
+}
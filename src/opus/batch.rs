@@ -0,0 +1,114 @@
+//! Enables encoding long buffers (Eg.: recorded voice memos) to opus on multiple threads, for offline transcoding.
+
+use anyhow::{bail, Result};
+use opus::Channels;
+use rayon::prelude::*;
+
+use crate::io::SoundPacket;
+
+use super::{
+    encode::{create_opus_encoder_with_config, encode_sample_set_size_opus},
+    encoder::OpusEncoderConfig,
+    frame::FrameDuration,
+};
+
+/// The settings of a parallel batch encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BatchEncodeConfig {
+    /// The count of frames every segment contains, a segment is encoded by a single encoder on a single thread.
+    pub segment_frames: usize,
+    /// The count of frames preceding a segment which are encoded (and then discarded) before the segment, so the encoder's state is settled at the segment's start.
+    pub warm_up_frames: usize,
+    /// The count of threads in the pool, the global [`rayon`] pool is used if this is `None`.
+    pub threads: Option<usize>,
+}
+
+impl Default for BatchEncodeConfig {
+    /// Segments of 250 frames (5 seconds of 20 ms frames) with 5 frames of warm-up, on the global thread pool.
+    fn default() -> Self {
+        Self {
+            segment_frames: 250,
+            warm_up_frames: 5,
+            threads: None,
+        }
+    }
+}
+
+///
+/// Encodes raw samples (f32) into a list of [`SoundPacket`]-s on multiple threads.
+///
+/// # Behavior
+/// The samples are cut into segments of [`BatchEncodeConfig::segment_frames`] frames, every segment is encoded by its own encoder created with the `config`.
+/// Every encoder first encodes the [`BatchEncodeConfig::warm_up_frames`] frames preceding its segment and discards their packets, so there are no audible seams between the segments.
+/// The packets are returned in order, their count and sizes match [`super::encode::encode_samples_opus`]: the last frame is padded with silence.
+///
+/// # Error
+/// Returns an error if an encoder could not be created, the thread pool could not be built or the segment size is `0`.
+///
+pub fn encode_samples_opus_parallel(
+    samples: &[f32],
+    sample_rate: u32,
+    opus_mode: opus::Application,
+    channels: Channels,
+    config: &OpusEncoderConfig,
+    frame_duration: FrameDuration,
+    batch_config: &BatchEncodeConfig,
+) -> Result<Vec<SoundPacket>> {
+    if batch_config.segment_frames == 0 {
+        bail!("A segment must contain at least one frame.");
+    }
+
+    let samples_per_frame = frame_duration.samples_per_frame(sample_rate, channels);
+    let samples_per_segment = samples_per_frame * batch_config.segment_frames;
+    let warm_up_samples = samples_per_frame * batch_config.warm_up_frames;
+
+    let encode_segment = |segment_start: usize| -> Result<Vec<SoundPacket>> {
+        let mut encoder =
+            create_opus_encoder_with_config(sample_rate, opus_mode, channels, config)?;
+
+        //Settle the encoder on the end of the previous segment
+        let warm_up_start = segment_start.saturating_sub(warm_up_samples);
+
+        for frame in samples[warm_up_start..segment_start].chunks_exact(samples_per_frame) {
+            encode_sample_set_size_opus(&mut encoder, frame, frame_duration)?;
+        }
+
+        let segment_end = (segment_start + samples_per_segment).min(samples.len());
+        let mut sound_packets = Vec::with_capacity(batch_config.segment_frames);
+
+        for sample_chunk in samples[segment_start..segment_end].chunks(samples_per_frame) {
+            let sound_packet = if sample_chunk.len() < samples_per_frame {
+                let mut padded_frame = sample_chunk.to_vec();
+                padded_frame.resize(samples_per_frame, 0.);
+
+                encode_sample_set_size_opus(&mut encoder, &padded_frame, frame_duration)?
+            } else {
+                encode_sample_set_size_opus(&mut encoder, sample_chunk, frame_duration)?
+            };
+
+            sound_packets.push(sound_packet);
+        }
+
+        Ok(sound_packets)
+    };
+
+    let encode_segments = || -> Result<Vec<Vec<SoundPacket>>> {
+        (0..samples.len())
+            .into_par_iter()
+            .step_by(samples_per_segment)
+            .map(encode_segment)
+            .collect()
+    };
+
+    //Rayon keeps the order of the segments while collecting
+    let segments = match batch_config.threads {
+        Some(threads) => rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()?
+            .install(encode_segments)?,
+        None => encode_segments()?,
+    };
+
+    Ok(segments.into_iter().flatten().collect())
+}
@@ -1,25 +1,56 @@
 //! Eanbles raw sample decoding from opus.
 
-use anyhow::Result;
-use opus::Decoder;
+use anyhow::{bail, Result};
+use opus::{Channels, Decoder};
 
-use crate::io::SoundPacket;
+use crate::io::{EncoderType, SoundPacket};
 
 ///
 /// Create an [`opus`] decoder.
 ///
 /// # Behavior
-/// Creates an [`opus`] decoder from a specified sample rate (`u32`).
+/// Creates an [`opus`] decoder from a specified sample rate (`u32`) and channel count ([`Channels`]).
+/// The channel count must match the [`SoundPacket`]-s' channel count which will be decoded with this decoder.
 ///
 /// # Error
 /// Returns an error when created with an invalid sample rate.
 ///
-pub fn create_opus_decoder(sample_rate: u32) -> anyhow::Result<Decoder> {
-    let decoder: Decoder = Decoder::new(sample_rate, opus::Channels::Stereo)?;
+pub fn create_opus_decoder(sample_rate: u32, channels: Channels) -> anyhow::Result<Decoder> {
+    let decoder: Decoder = Decoder::new(sample_rate, channels)?;
 
     Ok(decoder)
 }
 
+///
+/// Create an [`opus`] decoder matching a [`SoundPacket`].
+///
+/// # Behavior
+/// Creates an [`opus`] decoder from the sample rate and the channel count stored in the [`SoundPacket`].
+///
+/// # Error
+/// Returns an error when the [`SoundPacket`] contains an invalid sample rate or channel count.
+///
+pub fn create_opus_decoder_for_packet(sound_packet: &SoundPacket) -> anyhow::Result<Decoder> {
+    create_opus_decoder(
+        sound_packet.sample_rate,
+        channels_from_count(sound_packet.channels)?,
+    )
+}
+
+///
+/// Converts a channel count into [`Channels`].
+///
+/// # Error
+/// Returns an error if the channel count is not `1` or `2`.
+///
+pub fn channels_from_count(channels: u32) -> Result<Channels> {
+    match channels {
+        1 => Ok(Channels::Mono),
+        2 => Ok(Channels::Stereo),
+        _ => bail!("Invalid channel count: {channels}, only mono and stereo are supported."),
+    }
+}
+
 ///
 /// Decodes a [`SoundPacket`] (encoded with the [`opus`] codec), into raw samples.
 ///
@@ -27,18 +58,103 @@ pub fn create_opus_decoder(sample_rate: u32) -> anyhow::Result<Decoder> {
 /// The decoder takes `fec` (Forward Error Correction) as an argument.
 /// Decodes a sound packet with the [`opus`] decoder into raw samples (`Vec<f32>`).
 /// All additional information is included in the [`SoundPacket`] to maximise code efficiency.
+/// The [`Decoder`] must have been created with the [`SoundPacket`]'s channel count (See: [`create_opus_decoder_for_packet`]).
+/// Discontinuous transmission (DTX) frames decode into comfort noise, this is also the case if the [`SoundPacket`]'s bytes were stripped (empty).
 ///
 /// # Error
 /// Returns an error if an error occured while decoding the sound packet.
+/// Returns an error if the [`SoundPacket`]'s `samples_per_frame` does not match its channel count.
 ///
 pub fn decode_sample_set_size_opus(
     decoder: &mut Decoder,
     sound_packet: SoundPacket,
     fec: bool,
 ) -> Result<Vec<f32>> {
-    let mut buf = vec![0f32; sound_packet.samples_per_frame as usize];
+    let mut buf = Vec::with_capacity(sound_packet.samples_per_frame as usize);
+
+    decode_sample_set_size_opus_into(decoder, &sound_packet, fec, &mut buf)?;
+
+    Ok(buf)
+}
+
+///
+/// Decodes a [`SoundPacket`] (encoded with the [`opus`] codec) into a caller-provided buffer.
+///
+/// # Behavior
+/// The decoded samples are appended to `output`, the function returns the count of the appended samples.
+/// `output` only allocates if its capacity is not enough for the frame, so reusing (clearing) the same buffer for every packet makes decoding allocation-free.
+/// Otherwise this behaves like [`decode_sample_set_size_opus`].
+///
+/// # Error
+/// Returns an error if an error occured while decoding the sound packet.
+/// Returns an error if the [`SoundPacket`]'s `samples_per_frame` does not match its channel count.
+///
+pub fn decode_sample_set_size_opus_into(
+    decoder: &mut Decoder,
+    sound_packet: &SoundPacket,
+    fec: bool,
+    output: &mut Vec<f32>,
+) -> Result<usize> {
+    if !matches!(sound_packet.encoder_type, EncoderType::Opus(_)) {
+        bail!(
+            "Can not decode a {:?} packet with the opus decoder.",
+            sound_packet.encoder_type
+        );
+    }
+
+    let channels = channels_from_count(sound_packet.channels)? as usize;
+
+    if sound_packet.samples_per_frame == 0
+        || !(sound_packet.samples_per_frame as usize).is_multiple_of(channels)
+    {
+        bail!(
+            "Invalid samples per frame ({}) for {} channel(s).",
+            sound_packet.samples_per_frame,
+            channels
+        );
+    }
+
+    let start = output.len();
+    output.resize(start + sound_packet.samples_per_frame as usize, 0.);
+
+    let decoded_samples = match decoder.decode_float(&sound_packet.bytes, &mut output[start..], fec)
+    {
+        Ok(decoded_samples) => decoded_samples * channels,
+        Err(err) => {
+            output.truncate(start);
+
+            return Err(err.into());
+        }
+    };
+
+    //Only keep the samples the decoder has written
+    output.truncate(start + decoded_samples);
+
+    Ok(decoded_samples)
+}
+
+///
+/// Generates the samples of a frame which was not received, with the [`opus`] decoder.
+///
+/// # Behavior
+/// Runs the decoder's packet loss concealment, which continues the previous audio.
+/// If the previous frames were discontinuous transmission (DTX) frames, the decoder generates comfort noise, so the receiver can fill the silence between DTX updates.
+/// `samples_per_frame` is the interleaved sample count of the missing frame (Eg.: the [`SoundPacket::samples_per_frame`] of the previous packet).
+///
+/// # Error
+/// Returns an error if the sample count is not a valid frame size for the decoder.
+///
+pub fn decode_missing_frame_opus(
+    decoder: &mut Decoder,
+    samples_per_frame: u64,
+    channels: Channels,
+) -> Result<Vec<f32>> {
+    let mut buf = vec![0f32; samples_per_frame as usize];
+
+    //An empty input makes the decoder conceal the missing frame
+    let decoded_samples = decoder.decode_float(&[], &mut buf, false)?;
 
-    decoder.decode_float(&sound_packet.bytes, &mut buf, fec)?;
+    buf.truncate(decoded_samples * channels as usize);
 
     Ok(buf)
 }
@@ -50,20 +166,29 @@ pub fn decode_sample_set_size_opus(
 /// The function takes a [`Decoder`] and a list of [`SoundPacket`]-s to decode. All information about the decoding process is included in said [`SoundPacket`]-s.
 ///
 /// # Error
-/// Returns an error, if the [`SoundPacket`] is corrupted (Contains invalid data)
+/// Returns an error, if the [`SoundPacket`] is corrupted (Contains invalid data), or it was not encoded with [`opus`].
+/// Lists which also contain packets of other codecs can be decoded with [`crate::io::decode::decode_samples`].
 ///
 pub fn decode_samples_opus(
     mut decoder: Decoder,
     sound_packets: Vec<SoundPacket>,
 ) -> anyhow::Result<Vec<f32>> {
-    let mut samples = vec![];
-
-    for sound_packet in sound_packets {
-        let crate::io::EncoderType::Opus(fec) = sound_packet.encoder_type;
+    let mut samples = Vec::with_capacity(
+        sound_packets
+            .iter()
+            .map(|sound_packet| sound_packet.samples_per_frame as usize)
+            .sum(),
+    );
 
-        let decoded_samples = decode_sample_set_size_opus(&mut decoder, sound_packet, fec)?;
+    for sound_packet in &sound_packets {
+        let EncoderType::Opus(fec) = sound_packet.encoder_type else {
+            bail!(
+                "Can not decode a {:?} packet with the opus decoder.",
+                sound_packet.encoder_type
+            );
+        };
 
-        samples.extend(decoded_samples);
+        decode_sample_set_size_opus_into(&mut decoder, sound_packet, fec, &mut samples)?;
     }
 
     Ok(samples)
@@ -1,19 +1,40 @@
 //! Eanbles raw sample encoding to opus.
 
-use opus::{Channels, Encoder};
+use bytes::BytesMut;
+use opus::Channels;
 
 use crate::io::EncoderType;
 
+use super::{
+    encoder::{OpusEncoder, OpusEncoderConfig},
+    frame::FrameDuration,
+};
+
 use crate::io::SoundPacket;
 
+///
+/// The maximum size of a packet (in bytes) the [`opus`] encoder emits in discontinuous transmission mode.
+///
+/// # Information
+/// This is only used to detect DTX frames in demuxed packets (Eg.: read from an Ogg file), the encoders report DTX frames themselves (See: [`OpusEncoder::in_dtx`]).
+///
+pub const MAX_DTX_PACKET_SIZE: usize = 2;
+
+/// The size of the buffer (in bytes) a single packet is encoded into.
+pub const ENCODE_BUFFER_SIZE: usize = 1500;
+
+/// The count of packets a pooled encoding buffer can hold before it has to allocate again.
+pub(crate) const ENCODE_POOL_PACKETS: usize = 32;
+
 ///
 /// Create an [`opus`] encoder.
 ///
 /// # Behavior
 /// Creates an [`opus`] encoder from a [`SupportedStreamConfig`] to know the host's correct configurations, and an [`opus::Application`] to know which mode the user desires.
+/// In-band FEC is enabled for [`opus::Application::Voip`], if you want to control it (and every other setting) independently use [`create_opus_encoder_with_config`].
 ///
 /// # Error
-/// Returns an error if some kind of error occured while creating the [`Encoder`].
+/// Returns an error if some kind of error occured while creating the [`OpusEncoder`].
 /// Example: invalid configurations were found (highly unlikely).
 ///
 pub fn create_opus_encoder(
@@ -21,10 +42,10 @@ pub fn create_opus_encoder(
     opus_mode: opus::Application,
     bitrate: opus::Bitrate,
     channels: Channels,
-) -> anyhow::Result<Encoder> {
-    let mut encoder = opus::Encoder::new(sample_rate, channels, opus_mode)?;
+) -> anyhow::Result<OpusEncoder> {
+    let mut encoder = OpusEncoder::new(sample_rate, channels, opus_mode)?;
 
-    encoder.set_bitrate(bitrate)?;
+    encoder.set_bitrate(bitrate.into())?;
 
     if matches!(opus_mode, opus::Application::Voip) {
         encoder.set_inband_fec(true)?;
@@ -35,6 +56,29 @@ pub fn create_opus_encoder(
     Ok(encoder)
 }
 
+///
+/// Create an [`opus`] encoder with every setting defined by an [`OpusEncoderConfig`].
+///
+/// # Behavior
+/// Creates an [`OpusEncoder`] with the sample rate, channels ([`Channels`]) and [`opus::Application`], then applies the `config` to it.
+/// The settings can be changed later on with [`OpusEncoder::apply_config`].
+///
+/// # Error
+/// Returns an error if the encoder could not be created or if the `config` contains invalid settings.
+///
+pub fn create_opus_encoder_with_config(
+    sample_rate: u32,
+    opus_mode: opus::Application,
+    channels: Channels,
+    config: &OpusEncoderConfig,
+) -> anyhow::Result<OpusEncoder> {
+    let mut encoder = OpusEncoder::new(sample_rate, channels, opus_mode)?;
+
+    encoder.apply_config(config)?;
+
+    Ok(encoder)
+}
+
 ///
 /// Encode raw samples with the [`opus`] encoder.
 ///
@@ -42,25 +86,75 @@ pub fn create_opus_encoder(
 /// Returns the result of encoding the samples.
 /// In the returned result `(usize, Vec<u8>)` the `usize` will indicate the length of the encoded packet.
 /// The `Vec<u8>` is the output of the encoding process.
+/// The encoder's channel count is recorded in the [`SoundPacket`].
+/// If discontinuous transmission is enabled on the encoder (See: [`OpusEncoder::set_dtx`]), the frames containing silence are marked with [`SoundPacket::dtx`].
 ///
 /// # Error
 /// Returns an error if some kind of error occured during the encoding process.
+/// Returns an error if the sample count does not match the [`FrameDuration`] at the encoder's sample rate and channel count.
 ///
 pub fn encode_sample_set_size_opus(
-    encoder: &mut Encoder,
+    encoder: &mut OpusEncoder,
     samples: &[f32],
-    samples_per_frame: usize,
+    frame_duration: FrameDuration,
 ) -> anyhow::Result<SoundPacket> {
-    let mut compressed_buffer = vec![0; 1500];
+    encode_sample_set_size_opus_into(
+        encoder,
+        samples,
+        frame_duration,
+        &mut BytesMut::with_capacity(ENCODE_BUFFER_SIZE),
+    )
+}
 
-    let encoded_bytes_count = encoder.encode_float(samples, &mut compressed_buffer)?;
+///
+/// Encode raw samples with the [`opus`] encoder into a caller-provided buffer.
+///
+/// # Behavior
+/// The packet is encoded into `buffer`, then split off from it, so the [`SoundPacket`]'s bytes share the buffer's memory instead of being copied.
+/// When the buffer runs out of capacity it reserves room for multiple packets at once, reclaiming its memory if the previously returned packets were dropped, so reusing the same buffer makes encoding allocation-free in the steady state.
+/// Otherwise this behaves like [`encode_sample_set_size_opus`].
+///
+/// # Error
+/// Returns an error if some kind of error occured during the encoding process.
+/// Returns an error if the sample count does not match the [`FrameDuration`] at the encoder's sample rate and channel count.
+///
+pub fn encode_sample_set_size_opus_into(
+    encoder: &mut OpusEncoder,
+    samples: &[f32],
+    frame_duration: FrameDuration,
+    buffer: &mut BytesMut,
+) -> anyhow::Result<SoundPacket> {
+    let samples_per_frame =
+        frame_duration.samples_per_frame(encoder.sample_rate(), encoder.channels());
+
+    if samples.len() != samples_per_frame {
+        anyhow::bail!(
+            "Expected {samples_per_frame} samples for a {frame_duration:?} frame, got {}.",
+            samples.len()
+        );
+    }
+
+    buffer.clear();
+
+    //Reclaim (or allocate) the memory of multiple packets at once
+    if buffer.capacity() < ENCODE_BUFFER_SIZE {
+        buffer.reserve(ENCODE_BUFFER_SIZE * ENCODE_POOL_PACKETS);
+    }
+
+    buffer.resize(ENCODE_BUFFER_SIZE, 0);
+
+    let encoded_bytes_count = encoder.encode_float(samples, buffer)?;
+
+    buffer.truncate(encoded_bytes_count);
 
     Ok(SoundPacket {
-        encoder_type: EncoderType::Opus(encoder.get_inband_fec()?),
-        sample_rate: encoder.get_sample_rate()?,
-        channels: 2,
-        bytes: compressed_buffer[..encoded_bytes_count].to_vec(),
+        encoder_type: EncoderType::Opus(encoder.inband_fec()?),
+        sample_rate: encoder.sample_rate(),
+        channels: encoder.channels() as u32,
+        bytes: buffer.split().freeze(),
         samples_per_frame: samples_per_frame as u64,
+        dtx: encoder.in_dtx()?,
+        channel_mapping: None,
     })
 }
 
@@ -68,37 +162,189 @@ pub fn encode_sample_set_size_opus(
 /// Encodes raw samples (f32) into a list of [`SoundPacket`]-s.
 ///
 /// # Behavior
-/// Returns a list of the encoded [`SoundPacket`]-s. The frame duration and the channels ([`Channels`]) is needed to know the [`SoundPacket`]'s size.
+/// Returns a list of the encoded [`SoundPacket`]-s. The [`FrameDuration`] is needed to know the [`SoundPacket`]'s size.
 ///
 /// # Error
-/// Returns an error if the following arguments are invalid:
-///     * Invalid raw samples
-///     * Invalid frame duration
+/// Returns an error if the raw samples are invalid.
 ///
 pub fn encode_samples_opus(
-    mut encoder: Encoder,
+    mut encoder: OpusEncoder,
     samples: &[f32],
-    frame_duration_ms: u32,
-    channels: Channels,
+    frame_duration: FrameDuration,
 ) -> anyhow::Result<Vec<SoundPacket>> {
-    let samples_per_frame = (encoder.get_sample_rate()? * frame_duration_ms) / 1000;
-    let samples_per_frame = (samples_per_frame * channels as u32) as usize;
-    let mut sound_packets = vec![];
-
-    for sample_chunk in samples.chunks(samples_per_frame) {
-        let sample = if sample_chunk.len() < samples_per_frame {
-            let mut padded_frame = Vec::with_capacity(samples_per_frame);
-            padded_frame.extend_from_slice(sample_chunk);
-            padded_frame.resize(samples_per_frame, 0.);
-            padded_frame
-        } else {
-            sample_chunk.to_vec()
-        };
-
-        let sound_packet = encode_sample_set_size_opus(&mut encoder, &sample, samples_per_frame)?;
+    let samples_per_frame =
+        frame_duration.samples_per_frame(encoder.sample_rate(), encoder.channels());
+    let mut sound_packets = Vec::with_capacity(samples.len().div_ceil(samples_per_frame));
+    //The packets are split off from a shared buffer
+    let mut buffer = BytesMut::new();
+
+    let mut chunks = samples.chunks_exact(samples_per_frame);
+
+    for sample_chunk in &mut chunks {
+        let sound_packet = encode_sample_set_size_opus_into(
+            &mut encoder,
+            sample_chunk,
+            frame_duration,
+            &mut buffer,
+        )?;
 
         sound_packets.push(sound_packet);
     }
 
+    //Only the last frame is copied to be padded with silence
+    if !chunks.remainder().is_empty() {
+        let mut padded_frame = Vec::with_capacity(samples_per_frame);
+        padded_frame.extend_from_slice(chunks.remainder());
+        padded_frame.resize(samples_per_frame, 0.);
+
+        sound_packets.push(encode_sample_set_size_opus_into(
+            &mut encoder,
+            &padded_frame,
+            frame_duration,
+            &mut buffer,
+        )?);
+    }
+
     Ok(sound_packets)
-}
\ No newline at end of file
+}
+
+///
+/// A stateful [`opus`] encoder which can be fed with audio in pieces.
+///
+/// # Behavior
+/// The session owns the [`OpusEncoder`], so its state (Eg.: prediction, lookahead) is kept between [`OpusEncoderSession::push`] calls.
+/// Samples which do not make up a complete frame are buffered until the next [`OpusEncoderSession::push`], only complete frames are encoded.
+/// The remaining samples are only padded with silence when [`OpusEncoderSession::flush`] is called.
+///
+#[derive(Debug)]
+pub struct OpusEncoderSession {
+    encoder: OpusEncoder,
+    //Interleaved sample count of a single frame
+    samples_per_frame: usize,
+    frame_duration: FrameDuration,
+    //The samples which did not make up a complete frame yet
+    leftover: Vec<f32>,
+    //The buffer the packets are split off from
+    buffer: BytesMut,
+}
+
+impl OpusEncoderSession {
+    ///
+    /// Creates a new [`OpusEncoderSession`] from an [`OpusEncoder`].
+    ///
+    /// # Behavior
+    /// The [`FrameDuration`] is needed to know the [`SoundPacket`]'s size.
+    ///
+    pub fn new(encoder: OpusEncoder, frame_duration: FrameDuration) -> Self {
+        let samples_per_frame =
+            frame_duration.samples_per_frame(encoder.sample_rate(), encoder.channels());
+
+        Self {
+            encoder,
+            samples_per_frame,
+            frame_duration,
+            leftover: Vec::with_capacity(samples_per_frame),
+            buffer: BytesMut::new(),
+        }
+    }
+
+    ///
+    /// Pushes raw samples (f32) into the session, and encodes every completed frame.
+    ///
+    /// # Behavior
+    /// Returns the [`SoundPacket`]-s of the frames completed by the samples, this can be empty if the samples did not complete a frame.
+    /// The samples which do not make up a complete frame are kept until the next call.
+    ///
+    /// # Error
+    /// Returns an error if some kind of error occured during the encoding process.
+    ///
+    pub fn push(&mut self, mut samples: &[f32]) -> anyhow::Result<Vec<SoundPacket>> {
+        let mut sound_packets = vec![];
+
+        //Complete the buffered frame first
+        if !self.leftover.is_empty() {
+            let missing = (self.samples_per_frame - self.leftover.len()).min(samples.len());
+
+            self.leftover.extend_from_slice(&samples[..missing]);
+            samples = &samples[missing..];
+
+            if self.leftover.len() == self.samples_per_frame {
+                let sound_packet = self.encode_frame(None)?;
+
+                sound_packets.push(sound_packet);
+            }
+        }
+
+        //Encode the complete frames without copying them
+        let mut chunks = samples.chunks_exact(self.samples_per_frame);
+
+        for sample_chunk in &mut chunks {
+            sound_packets.push(self.encode_frame(Some(sample_chunk))?);
+        }
+
+        self.leftover.extend_from_slice(chunks.remainder());
+
+        Ok(sound_packets)
+    }
+
+    ///
+    /// Encodes the buffered samples padded with silence into a complete frame.
+    ///
+    /// # Behavior
+    /// Returns `None` if there weren't any buffered samples.
+    /// This should be called at the end of the stream, calling it in the middle of a stream inserts silence.
+    ///
+    /// # Error
+    /// Returns an error if some kind of error occured during the encoding process.
+    ///
+    pub fn flush(&mut self) -> anyhow::Result<Option<SoundPacket>> {
+        if self.leftover.is_empty() {
+            return Ok(None);
+        }
+
+        self.leftover.resize(self.samples_per_frame, 0.);
+
+        Ok(Some(self.encode_frame(None)?))
+    }
+
+    /// Returns the count of the buffered samples, which did not make up a complete frame yet.
+    pub fn buffered_samples(&self) -> usize {
+        self.leftover.len()
+    }
+
+    /// Returns the interleaved sample count of a single frame.
+    pub fn samples_per_frame(&self) -> usize {
+        self.samples_per_frame
+    }
+
+    /// Returns the [`FrameDuration`] of the encoded frames.
+    pub fn frame_duration(&self) -> FrameDuration {
+        self.frame_duration
+    }
+
+    /// Returns a mutable reference to the underlying [`OpusEncoder`], this can be used to change its settings on the fly (Eg.: with [`OpusEncoder::apply_config`]).
+    pub fn encoder_mut(&mut self) -> &mut OpusEncoder {
+        &mut self.encoder
+    }
+
+    /// Returns the underlying [`OpusEncoder`], the buffered samples are dropped.
+    pub fn into_encoder(self) -> OpusEncoder {
+        self.encoder
+    }
+
+    /// Encodes a complete frame, if `frame` is `None` the buffered samples are encoded.
+    fn encode_frame(&mut self, frame: Option<&[f32]>) -> anyhow::Result<SoundPacket> {
+        let sound_packet = encode_sample_set_size_opus_into(
+            &mut self.encoder,
+            frame.unwrap_or(&self.leftover),
+            self.frame_duration,
+            &mut self.buffer,
+        )?;
+
+        if frame.is_none() {
+            self.leftover.clear();
+        }
+
+        Ok(sound_packet)
+    }
+}
@@ -0,0 +1,517 @@
+//! Provides an [`opus`] encoder with every encoder setting exposed, and a serializable configuration for it.
+
+use std::ffi::CStr;
+
+use anyhow::{bail, Result};
+use audiopus_sys as ffi;
+use opus::{Application, Bitrate, Channels};
+
+/// The bitrate of an [`OpusEncoder`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OpusBitrate {
+    /// An explicit bitrate in bits per second.
+    Bits(i32),
+    /// The maximum bitrate allowed by the packet size.
+    Max,
+    /// The bitrate is decided by the encoder.
+    Auto,
+}
+
+impl OpusBitrate {
+    /// Converts the value into its libopus representation.
+    pub(crate) fn to_raw(self) -> i32 {
+        match self {
+            Self::Bits(bits) => bits,
+            Self::Max => ffi::OPUS_BITRATE_MAX,
+            Self::Auto => ffi::OPUS_AUTO,
+        }
+    }
+}
+
+impl From<Bitrate> for OpusBitrate {
+    fn from(bitrate: Bitrate) -> Self {
+        match bitrate {
+            Bitrate::Bits(bits) => Self::Bits(bits),
+            Bitrate::Max => Self::Max,
+            Bitrate::Auto => Self::Auto,
+        }
+    }
+}
+
+impl From<OpusBitrate> for Bitrate {
+    fn from(bitrate: OpusBitrate) -> Self {
+        match bitrate {
+            OpusBitrate::Bits(bits) => Self::Bits(bits),
+            OpusBitrate::Max => Self::Max,
+            OpusBitrate::Auto => Self::Auto,
+        }
+    }
+}
+
+/// The type of the signal being encoded, this is a hint for the encoder's mode decision.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OpusSignal {
+    /// The encoder detects the signal type.
+    #[default]
+    Auto,
+    /// The signal is mostly voice.
+    Voice,
+    /// The signal is mostly music.
+    Music,
+}
+
+impl OpusSignal {
+    /// Converts the value into its libopus representation.
+    pub(crate) fn to_raw(self) -> i32 {
+        match self {
+            Self::Auto => ffi::OPUS_AUTO,
+            Self::Voice => ffi::OPUS_SIGNAL_VOICE,
+            Self::Music => ffi::OPUS_SIGNAL_MUSIC,
+        }
+    }
+}
+
+/// The audio bandwidth of an [`opus`] stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OpusBandwidth {
+    /// 4kHz passband.
+    Narrowband,
+    /// 6kHz passband.
+    Mediumband,
+    /// 8kHz passband.
+    Wideband,
+    /// 12kHz passband.
+    Superwideband,
+    /// 20kHz passband.
+    #[default]
+    Fullband,
+}
+
+impl OpusBandwidth {
+    /// Converts the value into its libopus representation.
+    pub(crate) fn to_raw(self) -> i32 {
+        match self {
+            Self::Narrowband => ffi::OPUS_BANDWIDTH_NARROWBAND,
+            Self::Mediumband => ffi::OPUS_BANDWIDTH_MEDIUMBAND,
+            Self::Wideband => ffi::OPUS_BANDWIDTH_WIDEBAND,
+            Self::Superwideband => ffi::OPUS_BANDWIDTH_SUPERWIDEBAND,
+            Self::Fullband => ffi::OPUS_BANDWIDTH_FULLBAND,
+        }
+    }
+
+    /// Converts the value from its libopus representation.
+    fn from_raw(value: i32) -> Result<Self> {
+        Ok(match value {
+            ffi::OPUS_BANDWIDTH_NARROWBAND => Self::Narrowband,
+            ffi::OPUS_BANDWIDTH_MEDIUMBAND => Self::Mediumband,
+            ffi::OPUS_BANDWIDTH_WIDEBAND => Self::Wideband,
+            ffi::OPUS_BANDWIDTH_SUPERWIDEBAND => Self::Superwideband,
+            ffi::OPUS_BANDWIDTH_FULLBAND => Self::Fullband,
+            _ => bail!("Invalid bandwidth value: {value}"),
+        })
+    }
+}
+
+///
+/// The runtime settings of an [`OpusEncoder`].
+///
+/// # Behavior
+/// Every setting can be changed on a live encoder with [`OpusEncoder::apply_config`].
+/// The [`Default`] implementation contains the default settings of libopus.
+///
+/// # Information
+/// With the `serde` feature enabled the config can be (de)serialized, so tuning presets can be shipped as files.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct OpusEncoderConfig {
+    /// The target bitrate.
+    pub bitrate: OpusBitrate,
+    /// The computational complexity of the encoder (`0..=10`), higher values result in better quality.
+    pub complexity: u8,
+    /// Whether variable bitrate is enabled.
+    pub vbr: bool,
+    /// Whether the variable bitrate is constrained (CVBR), this only has an effect if [`Self::vbr`] is enabled.
+    pub constrained_vbr: bool,
+    /// The type of the signal being encoded.
+    pub signal: OpusSignal,
+    /// The maximum bandwidth the encoder may use.
+    pub max_bandwidth: OpusBandwidth,
+    /// The expected packet loss percentage (`0..=100`), the encoder adds more redundancy for higher values.
+    pub packet_loss_perc: u8,
+    /// Whether discontinuous transmission is enabled, which reduces the bitrate during silence.
+    pub dtx: bool,
+    /// Whether in-band Forward Error Correction is enabled, this only has an effect if [`Self::packet_loss_perc`] is non-zero.
+    pub inband_fec: bool,
+}
+
+impl Default for OpusEncoderConfig {
+    fn default() -> Self {
+        Self {
+            bitrate: OpusBitrate::Auto,
+            complexity: 9,
+            vbr: true,
+            constrained_vbr: true,
+            signal: OpusSignal::Auto,
+            max_bandwidth: OpusBandwidth::Fullband,
+            packet_loss_perc: 0,
+            dtx: false,
+            inband_fec: false,
+        }
+    }
+}
+
+impl OpusEncoderConfig {
+    /// Returns the setter ctl requests (request, value) of every setting, this is shared by every kind of encoder.
+    pub(crate) fn ctl_requests(&self) -> [(i32, i32); 9] {
+        [
+            (ffi::OPUS_SET_BITRATE_REQUEST, self.bitrate.to_raw()),
+            (ffi::OPUS_SET_COMPLEXITY_REQUEST, self.complexity as i32),
+            (ffi::OPUS_SET_VBR_REQUEST, self.vbr as i32),
+            (
+                ffi::OPUS_SET_VBR_CONSTRAINT_REQUEST,
+                self.constrained_vbr as i32,
+            ),
+            (ffi::OPUS_SET_SIGNAL_REQUEST, self.signal.to_raw()),
+            (
+                ffi::OPUS_SET_MAX_BANDWIDTH_REQUEST,
+                self.max_bandwidth.to_raw(),
+            ),
+            (
+                ffi::OPUS_SET_PACKET_LOSS_PERC_REQUEST,
+                self.packet_loss_perc as i32,
+            ),
+            (ffi::OPUS_SET_DTX_REQUEST, self.dtx as i32),
+            (ffi::OPUS_SET_INBAND_FEC_REQUEST, self.inband_fec as i32),
+        ]
+    }
+
+    /// A preset for voice calls: voice signal, wideband, FEC tuned for 10% packet loss and DTX.
+    pub fn voip() -> Self {
+        Self {
+            bitrate: OpusBitrate::Bits(32000),
+            signal: OpusSignal::Voice,
+            max_bandwidth: OpusBandwidth::Wideband,
+            packet_loss_perc: 10,
+            dtx: true,
+            inband_fec: true,
+            ..Default::default()
+        }
+    }
+
+    /// A preset for music: music signal, fullband, unconstrained VBR at high complexity.
+    pub fn music() -> Self {
+        Self {
+            bitrate: OpusBitrate::Bits(128000),
+            complexity: 10,
+            constrained_vbr: false,
+            signal: OpusSignal::Music,
+            ..Default::default()
+        }
+    }
+}
+
+///
+/// An [`opus`] encoder with associated state.
+///
+/// # Behavior
+/// Works the same way as [`opus::Encoder`], but every encoder setting can be read and changed at runtime (Eg.: complexity, signal type, DTX).
+/// The encoder also remembers its channel count and sample rate, so the [`crate::io::SoundPacket`]-s it creates contain the correct metadata.
+///
+#[derive(Debug)]
+pub struct OpusEncoder {
+    ptr: *mut ffi::OpusEncoder,
+    channels: Channels,
+    sample_rate: u32,
+    //The requested bitrate, libopus only reports the bitrate it resolved
+    bitrate: OpusBitrate,
+}
+
+// The encoder's state is only accessed through `&mut self`, so it can be moved between threads.
+unsafe impl Send for OpusEncoder {}
+
+impl Drop for OpusEncoder {
+    fn drop(&mut self) {
+        unsafe { ffi::opus_encoder_destroy(self.ptr) }
+    }
+}
+
+impl OpusEncoder {
+    ///
+    /// Creates a new [`OpusEncoder`].
+    ///
+    /// # Error
+    /// Returns an error if the sample rate is not supported by [`opus`] (8, 12, 16, 24 or 48kHz).
+    ///
+    pub fn new(sample_rate: u32, channels: Channels, application: Application) -> Result<Self> {
+        let mut error = 0;
+
+        let ptr = unsafe {
+            ffi::opus_encoder_create(
+                sample_rate as i32,
+                channels as i32,
+                application as i32,
+                &mut error,
+            )
+        };
+
+        if error != ffi::OPUS_OK || ptr.is_null() {
+            return Err(opus_error("opus_encoder_create", error));
+        }
+
+        Ok(Self {
+            ptr,
+            channels,
+            sample_rate,
+            bitrate: OpusBitrate::Auto,
+        })
+    }
+
+    ///
+    /// Encodes a frame of interleaved samples.
+    ///
+    /// # Behavior
+    /// Returns the length of the encoded packet written into `output`.
+    ///
+    /// # Error
+    /// Returns an error if the sample count does not make up a valid [`opus`] frame or if the `output` is too small.
+    ///
+    pub fn encode_float(&mut self, input: &[f32], output: &mut [u8]) -> Result<usize> {
+        let channels = self.channels as usize;
+
+        if !input.len().is_multiple_of(channels) {
+            bail!(
+                "The sample count ({}) is not a multiple of the channel count.",
+                input.len()
+            );
+        }
+
+        let len = unsafe {
+            ffi::opus_encode_float(
+                self.ptr,
+                input.as_ptr(),
+                (input.len() / channels) as i32,
+                output.as_mut_ptr(),
+                output.len().min(i32::MAX as usize) as i32,
+            )
+        };
+
+        if len < 0 {
+            return Err(opus_error("opus_encode_float", len));
+        }
+
+        Ok(len as usize)
+    }
+
+    /// Returns the channel count the encoder was created with.
+    pub fn channels(&self) -> Channels {
+        self.channels
+    }
+
+    /// Returns the sample rate the encoder was created with.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Resets the encoder's state to the freshly initialized state, the settings are kept.
+    pub fn reset_state(&mut self) -> Result<()> {
+        let code = unsafe { ffi::opus_encoder_ctl(self.ptr, ffi::OPUS_RESET_STATE) };
+
+        check_code("opus_encoder_ctl(OPUS_RESET_STATE)", code)
+    }
+
+    ///
+    /// Applies every setting of an [`OpusEncoderConfig`] to the encoder.
+    ///
+    /// # Behavior
+    /// The settings take effect from the next encoded frame, the encoder's state is kept.
+    ///
+    /// # Error
+    /// Returns an error if any of the settings are out of range.
+    ///
+    pub fn apply_config(&mut self, config: &OpusEncoderConfig) -> Result<()> {
+        for (request, value) in config.ctl_requests() {
+            self.set_ctl(request, value)?;
+
+            if request == ffi::OPUS_SET_BITRATE_REQUEST {
+                self.bitrate = config.bitrate;
+            }
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Reads the current settings of the encoder into an [`OpusEncoderConfig`].
+    ///
+    /// # Behavior
+    /// The bitrate is the requested one (See: [`OpusEncoder::bitrate`]), so the returned config can be applied to another encoder to get the same settings.
+    ///
+    /// # Error
+    /// Returns an error if any of the settings could not be read.
+    ///
+    pub fn config(&mut self) -> Result<OpusEncoderConfig> {
+        Ok(OpusEncoderConfig {
+            bitrate: self.bitrate,
+            complexity: self.complexity()?,
+            vbr: self.vbr()?,
+            constrained_vbr: self.vbr_constraint()?,
+            signal: self.signal()?,
+            max_bandwidth: self.max_bandwidth()?,
+            packet_loss_perc: self.packet_loss_perc()?,
+            dtx: self.dtx()?,
+            inband_fec: self.inband_fec()?,
+        })
+    }
+
+    /// Sets the target bitrate.
+    pub fn set_bitrate(&mut self, bitrate: OpusBitrate) -> Result<()> {
+        self.set_ctl(ffi::OPUS_SET_BITRATE_REQUEST, bitrate.to_raw())?;
+
+        self.bitrate = bitrate;
+
+        Ok(())
+    }
+
+    /// Gets the target bitrate as it was requested, [`OpusBitrate::Auto`] until a bitrate is set.
+    pub fn bitrate(&self) -> OpusBitrate {
+        self.bitrate
+    }
+
+    /// Gets the bitrate (in bits per second) the encoder resolved the target bitrate to (Eg.: for [`OpusBitrate::Auto`]).
+    pub fn resolved_bitrate(&mut self) -> Result<i32> {
+        self.get_ctl(ffi::OPUS_GET_BITRATE_REQUEST)
+    }
+
+    /// Sets the computational complexity (`0..=10`).
+    pub fn set_complexity(&mut self, complexity: u8) -> Result<()> {
+        self.set_ctl(ffi::OPUS_SET_COMPLEXITY_REQUEST, complexity as i32)
+    }
+
+    /// Gets the computational complexity.
+    pub fn complexity(&mut self) -> Result<u8> {
+        Ok(self.get_ctl(ffi::OPUS_GET_COMPLEXITY_REQUEST)? as u8)
+    }
+
+    /// Enables or disables variable bitrate.
+    pub fn set_vbr(&mut self, vbr: bool) -> Result<()> {
+        self.set_ctl(ffi::OPUS_SET_VBR_REQUEST, vbr as i32)
+    }
+
+    /// Gets whether variable bitrate is enabled.
+    pub fn vbr(&mut self) -> Result<bool> {
+        Ok(self.get_ctl(ffi::OPUS_GET_VBR_REQUEST)? != 0)
+    }
+
+    /// Enables or disables constrained variable bitrate.
+    pub fn set_vbr_constraint(&mut self, constrained: bool) -> Result<()> {
+        self.set_ctl(ffi::OPUS_SET_VBR_CONSTRAINT_REQUEST, constrained as i32)
+    }
+
+    /// Gets whether constrained variable bitrate is enabled.
+    pub fn vbr_constraint(&mut self) -> Result<bool> {
+        Ok(self.get_ctl(ffi::OPUS_GET_VBR_CONSTRAINT_REQUEST)? != 0)
+    }
+
+    /// Sets the type of the signal being encoded.
+    pub fn set_signal(&mut self, signal: OpusSignal) -> Result<()> {
+        self.set_ctl(ffi::OPUS_SET_SIGNAL_REQUEST, signal.to_raw())
+    }
+
+    /// Gets the type of the signal being encoded.
+    pub fn signal(&mut self) -> Result<OpusSignal> {
+        Ok(match self.get_ctl(ffi::OPUS_GET_SIGNAL_REQUEST)? {
+            ffi::OPUS_SIGNAL_VOICE => OpusSignal::Voice,
+            ffi::OPUS_SIGNAL_MUSIC => OpusSignal::Music,
+            _ => OpusSignal::Auto,
+        })
+    }
+
+    /// Sets the maximum bandwidth the encoder may use.
+    pub fn set_max_bandwidth(&mut self, bandwidth: OpusBandwidth) -> Result<()> {
+        self.set_ctl(ffi::OPUS_SET_MAX_BANDWIDTH_REQUEST, bandwidth.to_raw())
+    }
+
+    /// Gets the maximum bandwidth the encoder may use.
+    pub fn max_bandwidth(&mut self) -> Result<OpusBandwidth> {
+        OpusBandwidth::from_raw(self.get_ctl(ffi::OPUS_GET_MAX_BANDWIDTH_REQUEST)?)
+    }
+
+    /// Sets the expected packet loss percentage (`0..=100`).
+    pub fn set_packet_loss_perc(&mut self, percentage: u8) -> Result<()> {
+        self.set_ctl(ffi::OPUS_SET_PACKET_LOSS_PERC_REQUEST, percentage as i32)
+    }
+
+    /// Gets the expected packet loss percentage.
+    pub fn packet_loss_perc(&mut self) -> Result<u8> {
+        Ok(self.get_ctl(ffi::OPUS_GET_PACKET_LOSS_PERC_REQUEST)? as u8)
+    }
+
+    /// Enables or disables discontinuous transmission.
+    pub fn set_dtx(&mut self, dtx: bool) -> Result<()> {
+        self.set_ctl(ffi::OPUS_SET_DTX_REQUEST, dtx as i32)
+    }
+
+    /// Gets whether discontinuous transmission is enabled.
+    pub fn dtx(&mut self) -> Result<bool> {
+        Ok(self.get_ctl(ffi::OPUS_GET_DTX_REQUEST)? != 0)
+    }
+
+    /// Gets whether the last encoded frame was a discontinuous transmission frame (it contained silence).
+    pub fn in_dtx(&mut self) -> Result<bool> {
+        Ok(self.get_ctl(ffi::OPUS_GET_IN_DTX_REQUEST)? != 0)
+    }
+
+    /// Enables or disables in-band Forward Error Correction.
+    pub fn set_inband_fec(&mut self, inband_fec: bool) -> Result<()> {
+        self.set_ctl(ffi::OPUS_SET_INBAND_FEC_REQUEST, inband_fec as i32)
+    }
+
+    /// Gets whether in-band Forward Error Correction is enabled.
+    pub fn inband_fec(&mut self) -> Result<bool> {
+        Ok(self.get_ctl(ffi::OPUS_GET_INBAND_FEC_REQUEST)? != 0)
+    }
+
+    /// Gets the encoder's lookahead in samples (at the encoder's sample rate), this is the delay the encoder adds.
+    pub fn lookahead(&mut self) -> Result<u32> {
+        Ok(self.get_ctl(ffi::OPUS_GET_LOOKAHEAD_REQUEST)? as u32)
+    }
+
+    /// Calls a setter ctl of the encoder.
+    fn set_ctl(&mut self, request: i32, value: i32) -> Result<()> {
+        let code = unsafe { ffi::opus_encoder_ctl(self.ptr, request, value) };
+
+        check_code("opus_encoder_ctl", code)
+    }
+
+    /// Calls a getter ctl of the encoder.
+    fn get_ctl(&mut self, request: i32) -> Result<i32> {
+        let mut value: i32 = 0;
+
+        let code = unsafe { ffi::opus_encoder_ctl(self.ptr, request, &mut value as *mut i32) };
+
+        check_code("opus_encoder_ctl", code)?;
+
+        Ok(value)
+    }
+}
+
+/// Turns a libopus return code into a [`Result`].
+pub(crate) fn check_code(function: &str, code: i32) -> Result<()> {
+    if code < 0 {
+        return Err(opus_error(function, code));
+    }
+
+    Ok(())
+}
+
+/// Creates an error from a libopus error code.
+pub(crate) fn opus_error(function: &str, code: i32) -> anyhow::Error {
+    let description = unsafe { CStr::from_ptr(ffi::opus_strerror(code)) };
+
+    anyhow::anyhow!("{function} failed: {}", description.to_string_lossy())
+}
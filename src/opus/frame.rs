@@ -0,0 +1,152 @@
+//! Provides the frame durations supported by [`opus`], and conversions between durations, sample counts and byte budgets.
+
+use std::time::Duration;
+
+use anyhow::bail;
+use opus::Channels;
+
+/// The duration of an [`opus`] frame.
+/// Only these durations can be encoded, every encode API takes a [`FrameDuration`] so invalid durations are rejected at compile time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FrameDuration {
+    /// 2.5 ms frames.
+    Ms2_5,
+    /// 5 ms frames.
+    Ms5,
+    /// 10 ms frames.
+    Ms10,
+    /// 20 ms frames, this is the most common frame duration for voip.
+    #[default]
+    Ms20,
+    /// 40 ms frames.
+    Ms40,
+    /// 60 ms frames.
+    Ms60,
+    /// 80 ms frames.
+    Ms80,
+    /// 100 ms frames.
+    Ms100,
+    /// 120 ms frames.
+    Ms120,
+}
+
+impl FrameDuration {
+    /// Every supported [`FrameDuration`] in ascending order.
+    pub const ALL: [FrameDuration; 9] = [
+        Self::Ms2_5,
+        Self::Ms5,
+        Self::Ms10,
+        Self::Ms20,
+        Self::Ms40,
+        Self::Ms60,
+        Self::Ms80,
+        Self::Ms100,
+        Self::Ms120,
+    ];
+
+    /// Returns the duration in microseconds.
+    pub const fn as_micros(self) -> u32 {
+        match self {
+            Self::Ms2_5 => 2_500,
+            Self::Ms5 => 5_000,
+            Self::Ms10 => 10_000,
+            Self::Ms20 => 20_000,
+            Self::Ms40 => 40_000,
+            Self::Ms60 => 60_000,
+            Self::Ms80 => 80_000,
+            Self::Ms100 => 100_000,
+            Self::Ms120 => 120_000,
+        }
+    }
+
+    /// Returns the frame duration as a [`Duration`].
+    pub const fn as_duration(self) -> Duration {
+        Duration::from_micros(self.as_micros() as u64)
+    }
+
+    ///
+    /// Creates a [`FrameDuration`] from a duration in milliseconds.
+    ///
+    /// # Error
+    /// Returns an error if the duration is not supported by [`opus`]. 2.5 ms can only be created with [`FrameDuration::Ms2_5`] or [`FrameDuration::from_duration`].
+    ///
+    pub fn from_millis(millis: u32) -> anyhow::Result<Self> {
+        Self::from_duration(Duration::from_millis(millis as u64))
+    }
+
+    ///
+    /// Creates a [`FrameDuration`] from a [`Duration`].
+    ///
+    /// # Error
+    /// Returns an error if the duration is not supported by [`opus`].
+    ///
+    pub fn from_duration(duration: Duration) -> anyhow::Result<Self> {
+        match Self::ALL
+            .into_iter()
+            .find(|frame_duration| frame_duration.as_duration() == duration)
+        {
+            Some(frame_duration) => Ok(frame_duration),
+            None => bail!(
+                "Unsupported opus frame duration: {duration:?}, supported durations are 2.5, 5, 10, 20, 40, 60, 80, 100 and 120 ms."
+            ),
+        }
+    }
+
+    /// Returns the count of samples in a single channel of a frame.
+    pub const fn samples_per_channel(self, sample_rate: u32) -> usize {
+        (sample_rate as u64 * self.as_micros() as u64 / 1_000_000) as usize
+    }
+
+    /// Returns the count of interleaved samples in a frame (This is what [`crate::io::SoundPacket::samples_per_frame`] contains).
+    pub const fn samples_per_frame(self, sample_rate: u32, channels: Channels) -> usize {
+        self.samples_per_channel(sample_rate) * channels as usize
+    }
+
+    ///
+    /// Creates a [`FrameDuration`] from the count of samples in a single channel of a frame.
+    ///
+    /// # Error
+    /// Returns an error if the sample count does not make up a supported frame duration at the sample rate.
+    ///
+    pub fn from_samples_per_channel(samples: usize, sample_rate: u32) -> anyhow::Result<Self> {
+        match Self::ALL
+            .into_iter()
+            .find(|frame_duration| frame_duration.samples_per_channel(sample_rate) == samples)
+        {
+            Some(frame_duration) => Ok(frame_duration),
+            None => bail!(
+                "{samples} samples do not make up a supported opus frame duration at {sample_rate} Hz."
+            ),
+        }
+    }
+
+    /// Returns the size of an encoded frame in bytes at the given bitrate (bits per second).
+    pub const fn byte_budget(self, bitrate: u32) -> usize {
+        (bitrate as u64 * self.as_micros() as u64 / 8_000_000) as usize
+    }
+
+    /// Returns the bitrate (bits per second) which results in encoded frames of `bytes` size.
+    pub const fn bitrate_for_byte_budget(self, bytes: usize) -> u32 {
+        (bytes as u64 * 8_000_000 / self.as_micros() as u64) as u32
+    }
+
+    /// Returns the size of the raw (f32) samples of a frame in bytes.
+    pub const fn raw_byte_size(self, sample_rate: u32, channels: Channels) -> usize {
+        self.samples_per_frame(sample_rate, channels) * std::mem::size_of::<f32>()
+    }
+}
+
+impl TryFrom<Duration> for FrameDuration {
+    type Error = anyhow::Error;
+
+    fn try_from(duration: Duration) -> Result<Self, Self::Error> {
+        Self::from_duration(duration)
+    }
+}
+
+impl From<FrameDuration> for Duration {
+    fn from(frame_duration: FrameDuration) -> Self {
+        frame_duration.as_duration()
+    }
+}
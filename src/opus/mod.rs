@@ -1,7 +1,16 @@
 //! This feature allows opus encoding and decoding for efficient byte transfer, while not sacirifising audio quality.
 
+pub mod adaptive;
+#[cfg(feature = "parallel")]
+pub mod batch;
 pub mod decode;
 pub mod encode;
+pub mod encoder;
+pub mod frame;
+pub mod multistream;
+pub mod ogg;
+pub mod packet;
+pub mod repacketize;
 
 /// Re-export the opus crate.
 pub use opus;
\ No newline at end of file
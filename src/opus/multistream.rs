@@ -0,0 +1,786 @@
+//! Enables multistream [`opus`] encoding and decoding, for surround layouts and more than two channels.
+
+use anyhow::{bail, Result};
+use audiopus_sys as ffi;
+use bytes::BytesMut;
+use opus::Application;
+
+use crate::io::{ChannelMapping, EncoderType, SoundPacket};
+
+use super::{
+    encode::ENCODE_POOL_PACKETS,
+    encoder::{check_code, opus_error, OpusBitrate, OpusEncoderConfig},
+    frame::FrameDuration,
+};
+
+/// The size of the encode buffer for a single stream.
+const MAX_STREAM_PACKET_SIZE: usize = 1500;
+
+/// The channel layout of a multistream encoder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ChannelLayout {
+    /// 5.1 surround (front left, center, front right, rear left, rear right, LFE) in Vorbis channel order, channel mapping family 1.
+    Surround5_1,
+    /// 7.1 surround (front left, center, front right, side left, side right, rear left, rear right, LFE) in Vorbis channel order, channel mapping family 1.
+    Surround7_1,
+    /// A surround layout with the given channel count (`1..=8`) in Vorbis channel order, channel mapping family 1.
+    Surround(u8),
+    /// Channels without a defined meaning (Eg.: the microphones of a multi-mic interface), channel mapping family 255.
+    /// Every channel is coded in its own (uncoupled) stream.
+    Discrete(u8),
+}
+
+impl ChannelLayout {
+    /// Returns the channel count of the layout.
+    pub fn channels(self) -> u8 {
+        match self {
+            Self::Surround5_1 => 6,
+            Self::Surround7_1 => 8,
+            Self::Surround(channels) | Self::Discrete(channels) => channels,
+        }
+    }
+
+    /// Returns the channel mapping family of the layout.
+    pub fn mapping_family(self) -> u8 {
+        match self {
+            Self::Surround5_1 | Self::Surround7_1 | Self::Surround(_) => 1,
+            Self::Discrete(_) => 255,
+        }
+    }
+}
+
+///
+/// A multistream [`opus`] encoder.
+///
+/// # Behavior
+/// The channels are split into coupled (stereo) and uncoupled (mono) streams, every stream is coded by its own encoder, the streams are packed into a single packet.
+/// The bitrate is the total bitrate of every stream, the other settings apply to every stream.
+///
+#[derive(Debug)]
+pub struct OpusMultistreamEncoder {
+    ptr: *mut ffi::OpusMSEncoder,
+    mapping: ChannelMapping,
+    sample_rate: u32,
+    //The requested bitrate, libopus only reports the bitrate it resolved
+    bitrate: OpusBitrate,
+}
+
+// The encoder's state is only accessed through `&mut self`, so it can be moved between threads.
+unsafe impl Send for OpusMultistreamEncoder {}
+
+impl Drop for OpusMultistreamEncoder {
+    fn drop(&mut self) {
+        unsafe { ffi::opus_multistream_encoder_destroy(self.ptr) }
+    }
+}
+
+impl OpusMultistreamEncoder {
+    ///
+    /// Creates a new [`OpusMultistreamEncoder`] for a [`ChannelLayout`].
+    ///
+    /// # Behavior
+    /// The streams and the channel mapping are chosen by libopus, surround layouts get the LFE channel and the surround masking handled.
+    ///
+    /// # Error
+    /// Returns an error if the sample rate is not supported by [`opus`], or the layout's channel count is not supported by its mapping family.
+    ///
+    pub fn new(sample_rate: u32, layout: ChannelLayout, application: Application) -> Result<Self> {
+        let channels = layout.channels();
+
+        if channels == 0 {
+            bail!("A multistream encoder needs at least 1 channel.");
+        }
+
+        let mut error = 0;
+        let mut streams = 0;
+        let mut coupled_streams = 0;
+        let mut mapping = vec![0; channels as usize];
+
+        let ptr = unsafe {
+            ffi::opus_multistream_surround_encoder_create(
+                sample_rate as i32,
+                channels as i32,
+                layout.mapping_family() as i32,
+                &mut streams,
+                &mut coupled_streams,
+                mapping.as_mut_ptr(),
+                application as i32,
+                &mut error,
+            )
+        };
+
+        if error != ffi::OPUS_OK || ptr.is_null() {
+            return Err(opus_error(
+                "opus_multistream_surround_encoder_create",
+                error,
+            ));
+        }
+
+        Ok(Self {
+            ptr,
+            mapping: ChannelMapping {
+                family: layout.mapping_family(),
+                streams: streams as u8,
+                coupled_streams: coupled_streams as u8,
+                mapping,
+            },
+            sample_rate,
+            bitrate: OpusBitrate::Auto,
+        })
+    }
+
+    ///
+    /// Creates a new [`OpusMultistreamEncoder`] with a custom [`ChannelMapping`].
+    ///
+    /// # Behavior
+    /// This can be used to code pairs of channels in coupled streams (Eg.: stereo microphone pairs with mapping family 255).
+    /// The `mapping` contains the coded channel of every input channel, the first `coupled_streams * 2` coded channels belong to the coupled streams.
+    ///
+    /// # Error
+    /// Returns an error if the sample rate is not supported by [`opus`], or the [`ChannelMapping`] is invalid.
+    ///
+    pub fn with_mapping(
+        sample_rate: u32,
+        mapping: ChannelMapping,
+        application: Application,
+    ) -> Result<Self> {
+        validate_mapping(&mapping)?;
+
+        let mut error = 0;
+
+        let ptr = unsafe {
+            ffi::opus_multistream_encoder_create(
+                sample_rate as i32,
+                mapping.channels() as i32,
+                mapping.streams as i32,
+                mapping.coupled_streams as i32,
+                mapping.mapping.as_ptr(),
+                application as i32,
+                &mut error,
+            )
+        };
+
+        if error != ffi::OPUS_OK || ptr.is_null() {
+            return Err(opus_error("opus_multistream_encoder_create", error));
+        }
+
+        Ok(Self {
+            ptr,
+            mapping,
+            sample_rate,
+            bitrate: OpusBitrate::Auto,
+        })
+    }
+
+    ///
+    /// Encodes a frame of interleaved samples.
+    ///
+    /// # Behavior
+    /// Returns the length of the encoded packet written into `output`.
+    ///
+    /// # Error
+    /// Returns an error if the sample count does not make up a valid [`opus`] frame or if the `output` is too small.
+    ///
+    pub fn encode_float(&mut self, input: &[f32], output: &mut [u8]) -> Result<usize> {
+        let channels = self.mapping.channels() as usize;
+
+        if !input.len().is_multiple_of(channels) {
+            bail!(
+                "The sample count ({}) is not a multiple of the channel count.",
+                input.len()
+            );
+        }
+
+        let len = unsafe {
+            ffi::opus_multistream_encode_float(
+                self.ptr,
+                input.as_ptr(),
+                (input.len() / channels) as i32,
+                output.as_mut_ptr(),
+                output.len().min(i32::MAX as usize) as i32,
+            )
+        };
+
+        if len < 0 {
+            return Err(opus_error("opus_multistream_encode_float", len));
+        }
+
+        Ok(len as usize)
+    }
+
+    /// Returns the [`ChannelMapping`] of the encoder.
+    pub fn mapping(&self) -> &ChannelMapping {
+        &self.mapping
+    }
+
+    /// Returns the channel count the encoder was created with.
+    pub fn channels(&self) -> u32 {
+        self.mapping.channels()
+    }
+
+    /// Returns the sample rate the encoder was created with.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    ///
+    /// Applies every setting of an [`OpusEncoderConfig`] to every stream of the encoder.
+    ///
+    /// # Behavior
+    /// The bitrate is the total bitrate, which is distributed between the streams.
+    ///
+    /// # Error
+    /// Returns an error if any of the settings are out of range.
+    ///
+    pub fn apply_config(&mut self, config: &OpusEncoderConfig) -> Result<()> {
+        for (request, value) in config.ctl_requests() {
+            self.set_ctl(request, value)?;
+
+            if request == ffi::OPUS_SET_BITRATE_REQUEST {
+                self.bitrate = config.bitrate;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sets the total target bitrate of every stream.
+    pub fn set_bitrate(&mut self, bitrate: OpusBitrate) -> Result<()> {
+        self.set_ctl(ffi::OPUS_SET_BITRATE_REQUEST, bitrate.to_raw())?;
+
+        self.bitrate = bitrate;
+
+        Ok(())
+    }
+
+    /// Gets the total target bitrate of every stream as it was requested, [`OpusBitrate::Auto`] until a bitrate is set.
+    pub fn bitrate(&self) -> OpusBitrate {
+        self.bitrate
+    }
+
+    /// Gets the total bitrate (in bits per second) the encoder resolved the target bitrate to.
+    pub fn resolved_bitrate(&mut self) -> Result<i32> {
+        self.get_ctl(ffi::OPUS_GET_BITRATE_REQUEST)
+    }
+
+    /// Enables or disables in-band Forward Error Correction.
+    pub fn set_inband_fec(&mut self, inband_fec: bool) -> Result<()> {
+        self.set_ctl(ffi::OPUS_SET_INBAND_FEC_REQUEST, inband_fec as i32)
+    }
+
+    /// Gets whether in-band Forward Error Correction is enabled.
+    pub fn inband_fec(&mut self) -> Result<bool> {
+        Ok(self.get_ctl(ffi::OPUS_GET_INBAND_FEC_REQUEST)? != 0)
+    }
+
+    /// Gets the encoder's lookahead in samples (at the encoder's sample rate), this is the delay the encoder adds.
+    pub fn lookahead(&mut self) -> Result<u32> {
+        Ok(self.get_ctl(ffi::OPUS_GET_LOOKAHEAD_REQUEST)? as u32)
+    }
+
+    ///
+    /// Gets whether the last encoded frame was a discontinuous transmission frame (it contained silence).
+    ///
+    /// # Behavior
+    /// The multistream encoder does not report this itself, so every stream's encoder is asked, the frame is only a DTX frame if every stream was in DTX.
+    ///
+    /// # Error
+    /// Returns an error if the state of a stream could not be read.
+    ///
+    pub fn in_dtx(&mut self) -> Result<bool> {
+        for stream in 0..self.mapping.streams as i32 {
+            let mut stream_encoder: *mut ffi::OpusEncoder = std::ptr::null_mut();
+
+            let code = unsafe {
+                ffi::opus_multistream_encoder_ctl(
+                    self.ptr,
+                    ffi::OPUS_MULTISTREAM_GET_ENCODER_STATE_REQUEST,
+                    stream,
+                    &mut stream_encoder as *mut *mut ffi::OpusEncoder,
+                )
+            };
+
+            check_code("opus_multistream_encoder_ctl", code)?;
+
+            let mut in_dtx: i32 = 0;
+
+            //The stream's encoder is owned by the multistream encoder
+            let code = unsafe {
+                ffi::opus_encoder_ctl(
+                    stream_encoder,
+                    ffi::OPUS_GET_IN_DTX_REQUEST,
+                    &mut in_dtx as *mut i32,
+                )
+            };
+
+            check_code("opus_encoder_ctl", code)?;
+
+            if in_dtx == 0 {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Calls a setter ctl of the encoder.
+    fn set_ctl(&mut self, request: i32, value: i32) -> Result<()> {
+        let code = unsafe { ffi::opus_multistream_encoder_ctl(self.ptr, request, value) };
+
+        check_code("opus_multistream_encoder_ctl", code)
+    }
+
+    /// Calls a getter ctl of the encoder.
+    fn get_ctl(&mut self, request: i32) -> Result<i32> {
+        let mut value: i32 = 0;
+
+        let code =
+            unsafe { ffi::opus_multistream_encoder_ctl(self.ptr, request, &mut value as *mut i32) };
+
+        check_code("opus_multistream_encoder_ctl", code)?;
+
+        Ok(value)
+    }
+}
+
+///
+/// A multistream [`opus`] decoder.
+///
+/// # Behavior
+/// Decodes the packets of an [`OpusMultistreamEncoder`] (or any other multistream encoder) into interleaved samples, using the packets' [`ChannelMapping`].
+///
+#[derive(Debug)]
+pub struct OpusMultistreamDecoder {
+    ptr: *mut ffi::OpusMSDecoder,
+    mapping: ChannelMapping,
+    sample_rate: u32,
+}
+
+// The decoder's state is only accessed through `&mut self`, so it can be moved between threads.
+unsafe impl Send for OpusMultistreamDecoder {}
+
+impl Drop for OpusMultistreamDecoder {
+    fn drop(&mut self) {
+        unsafe { ffi::opus_multistream_decoder_destroy(self.ptr) }
+    }
+}
+
+impl OpusMultistreamDecoder {
+    ///
+    /// Creates a new [`OpusMultistreamDecoder`].
+    ///
+    /// # Error
+    /// Returns an error if the sample rate is not supported by [`opus`], or the [`ChannelMapping`] is invalid.
+    ///
+    pub fn new(sample_rate: u32, mapping: ChannelMapping) -> Result<Self> {
+        validate_mapping(&mapping)?;
+
+        let mut error = 0;
+
+        let ptr = unsafe {
+            ffi::opus_multistream_decoder_create(
+                sample_rate as i32,
+                mapping.channels() as i32,
+                mapping.streams as i32,
+                mapping.coupled_streams as i32,
+                mapping.mapping.as_ptr(),
+                &mut error,
+            )
+        };
+
+        if error != ffi::OPUS_OK || ptr.is_null() {
+            return Err(opus_error("opus_multistream_decoder_create", error));
+        }
+
+        Ok(Self {
+            ptr,
+            mapping,
+            sample_rate,
+        })
+    }
+
+    ///
+    /// Decodes a packet into interleaved samples.
+    ///
+    /// # Behavior
+    /// Returns the count of decoded samples per channel.
+    /// An empty `input` makes the decoder conceal a missing packet.
+    ///
+    /// # Error
+    /// Returns an error if the packet is corrupted or the `output` is too small.
+    ///
+    pub fn decode_float(&mut self, input: &[u8], output: &mut [f32], fec: bool) -> Result<usize> {
+        let input_ptr = if input.is_empty() {
+            std::ptr::null()
+        } else {
+            input.as_ptr()
+        };
+
+        let len = unsafe {
+            ffi::opus_multistream_decode_float(
+                self.ptr,
+                input_ptr,
+                input.len().min(i32::MAX as usize) as i32,
+                output.as_mut_ptr(),
+                (output.len() / self.mapping.channels() as usize) as i32,
+                fec as i32,
+            )
+        };
+
+        if len < 0 {
+            return Err(opus_error("opus_multistream_decode_float", len));
+        }
+
+        Ok(len as usize)
+    }
+
+    /// Returns the [`ChannelMapping`] of the decoder.
+    pub fn mapping(&self) -> &ChannelMapping {
+        &self.mapping
+    }
+
+    /// Returns the channel count the decoder was created with.
+    pub fn channels(&self) -> u32 {
+        self.mapping.channels()
+    }
+
+    /// Returns the sample rate the decoder was created with.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+}
+
+///
+/// Create a multistream [`opus`] encoder.
+///
+/// # Behavior
+/// Creates an [`OpusMultistreamEncoder`] for the [`ChannelLayout`], and sets the total bitrate of the streams.
+/// In-band FEC is enabled for [`opus::Application::Voip`] the same way as [`super::encode::create_opus_encoder`] does.
+///
+/// # Error
+/// Returns an error if the encoder could not be created.
+///
+pub fn create_opus_multistream_encoder(
+    sample_rate: u32,
+    layout: ChannelLayout,
+    opus_mode: Application,
+    bitrate: OpusBitrate,
+) -> Result<OpusMultistreamEncoder> {
+    let mut encoder = OpusMultistreamEncoder::new(sample_rate, layout, opus_mode)?;
+
+    encoder.set_bitrate(bitrate)?;
+    encoder.set_inband_fec(matches!(opus_mode, Application::Voip))?;
+
+    Ok(encoder)
+}
+
+///
+/// Encode raw samples with the multistream [`opus`] encoder.
+///
+/// # Behavior
+/// Returns the encoded [`SoundPacket`], which carries the encoder's [`ChannelMapping`] and total channel count.
+///
+/// # Error
+/// Returns an error if some kind of error occured during the encoding process.
+/// Returns an error if the sample count does not match the [`FrameDuration`] at the encoder's sample rate and channel count.
+///
+pub fn encode_sample_set_size_opus_multistream(
+    encoder: &mut OpusMultistreamEncoder,
+    samples: &[f32],
+    frame_duration: FrameDuration,
+) -> Result<SoundPacket> {
+    encode_sample_set_size_opus_multistream_into(
+        encoder,
+        samples,
+        frame_duration,
+        &mut BytesMut::new(),
+    )
+}
+
+///
+/// Encode raw samples with the multistream [`opus`] encoder into a caller-provided buffer.
+///
+/// # Behavior
+/// The packet is encoded into `buffer`, then split off from it, so the [`SoundPacket`]'s bytes only hold the encoded bytes instead of the whole encode buffer.
+/// The buffer is pooled the same way as in [`super::encode::encode_sample_set_size_opus_into`], so reusing the same buffer makes encoding allocation-free in the steady state.
+/// Otherwise this behaves like [`encode_sample_set_size_opus_multistream`].
+///
+/// # Error
+/// Returns an error if some kind of error occured during the encoding process.
+/// Returns an error if the sample count does not match the [`FrameDuration`] at the encoder's sample rate and channel count.
+///
+pub fn encode_sample_set_size_opus_multistream_into(
+    encoder: &mut OpusMultistreamEncoder,
+    samples: &[f32],
+    frame_duration: FrameDuration,
+    buffer: &mut BytesMut,
+) -> Result<SoundPacket> {
+    let samples_per_frame =
+        frame_duration.samples_per_channel(encoder.sample_rate()) * encoder.channels() as usize;
+
+    if samples.len() != samples_per_frame {
+        bail!(
+            "Expected {samples_per_frame} samples for a {frame_duration:?} frame, got {}.",
+            samples.len()
+        );
+    }
+
+    let buffer_size = MAX_STREAM_PACKET_SIZE * encoder.mapping().streams as usize;
+
+    buffer.clear();
+
+    //Reclaim (or allocate) the memory of multiple packets at once
+    if buffer.capacity() < buffer_size {
+        buffer.reserve(buffer_size * ENCODE_POOL_PACKETS);
+    }
+
+    buffer.resize(buffer_size, 0);
+
+    let encoded_bytes_count = encoder.encode_float(samples, buffer)?;
+
+    buffer.truncate(encoded_bytes_count);
+
+    Ok(SoundPacket {
+        encoder_type: EncoderType::Opus(encoder.inband_fec()?),
+        sample_rate: encoder.sample_rate(),
+        channels: encoder.channels(),
+        bytes: buffer.split().freeze(),
+        samples_per_frame: samples_per_frame as u64,
+        dtx: encoder.in_dtx()?,
+        channel_mapping: Some(encoder.mapping().clone()),
+    })
+}
+
+///
+/// Encodes raw samples (f32) into a list of multistream [`SoundPacket`]-s.
+///
+/// # Behavior
+/// Returns a list of the encoded [`SoundPacket`]-s, the last frame is padded with silence.
+///
+/// # Error
+/// Returns an error if the raw samples are invalid.
+///
+pub fn encode_samples_opus_multistream(
+    mut encoder: OpusMultistreamEncoder,
+    samples: &[f32],
+    frame_duration: FrameDuration,
+) -> Result<Vec<SoundPacket>> {
+    let samples_per_frame =
+        frame_duration.samples_per_channel(encoder.sample_rate()) * encoder.channels() as usize;
+    let mut sound_packets = Vec::with_capacity(samples.len().div_ceil(samples_per_frame));
+    //The packets are split off from a shared buffer
+    let mut buffer = BytesMut::new();
+
+    let mut chunks = samples.chunks_exact(samples_per_frame);
+
+    for sample_chunk in &mut chunks {
+        let sound_packet = encode_sample_set_size_opus_multistream_into(
+            &mut encoder,
+            sample_chunk,
+            frame_duration,
+            &mut buffer,
+        )?;
+
+        sound_packets.push(sound_packet);
+    }
+
+    //Only the last frame is copied to be padded with silence
+    if !chunks.remainder().is_empty() {
+        let mut padded_frame = Vec::with_capacity(samples_per_frame);
+        padded_frame.extend_from_slice(chunks.remainder());
+        padded_frame.resize(samples_per_frame, 0.);
+
+        sound_packets.push(encode_sample_set_size_opus_multistream_into(
+            &mut encoder,
+            &padded_frame,
+            frame_duration,
+            &mut buffer,
+        )?);
+    }
+
+    Ok(sound_packets)
+}
+
+///
+/// Create a multistream [`opus`] decoder matching a [`SoundPacket`].
+///
+/// # Behavior
+/// Uses the [`SoundPacket`]'s [`ChannelMapping`], mono and stereo packets without a mapping get the mapping of channel mapping family 0.
+///
+/// # Error
+/// Returns an error if the [`SoundPacket`] contains an invalid sample rate or mapping.
+///
+pub fn create_opus_multistream_decoder_for_packet(
+    sound_packet: &SoundPacket,
+) -> Result<OpusMultistreamDecoder> {
+    let mapping = match &sound_packet.channel_mapping {
+        Some(mapping) => mapping.clone(),
+        None => default_channel_mapping(sound_packet.channels)?,
+    };
+
+    OpusMultistreamDecoder::new(sound_packet.sample_rate, mapping)
+}
+
+///
+/// Decodes a multistream [`SoundPacket`] into raw samples.
+///
+/// # Behavior
+/// Works the same way as [`super::decode::decode_sample_set_size_opus`], but with an [`OpusMultistreamDecoder`].
+///
+/// # Error
+/// Returns an error if the packet's channel count does not match the decoder's, or the packet is corrupted.
+///
+pub fn decode_sample_set_size_opus_multistream(
+    decoder: &mut OpusMultistreamDecoder,
+    sound_packet: SoundPacket,
+    fec: bool,
+) -> Result<Vec<f32>> {
+    let mut buf = Vec::with_capacity(sound_packet.samples_per_frame as usize);
+
+    decode_sample_set_size_opus_multistream_into(decoder, &sound_packet, fec, &mut buf)?;
+
+    Ok(buf)
+}
+
+///
+/// Decodes a multistream [`SoundPacket`] into a caller-provided buffer.
+///
+/// # Behavior
+/// The decoded samples are appended to `output`, the function returns the count of the appended samples.
+/// Works the same way as [`super::decode::decode_sample_set_size_opus_into`], but with an [`OpusMultistreamDecoder`].
+///
+/// # Error
+/// Returns an error if the packet's channel count does not match the decoder's, or the packet is corrupted.
+///
+pub fn decode_sample_set_size_opus_multistream_into(
+    decoder: &mut OpusMultistreamDecoder,
+    sound_packet: &SoundPacket,
+    fec: bool,
+    output: &mut Vec<f32>,
+) -> Result<usize> {
+    let channels = decoder.channels() as usize;
+
+    if sound_packet.channels as usize != channels {
+        bail!(
+            "The sound packet has {} channels, the decoder has {channels}.",
+            sound_packet.channels
+        );
+    }
+
+    if sound_packet.samples_per_frame == 0
+        || !(sound_packet.samples_per_frame as usize).is_multiple_of(channels)
+    {
+        bail!(
+            "Invalid samples per frame ({}) for {channels} channels.",
+            sound_packet.samples_per_frame
+        );
+    }
+
+    let start = output.len();
+    output.resize(start + sound_packet.samples_per_frame as usize, 0.);
+
+    let decoded_samples = match decoder.decode_float(&sound_packet.bytes, &mut output[start..], fec)
+    {
+        Ok(decoded_samples) => decoded_samples * channels,
+        Err(err) => {
+            output.truncate(start);
+
+            return Err(err);
+        }
+    };
+
+    //Only keep the samples the decoder has written
+    output.truncate(start + decoded_samples);
+
+    Ok(decoded_samples)
+}
+
+///
+/// Decodes a list of multistream [`SoundPacket`]-s into one raw sample.
+///
+/// # Error
+/// Returns an error if a packet could not be decoded, or it was not encoded with [`opus`].
+///
+pub fn decode_samples_opus_multistream(
+    mut decoder: OpusMultistreamDecoder,
+    sound_packets: Vec<SoundPacket>,
+) -> Result<Vec<f32>> {
+    let mut samples = Vec::with_capacity(
+        sound_packets
+            .iter()
+            .map(|sound_packet| sound_packet.samples_per_frame as usize)
+            .sum(),
+    );
+
+    for sound_packet in &sound_packets {
+        let EncoderType::Opus(fec) = sound_packet.encoder_type else {
+            bail!(
+                "Can not decode a {:?} packet with the opus decoder.",
+                sound_packet.encoder_type
+            );
+        };
+
+        decode_sample_set_size_opus_multistream_into(
+            &mut decoder,
+            sound_packet,
+            fec,
+            &mut samples,
+        )?;
+    }
+
+    Ok(samples)
+}
+
+/// Returns the implied [`ChannelMapping`] of mono and stereo streams (channel mapping family 0).
+pub(crate) fn default_channel_mapping(channels: u32) -> Result<ChannelMapping> {
+    match channels {
+        1 => Ok(ChannelMapping {
+            family: 0,
+            streams: 1,
+            coupled_streams: 0,
+            mapping: vec![0],
+        }),
+        2 => Ok(ChannelMapping {
+            family: 0,
+            streams: 1,
+            coupled_streams: 1,
+            mapping: vec![0, 1],
+        }),
+        _ => {
+            bail!("Invalid channel count: {channels}, more than 2 channels need a channel mapping.")
+        }
+    }
+}
+
+/// Checks that a [`ChannelMapping`] can be used by libopus.
+pub(crate) fn validate_mapping(mapping: &ChannelMapping) -> Result<()> {
+    if mapping.mapping.is_empty() {
+        bail!("The channel mapping does not contain any channels.");
+    }
+
+    if mapping.streams == 0 || mapping.coupled_streams > mapping.streams {
+        bail!(
+            "Invalid stream counts: {} streams, {} coupled streams.",
+            mapping.streams,
+            mapping.coupled_streams
+        );
+    }
+
+    if mapping.coded_channels() > 255 {
+        bail!("The streams code more than 255 channels.");
+    }
+
+    if let Some(channel) = mapping
+        .mapping
+        .iter()
+        .find(|channel| **channel != 255 && **channel as u32 >= mapping.coded_channels())
+    {
+        bail!(
+            "The channel mapping refers to coded channel {channel}, but the streams only code {} channels.",
+            mapping.coded_channels()
+        );
+    }
+
+    Ok(())
+}
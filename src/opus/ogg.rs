@@ -0,0 +1,720 @@
+//! Enables saving and loading [`SoundPacket`]-s as [Ogg Opus](https://datatracker.ietf.org/doc/html/rfc7845) (`.opus`) files.
+
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use anyhow::{bail, Result};
+use bytes::Bytes;
+
+use crate::io::{ChannelMapping, EncoderType, SoundPacket};
+
+use super::{
+    encode::MAX_DTX_PACKET_SIZE,
+    multistream::{default_channel_mapping, validate_mapping},
+};
+
+/// The sample rate every granule position and pre-skip value is expressed in.
+pub const OGG_OPUS_GRANULE_RATE: u32 = 48000;
+
+/// The pages are flushed after reaching this size, unless a single packet is larger.
+const MAX_PAGE_BODY_SIZE: usize = 4096;
+
+/// The vendor string written into the `OpusTags` header.
+const VENDOR: &str = concat!("silence-core ", env!("CARGO_PKG_VERSION"));
+
+/// Ogg page header flags.
+const FLAG_CONTINUED: u8 = 0x01;
+const FLAG_BOS: u8 = 0x02;
+const FLAG_EOS: u8 = 0x04;
+
+/// The identification header (`OpusHead`) of an Ogg Opus stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OpusHead {
+    /// The version of the header, this is always `1` for the streams written by this crate.
+    pub version: u8,
+    /// The output channel count.
+    pub channels: u8,
+    /// The count of samples (at 48kHz) which should be discarded from the start of the decoded output.
+    pub pre_skip: u16,
+    /// The sample rate of the original input (informational only, Opus always decodes at 48kHz internally).
+    pub input_sample_rate: u32,
+    /// The gain to apply to the decoded output in Q7.8 dB.
+    pub output_gain: i16,
+    /// The channel mapping family.
+    pub mapping_family: u8,
+    /// The count of streams in each packet, this is only written into the header if the mapping family is not `0`.
+    pub stream_count: u8,
+    /// The count of coupled (two channel) streams in each packet, this is only written into the header if the mapping family is not `0`.
+    pub coupled_count: u8,
+    /// The coded channel of every output channel, this is empty for mapping family `0`.
+    pub channel_mapping: Vec<u8>,
+}
+
+impl OpusHead {
+    /// Creates a new [`OpusHead`] for a mono or stereo stream (channel mapping family 0).
+    pub fn new(channels: u8, input_sample_rate: u32, pre_skip: u16) -> Self {
+        Self {
+            version: 1,
+            channels,
+            pre_skip,
+            input_sample_rate,
+            output_gain: 0,
+            mapping_family: 0,
+            stream_count: 1,
+            coupled_count: (channels == 2) as u8,
+            channel_mapping: vec![],
+        }
+    }
+
+    /// Creates a new [`OpusHead`] for a multistream stream (Eg.: surround sound) with the given [`ChannelMapping`].
+    pub fn with_mapping(mapping: &ChannelMapping, input_sample_rate: u32, pre_skip: u16) -> Self {
+        if mapping.family == 0 {
+            return Self::new(mapping.channels() as u8, input_sample_rate, pre_skip);
+        }
+
+        Self {
+            mapping_family: mapping.family,
+            stream_count: mapping.streams,
+            coupled_count: mapping.coupled_streams,
+            channel_mapping: mapping.mapping.clone(),
+            ..Self::new(mapping.channels() as u8, input_sample_rate, pre_skip)
+        }
+    }
+
+    /// Returns the [`ChannelMapping`] of the stream, the mapping of family 0 streams is implied by the channel count.
+    pub fn mapping(&self) -> ChannelMapping {
+        if self.mapping_family == 0 {
+            if let Ok(mapping) = default_channel_mapping(self.channels as u32) {
+                return mapping;
+            }
+        }
+
+        ChannelMapping {
+            family: self.mapping_family,
+            streams: self.stream_count,
+            coupled_streams: self.coupled_count,
+            mapping: self.channel_mapping.clone(),
+        }
+    }
+
+    /// Serializes the header into its binary representation.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(21 + self.channel_mapping.len());
+
+        bytes.extend_from_slice(b"OpusHead");
+        bytes.push(self.version);
+        bytes.push(self.channels);
+        bytes.extend_from_slice(&self.pre_skip.to_le_bytes());
+        bytes.extend_from_slice(&self.input_sample_rate.to_le_bytes());
+        bytes.extend_from_slice(&self.output_gain.to_le_bytes());
+        bytes.push(self.mapping_family);
+
+        //The channel mapping table is only present for other families than 0
+        if self.mapping_family != 0 {
+            bytes.push(self.stream_count);
+            bytes.push(self.coupled_count);
+            bytes.extend_from_slice(&self.channel_mapping);
+        }
+
+        bytes
+    }
+
+    /// Parses the header from its binary representation.
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 19 || &bytes[..8] != b"OpusHead" {
+            bail!("The stream does not start with a valid OpusHead header.");
+        }
+
+        let mut head = Self {
+            version: bytes[8],
+            channels: bytes[9],
+            pre_skip: u16::from_le_bytes([bytes[10], bytes[11]]),
+            input_sample_rate: u32::from_le_bytes([bytes[12], bytes[13], bytes[14], bytes[15]]),
+            output_gain: i16::from_le_bytes([bytes[16], bytes[17]]),
+            mapping_family: bytes[18],
+            stream_count: 1,
+            coupled_count: (bytes[9] == 2) as u8,
+            channel_mapping: vec![],
+        };
+
+        //Only the major version (upper 4 bits) has to match
+        if head.version >> 4 != 0 {
+            bail!("Unsupported OpusHead version: {}", head.version);
+        }
+
+        if head.channels == 0 {
+            bail!("The OpusHead header has a channel count of 0.");
+        }
+
+        if head.mapping_family == 0 {
+            if head.channels > 2 {
+                bail!(
+                    "The OpusHead header has {} channels without a channel mapping table.",
+                    head.channels
+                );
+            }
+        } else {
+            let Some(table) = bytes.get(19..21 + head.channels as usize) else {
+                bail!("The OpusHead header's channel mapping table is truncated.");
+            };
+
+            head.stream_count = table[0];
+            head.coupled_count = table[1];
+            head.channel_mapping = table[2..].to_vec();
+
+            validate_mapping(&head.mapping())?;
+        }
+
+        Ok(head)
+    }
+}
+
+/// The comment header (`OpusTags`) of an Ogg Opus stream.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OpusTags {
+    /// The name of the encoder which created the stream.
+    pub vendor: String,
+    /// The user comments in `TAG=value` format (Eg.: `TITLE=Voice message`).
+    pub comments: Vec<String>,
+}
+
+impl OpusTags {
+    /// Serializes the header into its binary representation.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        bytes.extend_from_slice(b"OpusTags");
+        bytes.extend_from_slice(&(self.vendor.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(self.vendor.as_bytes());
+        bytes.extend_from_slice(&(self.comments.len() as u32).to_le_bytes());
+
+        for comment in &self.comments {
+            bytes.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(comment.as_bytes());
+        }
+
+        bytes
+    }
+
+    /// Parses the header from its binary representation.
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 8 || &bytes[..8] != b"OpusTags" {
+            bail!("The stream does not contain a valid OpusTags header.");
+        }
+
+        let mut cursor = 8;
+
+        let read_string = |cursor: &mut usize| -> Result<String> {
+            let len = read_u32_le(bytes, *cursor)? as usize;
+            *cursor += 4;
+
+            let Some(string) = bytes.get(*cursor..*cursor + len) else {
+                bail!("The OpusTags header is truncated.");
+            };
+            *cursor += len;
+
+            Ok(String::from_utf8_lossy(string).into_owned())
+        };
+
+        let vendor = read_string(&mut cursor)?;
+
+        let comment_count = read_u32_le(bytes, cursor)?;
+        cursor += 4;
+
+        let comments = (0..comment_count)
+            .map(|_| read_string(&mut cursor))
+            .collect::<Result<Vec<String>>>()?;
+
+        Ok(Self { vendor, comments })
+    }
+}
+
+///
+/// Writes [`SoundPacket`]-s (encoded with the [`opus`] codec) into an Ogg Opus stream.
+///
+/// # Behavior
+/// The `OpusHead` and `OpusTags` headers are written at the creation of the writer.
+/// The packets are collected into pages, the granule position of each page is calculated from the packets' durations.
+/// The stream has to be finished with [`OggOpusWriter::finish`], otherwise the last page is never written.
+///
+#[derive(Debug)]
+pub struct OggOpusWriter<W: Write> {
+    writer: W,
+    serial: u32,
+    sequence: u32,
+    //Total samples (at 48kHz) of the written packets, including the pre-skip
+    granule_position: u64,
+    //The packets waiting to be written onto the next page
+    pending: Vec<Bytes>,
+    pending_size: usize,
+}
+
+impl<W: Write> OggOpusWriter<W> {
+    ///
+    /// Creates a new [`OggOpusWriter`] and writes the headers.
+    ///
+    /// # Behavior
+    /// `tags` are the user comments (Eg.: `TITLE=Voice message`) written into the `OpusTags` header.
+    /// The [`OpusHead::pre_skip`] should be set to the encoder's lookahead (at 48kHz), so that players can trim the encoder delay.
+    ///
+    /// # Error
+    /// Returns an error if the headers could not be written.
+    ///
+    pub fn new(writer: W, head: OpusHead, tags: Vec<String>) -> Result<Self> {
+        let mut ogg_writer = Self {
+            writer,
+            serial: stream_serial(),
+            sequence: 0,
+            granule_position: 0,
+            pending: Vec::new(),
+            pending_size: 0,
+        };
+
+        //The headers are on their own pages with a granule position of 0
+        ogg_writer.write_page(&[&head.to_bytes()], 0, FLAG_BOS)?;
+        ogg_writer.write_page(
+            &[&OpusTags {
+                vendor: VENDOR.to_string(),
+                comments: tags,
+            }
+            .to_bytes()],
+            0,
+            0,
+        )?;
+
+        Ok(ogg_writer)
+    }
+
+    ///
+    /// Queues a [`SoundPacket`] to be written into the stream.
+    ///
+    /// # Behavior
+    /// The packet is written once its page is full, or when the writer gets finished.
+    ///
+    /// # Error
+    /// Returns an error if the [`SoundPacket`] was not encoded with [`opus`], if it is corrupted or if a full page could not be written.
+    ///
+    pub fn write_packet(&mut self, sound_packet: &SoundPacket) -> Result<()> {
+        if !matches!(sound_packet.encoder_type, EncoderType::Opus(_)) {
+            bail!("Only opus encoded packets can be written into an Ogg Opus stream.");
+        }
+
+        let duration =
+            opus::packet::get_nb_samples(&sound_packet.bytes, OGG_OPUS_GRANULE_RATE)? as u64;
+
+        let segments = self
+            .pending
+            .iter()
+            .chain(std::iter::once(&sound_packet.bytes))
+            .map(|packet| packet.len() / 255 + 1)
+            .sum::<usize>();
+
+        //Flush the page if the packet does not fit onto it
+        if !self.pending.is_empty()
+            && (segments > 255 || self.pending_size + sound_packet.bytes.len() > MAX_PAGE_BODY_SIZE)
+        {
+            self.flush_page(0)?;
+        }
+
+        self.granule_position += duration;
+        self.pending_size += sound_packet.bytes.len();
+        self.pending.push(sound_packet.bytes.clone());
+
+        Ok(())
+    }
+
+    ///
+    /// Writes the last page with the end of stream flag, then returns the inner writer.
+    ///
+    /// # Error
+    /// Returns an error if the page could not be written.
+    ///
+    pub fn finish(mut self) -> Result<W> {
+        self.flush_page(FLAG_EOS)?;
+
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+
+    /// Writes the pending packets onto a page.
+    fn flush_page(&mut self, flags: u8) -> Result<()> {
+        let packets = std::mem::take(&mut self.pending);
+        self.pending_size = 0;
+
+        let packets = packets.iter().map(Bytes::as_ref).collect::<Vec<&[u8]>>();
+
+        self.write_page(&packets, self.granule_position, flags)
+    }
+
+    /// Writes a single page containing the complete `packets`.
+    fn write_page(&mut self, packets: &[&[u8]], granule_position: u64, flags: u8) -> Result<()> {
+        let mut lacing_values = Vec::new();
+
+        for packet in packets {
+            lacing_values.extend(std::iter::repeat_n(255, packet.len() / 255));
+            lacing_values.push((packet.len() % 255) as u8);
+        }
+
+        if lacing_values.len() > 255 {
+            bail!("The packets do not fit onto a single page.");
+        }
+
+        let mut page = Vec::with_capacity(27 + lacing_values.len() + packets.concat().len());
+
+        page.extend_from_slice(b"OggS");
+        page.push(0);
+        page.push(flags);
+        page.extend_from_slice(&granule_position.to_le_bytes());
+        page.extend_from_slice(&self.serial.to_le_bytes());
+        page.extend_from_slice(&self.sequence.to_le_bytes());
+        //The checksum is calculated with this field zeroed
+        page.extend_from_slice(&[0; 4]);
+        page.push(lacing_values.len() as u8);
+        page.extend_from_slice(&lacing_values);
+
+        for packet in packets {
+            page.extend_from_slice(packet);
+        }
+
+        let checksum = ogg_crc32(&page);
+        page[22..26].copy_from_slice(&checksum.to_le_bytes());
+
+        self.writer.write_all(&page)?;
+        self.sequence += 1;
+
+        Ok(())
+    }
+}
+
+///
+/// Reads [`SoundPacket`]-s out of an Ogg Opus stream.
+///
+/// # Behavior
+/// The `OpusHead` and `OpusTags` headers are parsed at the creation of the reader.
+/// Only the first logical stream is read, the pages of other multiplexed streams are skipped.
+/// Every page's checksum is verified.
+///
+#[derive(Debug)]
+pub struct OggOpusReader<R: Read> {
+    reader: R,
+    head: OpusHead,
+    tags: OpusTags,
+    serial: u32,
+    //The completed packets of the last read page
+    packets: VecDeque<Vec<u8>>,
+    //A packet continuing on the next page
+    partial: Vec<u8>,
+    finished: bool,
+}
+
+impl<R: Read> OggOpusReader<R> {
+    ///
+    /// Creates a new [`OggOpusReader`] and parses the headers.
+    ///
+    /// # Error
+    /// Returns an error if the stream is not an Ogg Opus stream or if it is corrupted.
+    ///
+    pub fn new(mut reader: R) -> Result<Self> {
+        //Find the beginning of the first stream
+        let first_page = loop {
+            let Some(page) = read_page(&mut reader)? else {
+                bail!("The stream does not contain any Ogg pages.");
+            };
+
+            if page.flags & FLAG_BOS != 0 {
+                break page;
+            }
+        };
+
+        let mut ogg_reader = Self {
+            reader,
+            head: OpusHead::new(1, OGG_OPUS_GRANULE_RATE, 0),
+            tags: OpusTags::default(),
+            serial: first_page.serial,
+            packets: VecDeque::new(),
+            partial: Vec::new(),
+            finished: false,
+        };
+
+        ogg_reader.push_page(first_page);
+
+        let Some(head) = ogg_reader.next_raw_packet()? else {
+            bail!("The stream does not contain an OpusHead header.");
+        };
+        ogg_reader.head = OpusHead::from_bytes(&head)?;
+
+        let Some(tags) = ogg_reader.next_raw_packet()? else {
+            bail!("The stream does not contain an OpusTags header.");
+        };
+        ogg_reader.tags = OpusTags::from_bytes(&tags)?;
+
+        Ok(ogg_reader)
+    }
+
+    /// Returns the `OpusHead` header of the stream.
+    pub fn head(&self) -> &OpusHead {
+        &self.head
+    }
+
+    /// Returns the `OpusTags` header of the stream.
+    pub fn tags(&self) -> &OpusTags {
+        &self.tags
+    }
+
+    ///
+    /// Reads the next [`SoundPacket`] from the stream.
+    ///
+    /// # Behavior
+    /// Returns `None` if the end of the stream has been reached.
+    /// The returned [`SoundPacket`]-s are set up to be decoded at 48kHz with the stream's channel count (Eg.: with [`super::decode::decode_samples_opus`]).
+    /// The packets of multistream streams carry the stream's [`ChannelMapping`] (See: [`super::multistream::decode_samples_opus_multistream`]).
+    ///
+    /// # Error
+    /// Returns an error if the stream is corrupted.
+    ///
+    pub fn read_packet(&mut self) -> Result<Option<SoundPacket>> {
+        let Some(bytes) = self.next_raw_packet()? else {
+            return Ok(None);
+        };
+
+        let frame_size = opus::packet::get_nb_samples(&bytes, OGG_OPUS_GRANULE_RATE)?;
+
+        Ok(Some(SoundPacket {
+            encoder_type: EncoderType::Opus(false),
+            sample_rate: OGG_OPUS_GRANULE_RATE,
+            channels: self.head.channels as u32,
+            samples_per_frame: (frame_size * self.head.channels as usize) as u64,
+            dtx: bytes.len() <= MAX_DTX_PACKET_SIZE * self.head.stream_count as usize,
+            bytes: bytes.into(),
+            channel_mapping: (self.head.mapping_family != 0).then(|| self.head.mapping()),
+        }))
+    }
+
+    ///
+    /// Reads every remaining [`SoundPacket`] from the stream.
+    ///
+    /// # Error
+    /// Returns an error if the stream is corrupted.
+    ///
+    pub fn read_packets(&mut self) -> Result<Vec<SoundPacket>> {
+        let mut sound_packets = vec![];
+
+        while let Some(sound_packet) = self.read_packet()? {
+            sound_packets.push(sound_packet);
+        }
+
+        Ok(sound_packets)
+    }
+
+    /// Returns the next complete packet of the stream, reading new pages when needed.
+    fn next_raw_packet(&mut self) -> Result<Option<Vec<u8>>> {
+        loop {
+            if let Some(packet) = self.packets.pop_front() {
+                return Ok(Some(packet));
+            }
+
+            if self.finished {
+                return Ok(None);
+            }
+
+            match read_page(&mut self.reader)? {
+                Some(page) if page.serial == self.serial => self.push_page(page),
+                //Skip the pages of other logical streams
+                Some(_) => continue,
+                None => self.finished = true,
+            }
+        }
+    }
+
+    /// Splits a page into packets using its lacing values.
+    fn push_page(&mut self, page: Page) {
+        if page.flags & FLAG_CONTINUED == 0 {
+            //A packet which did not get continued is dropped
+            self.partial.clear();
+        }
+
+        let mut offset = 0;
+
+        for lacing_value in page.lacing_values {
+            let end = offset + lacing_value as usize;
+            self.partial.extend_from_slice(&page.body[offset..end]);
+            offset = end;
+
+            if lacing_value < 255 {
+                self.packets.push_back(std::mem::take(&mut self.partial));
+            }
+        }
+
+        if page.flags & FLAG_EOS != 0 {
+            self.finished = true;
+        }
+    }
+}
+
+///
+/// Writes a list of [`SoundPacket`]-s into an Ogg Opus (`.opus`) file.
+///
+/// # Behavior
+/// Creates (or truncates) the file at `path`. The [`OpusHead`] is created from the first [`SoundPacket`]'s channel count, sample rate and [`ChannelMapping`].
+/// `pre_skip` is the encoder's lookahead at 48kHz.
+///
+/// # Error
+/// Returns an error if the list is empty, the file could not be written or a [`SoundPacket`] is invalid.
+///
+pub fn write_ogg_opus_file(
+    path: impl AsRef<Path>,
+    sound_packets: &[SoundPacket],
+    pre_skip: u16,
+) -> Result<()> {
+    let Some(first_packet) = sound_packets.first() else {
+        bail!("There are no packets to write.");
+    };
+
+    let head = match &first_packet.channel_mapping {
+        Some(mapping) => OpusHead::with_mapping(mapping, first_packet.sample_rate, pre_skip),
+        None => OpusHead::new(
+            first_packet.channels as u8,
+            first_packet.sample_rate,
+            pre_skip,
+        ),
+    };
+
+    let mut writer = OggOpusWriter::new(BufWriter::new(File::create(path)?), head, vec![])?;
+
+    for sound_packet in sound_packets {
+        writer.write_packet(sound_packet)?;
+    }
+
+    writer.finish()?;
+
+    Ok(())
+}
+
+///
+/// Reads an Ogg Opus (`.opus`) file into a list of [`SoundPacket`]-s.
+///
+/// # Behavior
+/// Returns the [`OpusHead`] of the file alongside the packets, the decoder should be created with the header's channel count at 48kHz.
+///
+/// # Error
+/// Returns an error if the file could not be read or if it is not a valid Ogg Opus file.
+///
+pub fn read_ogg_opus_file(path: impl AsRef<Path>) -> Result<(OpusHead, Vec<SoundPacket>)> {
+    let mut reader = OggOpusReader::new(BufReader::new(File::open(path)?))?;
+
+    let sound_packets = reader.read_packets()?;
+
+    Ok((reader.head().clone(), sound_packets))
+}
+
+/// A parsed Ogg page.
+#[derive(Debug)]
+struct Page {
+    flags: u8,
+    serial: u32,
+    lacing_values: Vec<u8>,
+    body: Vec<u8>,
+}
+
+/// Reads and verifies the next page, returns `None` at the end of the input.
+fn read_page<R: Read>(reader: &mut R) -> Result<Option<Page>> {
+    let mut header = [0; 27];
+
+    //Check for the end of the input
+    let read = reader.read(&mut header)?;
+    if read == 0 {
+        return Ok(None);
+    }
+    reader.read_exact(&mut header[read..])?;
+
+    if &header[..4] != b"OggS" {
+        bail!("Invalid Ogg page capture pattern.");
+    }
+
+    if header[4] != 0 {
+        bail!("Unsupported Ogg stream structure version: {}", header[4]);
+    }
+
+    let mut lacing_values = vec![0; header[26] as usize];
+    reader.read_exact(&mut lacing_values)?;
+
+    let mut body = vec![0; lacing_values.iter().map(|value| *value as usize).sum()];
+    reader.read_exact(&mut body)?;
+
+    let checksum = u32::from_le_bytes([header[22], header[23], header[24], header[25]]);
+
+    //Verify the checksum with the checksum field zeroed
+    header[22..26].fill(0);
+
+    let mut page = Vec::with_capacity(header.len() + lacing_values.len() + body.len());
+    page.extend_from_slice(&header);
+    page.extend_from_slice(&lacing_values);
+    page.extend_from_slice(&body);
+
+    if ogg_crc32(&page) != checksum {
+        bail!("Ogg page checksum mismatch.");
+    }
+
+    Ok(Some(Page {
+        flags: header[5],
+        serial: u32::from_le_bytes([header[14], header[15], header[16], header[17]]),
+        lacing_values,
+        body,
+    }))
+}
+
+/// Reads a little endian [`u32`] at the `offset`.
+fn read_u32_le(bytes: &[u8], offset: usize) -> Result<u32> {
+    let Some(value) = bytes.get(offset..offset + 4) else {
+        bail!("Unexpected end of header.");
+    };
+
+    Ok(u32::from_le_bytes([value[0], value[1], value[2], value[3]]))
+}
+
+/// Creates a serial number for a new logical stream.
+fn stream_serial() -> u32 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.subsec_nanos() ^ duration.as_secs() as u32)
+        .unwrap_or(0)
+}
+
+/// The lookup table of the Ogg CRC-32 (polynomial `0x04c11db7`, no reflection).
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut idx = 0;
+
+    while idx < 256 {
+        let mut crc = (idx as u32) << 24;
+        let mut bit = 0;
+
+        while bit < 8 {
+            crc = if crc & 0x80000000 != 0 {
+                (crc << 1) ^ 0x04c11db7
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+
+        table[idx] = crc;
+        idx += 1;
+    }
+
+    table
+};
+
+/// Calculates the checksum of an Ogg page.
+fn ogg_crc32(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0, |crc, byte| {
+        (crc << 8) ^ CRC_TABLE[((crc >> 24) as u8 ^ byte) as usize]
+    })
+}
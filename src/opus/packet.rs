@@ -0,0 +1,359 @@
+//! Enables the inspection of [`opus`] packets without decoding them, by parsing the TOC byte and the frame packing (RFC 6716 section 3).
+
+use std::{ops::Range, time::Duration};
+
+use anyhow::{bail, Result};
+
+use crate::io::SoundPacket;
+
+use super::{encoder::OpusBandwidth, frame::FrameDuration};
+
+/// The maximum size of a single frame in bytes (RFC 6716 section 3.4, R2).
+pub const MAX_FRAME_SIZE: usize = 1275;
+
+/// The maximum duration of the audio in a single packet (RFC 6716 section 3.4, R5).
+pub const MAX_PACKET_DURATION: Duration = Duration::from_millis(120);
+
+/// The coding mode of an [`opus`] packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OpusMode {
+    /// Linear prediction based mode, used for speech at lower bandwidths.
+    Silk,
+    /// SILK for the lower and CELT for the upper frequencies.
+    Hybrid,
+    /// Transform based mode, used for music and low latency.
+    Celt,
+}
+
+/// The parsed TOC (table of contents) byte of an [`opus`] packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OpusToc {
+    /// The configuration number (`0..32`), this determines the mode, bandwidth and frame duration.
+    pub config: u8,
+    /// The coding mode of the frames.
+    pub mode: OpusMode,
+    /// The audio bandwidth of the frames.
+    pub bandwidth: OpusBandwidth,
+    /// The duration of a single frame.
+    pub frame_duration: FrameDuration,
+    /// Whether the frames are coded in stereo.
+    pub stereo: bool,
+    /// The frame count code (`0..4`), this determines how the frames are packed.
+    pub code: u8,
+}
+
+impl OpusToc {
+    /// Parses a TOC byte, every byte is a valid TOC.
+    pub fn from_byte(toc: u8) -> Self {
+        let config = toc >> 3;
+
+        let (mode, bandwidth, frame_duration) = match config {
+            0..=11 => (
+                OpusMode::Silk,
+                match config / 4 {
+                    0 => OpusBandwidth::Narrowband,
+                    1 => OpusBandwidth::Mediumband,
+                    _ => OpusBandwidth::Wideband,
+                },
+                [
+                    FrameDuration::Ms10,
+                    FrameDuration::Ms20,
+                    FrameDuration::Ms40,
+                    FrameDuration::Ms60,
+                ][config as usize % 4],
+            ),
+            12..=15 => (
+                OpusMode::Hybrid,
+                if config < 14 {
+                    OpusBandwidth::Superwideband
+                } else {
+                    OpusBandwidth::Fullband
+                },
+                [FrameDuration::Ms10, FrameDuration::Ms20][config as usize % 2],
+            ),
+            _ => (
+                OpusMode::Celt,
+                match (config - 16) / 4 {
+                    0 => OpusBandwidth::Narrowband,
+                    1 => OpusBandwidth::Wideband,
+                    2 => OpusBandwidth::Superwideband,
+                    _ => OpusBandwidth::Fullband,
+                },
+                [
+                    FrameDuration::Ms2_5,
+                    FrameDuration::Ms5,
+                    FrameDuration::Ms10,
+                    FrameDuration::Ms20,
+                ][config as usize % 4],
+            ),
+        };
+
+        Self {
+            config,
+            mode,
+            bandwidth,
+            frame_duration,
+            stereo: toc & 0b100 != 0,
+            code: toc & 0b11,
+        }
+    }
+}
+
+/// The description of an [`opus`] packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OpusPacketInfo {
+    /// The parsed TOC byte.
+    pub toc: OpusToc,
+    /// The byte ranges of the frames inside of the packet, a frame can be empty (Eg.: DTX).
+    pub frames: Vec<Range<usize>>,
+    /// Whether the frames have different sizes (variable bitrate).
+    pub vbr: bool,
+    /// The count of padding bytes at the end of the packet.
+    pub padding: usize,
+}
+
+impl OpusPacketInfo {
+    /// Returns the coding mode of the packet.
+    pub fn mode(&self) -> OpusMode {
+        self.toc.mode
+    }
+
+    /// Returns the audio bandwidth of the packet.
+    pub fn bandwidth(&self) -> OpusBandwidth {
+        self.toc.bandwidth
+    }
+
+    /// Returns whether the packet is coded in stereo.
+    pub fn is_stereo(&self) -> bool {
+        self.toc.stereo
+    }
+
+    /// Returns the count of frames in the packet.
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Returns the duration of the audio in the packet.
+    pub fn duration(&self) -> Duration {
+        self.toc.frame_duration.as_duration() * self.frames.len() as u32
+    }
+
+    /// Returns the count of samples in a single channel of the packet at the given sample rate.
+    pub fn samples_per_channel(&self, sample_rate: u32) -> usize {
+        self.toc.frame_duration.samples_per_channel(sample_rate) * self.frames.len()
+    }
+}
+
+///
+/// Parses an [`opus`] packet without decoding it.
+///
+/// # Behavior
+/// Parses the TOC byte, the frame count and the frame lengths following RFC 6716 section 3.
+/// The returned [`OpusPacketInfo`] contains the byte ranges of every frame, so frames can be extracted without copying the packet.
+///
+/// # Error
+/// Returns an error if the packet is malformed, this includes every case listed in RFC 6716 section 3.4 (Eg.: empty packets, frames longer than 1275 bytes, packets longer than 120 ms, truncated lengths).
+///
+pub fn parse_opus_packet(packet: &[u8]) -> Result<OpusPacketInfo> {
+    let Some(&toc_byte) = packet.first() else {
+        bail!("Malformed opus packet: the packet is empty.");
+    };
+
+    let toc = OpusToc::from_byte(toc_byte);
+    let mut offset = 1;
+    let mut padding = 0;
+    let mut vbr = false;
+
+    let frames = match toc.code {
+        //One frame
+        0 => vec![check_frame_size(offset..packet.len())?],
+        //Two frames of equal size
+        1 => {
+            let payload = packet.len() - offset;
+
+            if !payload.is_multiple_of(2) {
+                bail!("Malformed opus packet: two equal frames can not make up {payload} bytes.");
+            }
+
+            let frame_size = payload / 2;
+
+            vec![
+                check_frame_size(offset..offset + frame_size)?,
+                check_frame_size(offset + frame_size..packet.len())?,
+            ]
+        }
+        //Two frames of different size
+        2 => {
+            vbr = true;
+
+            let first_size = read_frame_size(packet, &mut offset)?;
+
+            if offset + first_size > packet.len() {
+                bail!("Malformed opus packet: the first frame is longer than the packet.");
+            }
+
+            vec![
+                check_frame_size(offset..offset + first_size)?,
+                check_frame_size(offset + first_size..packet.len())?,
+            ]
+        }
+        //An arbitrary number of frames
+        _ => {
+            let Some(&frame_count_byte) = packet.get(offset) else {
+                bail!("Malformed opus packet: the frame count byte is missing.");
+            };
+            offset += 1;
+
+            vbr = frame_count_byte & 0b1000_0000 != 0;
+            let padded = frame_count_byte & 0b0100_0000 != 0;
+            let frame_count = (frame_count_byte & 0b0011_1111) as usize;
+
+            if frame_count == 0 {
+                bail!("Malformed opus packet: the packet contains 0 frames.");
+            }
+
+            if toc.frame_duration.as_duration() * frame_count as u32 > MAX_PACKET_DURATION {
+                bail!(
+                    "Malformed opus packet: {frame_count} frames of {:?} are longer than {MAX_PACKET_DURATION:?}.",
+                    toc.frame_duration
+                );
+            }
+
+            if padded {
+                padding = read_padding_size(packet, &mut offset)?;
+            }
+
+            let Some(end) = packet
+                .len()
+                .checked_sub(padding)
+                .filter(|end| *end >= offset)
+            else {
+                bail!("Malformed opus packet: the padding is longer than the packet.");
+            };
+
+            let mut frames = Vec::with_capacity(frame_count);
+
+            if vbr {
+                //Every frame's size is coded except the last one's
+                let mut frame_sizes = Vec::with_capacity(frame_count);
+
+                for _ in 0..frame_count - 1 {
+                    frame_sizes.push(read_frame_size(&packet[..end], &mut offset)?);
+                }
+
+                for frame_size in frame_sizes {
+                    if offset + frame_size > end {
+                        bail!("Malformed opus packet: the frames are longer than the packet.");
+                    }
+
+                    frames.push(check_frame_size(offset..offset + frame_size)?);
+                    offset += frame_size;
+                }
+
+                frames.push(check_frame_size(offset..end)?);
+            } else {
+                let payload = end - offset;
+
+                if !payload.is_multiple_of(frame_count) {
+                    bail!(
+                        "Malformed opus packet: {frame_count} equal frames can not make up {payload} bytes."
+                    );
+                }
+
+                let frame_size = payload / frame_count;
+
+                for index in 0..frame_count {
+                    let start = offset + index * frame_size;
+
+                    frames.push(check_frame_size(start..start + frame_size)?);
+                }
+            }
+
+            frames
+        }
+    };
+
+    Ok(OpusPacketInfo {
+        toc,
+        frames,
+        vbr,
+        padding,
+    })
+}
+
+///
+/// Parses the bytes of an [`opus`] encoded [`SoundPacket`] without decoding it.
+///
+/// # Behavior
+/// See: [`parse_opus_packet`].
+///
+/// # Error
+/// Returns an error if the [`SoundPacket`] was not encoded with [`opus`], or the packet is malformed.
+///
+pub fn inspect_sound_packet(sound_packet: &SoundPacket) -> Result<OpusPacketInfo> {
+    if !matches!(sound_packet.encoder_type, crate::io::EncoderType::Opus(_)) {
+        bail!(
+            "Can not inspect a {:?} packet as an opus packet.",
+            sound_packet.encoder_type
+        );
+    }
+
+    parse_opus_packet(&sound_packet.bytes)
+}
+
+/// Reads a frame length coded in one or two bytes (RFC 6716 section 3.2.1).
+fn read_frame_size(packet: &[u8], offset: &mut usize) -> Result<usize> {
+    let Some(&first) = packet.get(*offset) else {
+        bail!("Malformed opus packet: a frame length is missing.");
+    };
+
+    if first < 252 {
+        *offset += 1;
+
+        return Ok(first as usize);
+    }
+
+    let Some(&second) = packet.get(*offset + 1) else {
+        bail!("Malformed opus packet: a two byte frame length is truncated.");
+    };
+
+    *offset += 2;
+
+    Ok(second as usize * 4 + first as usize)
+}
+
+/// Reads the padding length of a code 3 packet (RFC 6716 section 3.2.5).
+fn read_padding_size(packet: &[u8], offset: &mut usize) -> Result<usize> {
+    let mut padding = 0;
+
+    loop {
+        let Some(&byte) = packet.get(*offset) else {
+            bail!("Malformed opus packet: the padding length is truncated.");
+        };
+        *offset += 1;
+
+        //255 means 254 bytes of padding and another length byte
+        if byte == 255 {
+            padding += 254;
+        } else {
+            padding += byte as usize;
+
+            return Ok(padding);
+        }
+    }
+}
+
+/// Checks that a frame is not longer than [`MAX_FRAME_SIZE`].
+fn check_frame_size(frame: Range<usize>) -> Result<Range<usize>> {
+    if frame.len() > MAX_FRAME_SIZE {
+        bail!(
+            "Malformed opus packet: a frame is {} bytes long, the maximum is {MAX_FRAME_SIZE}.",
+            frame.len()
+        );
+    }
+
+    Ok(frame)
+}
@@ -0,0 +1,189 @@
+//! Enables merging multiple [`opus`] packets into one, and splitting them back apart without decoding them.
+
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use opus::Repacketizer;
+
+use crate::io::SoundPacket;
+
+use super::{
+    encode::MAX_DTX_PACKET_SIZE,
+    frame::FrameDuration,
+    packet::{parse_opus_packet, MAX_PACKET_DURATION},
+};
+
+///
+/// Merges a list of [`SoundPacket`]-s (encoded with the [`opus`] codec) into a single [`SoundPacket`].
+///
+/// # Behavior
+/// The frames of the packets are copied into a single packet, so the per-packet overhead is only paid once (Eg.: three 20 ms packets are merged into one 60 ms packet).
+/// The `samples_per_frame` of the merged packet is the sum of the merged packets' `samples_per_frame`, so the position (timestamp) of every following packet stays the same.
+/// The merged packet is only marked as DTX if every merged packet was DTX.
+///
+/// # Error
+/// Returns an error if the list is empty, the packets have different sample rates or channel counts, or they are multistream packets.
+/// Returns an error if the packets can not be merged, this happens if the packets have different modes, bandwidths or frame durations, or the merged packet would be longer than 120 ms.
+///
+pub fn merge_sound_packets(sound_packets: &[SoundPacket]) -> Result<SoundPacket> {
+    let Some(first) = sound_packets.first() else {
+        bail!("Can not merge an empty list of sound packets.");
+    };
+
+    let mut frame_count = 0;
+
+    for sound_packet in sound_packets {
+        if sound_packet.sample_rate != first.sample_rate || sound_packet.channels != first.channels
+        {
+            bail!(
+                "Can not merge sound packets with different formats: {} Hz, {} channels and {} Hz, {} channels.",
+                first.sample_rate,
+                first.channels,
+                sound_packet.sample_rate,
+                sound_packet.channels
+            );
+        }
+
+        if sound_packet.channel_mapping.is_some() {
+            bail!("Multistream sound packets can not be merged.");
+        }
+
+        frame_count += parse_opus_packet(&sound_packet.bytes)?.frame_count();
+    }
+
+    let mut repacketizer = Repacketizer::new()?;
+    let mut state = repacketizer.begin();
+
+    for sound_packet in sound_packets {
+        state.cat(&sound_packet.bytes)?;
+    }
+
+    //The merged packet is at most 2 bytes longer per frame (frame lengths) and 2 bytes longer in its header
+    let mut compressed_buffer = vec![
+        0;
+        sound_packets
+            .iter()
+            .map(|sound_packet| sound_packet.bytes.len())
+            .sum::<usize>()
+            + frame_count * 2
+            + 2
+    ];
+
+    let encoded_bytes_count = state.out(&mut compressed_buffer)?;
+    compressed_buffer.truncate(encoded_bytes_count);
+
+    Ok(SoundPacket {
+        encoder_type: first.encoder_type,
+        sample_rate: first.sample_rate,
+        channels: first.channels,
+        bytes: compressed_buffer.into(),
+        samples_per_frame: sound_packets
+            .iter()
+            .map(|sound_packet| sound_packet.samples_per_frame)
+            .sum(),
+        dtx: sound_packets.iter().all(|sound_packet| sound_packet.dtx),
+        channel_mapping: None,
+    })
+}
+
+///
+/// Splits a [`SoundPacket`] (encoded with the [`opus`] codec) into a [`SoundPacket`] per frame.
+///
+/// # Behavior
+/// This is the inverse of [`merge_sound_packets`], the frames are copied without decoding them.
+/// The `samples_per_frame` of the packet is divided evenly between the frames, since every frame of an [`opus`] packet has the same duration.
+///
+/// # Error
+/// Returns an error if the packet is malformed, or it is a multistream packet.
+///
+pub fn split_sound_packet(sound_packet: &SoundPacket) -> Result<Vec<SoundPacket>> {
+    if sound_packet.channel_mapping.is_some() {
+        bail!("Multistream sound packets can not be split.");
+    }
+
+    let info = parse_opus_packet(&sound_packet.bytes)?;
+    let frame_count = info.frame_count();
+
+    if !sound_packet
+        .samples_per_frame
+        .is_multiple_of(frame_count as u64)
+    {
+        bail!(
+            "The sound packet's sample count ({}) can not be divided between {frame_count} frames.",
+            sound_packet.samples_per_frame
+        );
+    }
+
+    let mut repacketizer = Repacketizer::new()?;
+    let mut state = repacketizer.begin();
+
+    state.cat(&sound_packet.bytes)?;
+
+    let mut sound_packets = Vec::with_capacity(frame_count);
+
+    for (index, frame) in info.frames.iter().enumerate() {
+        //TOC byte + frame
+        let mut compressed_buffer = vec![0; frame.len() + 1];
+
+        let encoded_bytes_count = state.out_range(index, index + 1, &mut compressed_buffer)?;
+        compressed_buffer.truncate(encoded_bytes_count);
+
+        sound_packets.push(SoundPacket {
+            encoder_type: sound_packet.encoder_type,
+            sample_rate: sound_packet.sample_rate,
+            channels: sound_packet.channels,
+            dtx: compressed_buffer.len() <= MAX_DTX_PACKET_SIZE,
+            bytes: compressed_buffer.into(),
+            samples_per_frame: sound_packet.samples_per_frame / frame_count as u64,
+            channel_mapping: None,
+        });
+    }
+
+    Ok(sound_packets)
+}
+
+///
+/// Repacketizes a stream of [`SoundPacket`]-s (encoded with the [`opus`] codec) into packets of the given duration.
+///
+/// # Behavior
+/// Consecutive packets are merged until they make up the `packet_duration`, the packets are never split.
+/// If the next packet can not be merged into the current one (Eg.: the encoder switched modes, or the duration would be exceeded), the current packet is finished early.
+/// The order of the audio is kept, and the sum of the `samples_per_frame` does not change, so the positions (timestamps) of the packets stay consistent.
+///
+/// # Error
+/// Returns an error if a packet is malformed, or the packets have different sample rates or channel counts.
+///
+pub fn repacketize_sound_packets(
+    sound_packets: &[SoundPacket],
+    packet_duration: FrameDuration,
+) -> Result<Vec<SoundPacket>> {
+    let target = packet_duration.as_duration().min(MAX_PACKET_DURATION);
+    let mut repacketized = vec![];
+    let mut group_start = 0;
+    let mut group_duration = Duration::ZERO;
+    let mut group_config = None;
+
+    for (index, sound_packet) in sound_packets.iter().enumerate() {
+        let info = parse_opus_packet(&sound_packet.bytes)?;
+        //The mode, bandwidth, frame duration and stereo flag must match for the frames to be merged
+        let config = (info.toc.config, info.toc.stereo);
+
+        if index > group_start
+            && (group_config != Some(config) || group_duration + info.duration() > target)
+        {
+            repacketized.push(merge_sound_packets(&sound_packets[group_start..index])?);
+
+            group_start = index;
+            group_duration = Duration::ZERO;
+        }
+
+        group_config = Some(config);
+        group_duration += info.duration();
+    }
+
+    if group_start < sound_packets.len() {
+        repacketized.push(merge_sound_packets(&sound_packets[group_start..])?);
+    }
+
+    Ok(repacketized)
+}
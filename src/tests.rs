@@ -13,14 +13,67 @@ mod tests {
 
     use tokio::sync::oneshot;
     use crate::{
-        avif::encoding::encode_raw_image,
+        avif::{
+            container::{parse_avif, MATRIX_BT601},
+            decoding::{yuv_to_rgb8, ChromaSubsampling, YuvFrame, YuvMatrix},
+            encoding::{
+                encode_image, encode_raw_image, encode_raw_image_10_bit,
+                encode_raw_image_grayscale, encode_raw_image_rgba, encode_raw_image_rgba_10_bit,
+                encode_raw_image_with_budget, AvifColorSpace, AvifEncoderConfig, BudgetEncoder,
+                ByteBudgetConfig,
+            },
+            scale::{crop_raw_image, scale_raw_image, CropRect, ScaleConfig, ScaleFilter, ScaleMode},
+            video::{Av1VideoEncoder, VideoEncoderConfig},
+            yuv::rgb_to_yuv420,
+        },
         cam,
-        io::{self, playback::stream_audio, record::record_audio_with_interrupt},
+        io::{
+            self,
+            gain::{GainControl, GainRamp},
+            playback::{stream_audio, stream_audio_queue, stream_audio_with_gain},
+            record::record_audio_with_interrupt,
+            resample::{resample, Resampler},
+        },
         opus::{
-            decode::{create_opus_decoder, decode_samples_opus},
-            encode::{create_opus_encoder, encode_samples_opus},
+            adaptive::{
+                AdaptiveBitrateConfig, AdaptiveBitrateController, BitrateAdjustment,
+                NetworkFeedback,
+            },
+            decode::{
+                create_opus_decoder, create_opus_decoder_for_packet, decode_missing_frame_opus,
+                decode_sample_set_size_opus, decode_sample_set_size_opus_into,
+                decode_samples_opus,
+            },
+            encode::{
+                create_opus_encoder, create_opus_encoder_with_config, encode_sample_set_size_opus,
+                encode_sample_set_size_opus_into, encode_samples_opus, OpusEncoderSession,
+            },
+            encoder::{OpusBandwidth, OpusBitrate, OpusEncoderConfig, OpusSignal},
+            frame::FrameDuration,
+            multistream::{
+                create_opus_multistream_decoder_for_packet, create_opus_multistream_encoder,
+                decode_sample_set_size_opus_multistream_into, decode_samples_opus_multistream,
+                encode_sample_set_size_opus_multistream_into, encode_samples_opus_multistream,
+                ChannelLayout,
+                OpusMultistreamDecoder, OpusMultistreamEncoder,
+            },
+            ogg::{OggOpusReader, OggOpusWriter, OpusHead},
+            packet::{inspect_sound_packet, parse_opus_packet, OpusMode},
+            repacketize::{merge_sound_packets, repacketize_sound_packets, split_sound_packet},
         },
     };
+    #[cfg(feature = "flac")]
+    use crate::flac::{
+        decode::{decode_sample_set_size_flac, decode_samples_flac},
+        encode::{encode_samples_flac, FlacEncoder},
+        file::{read_flac, write_flac, FlacStreamInfo},
+    };
+    #[cfg(feature = "g711")]
+    use crate::g711::{
+        decode::decode_samples_g711,
+        encode::encode_samples_g711,
+        law::{decode_a_law, decode_mu_law, encode_a_law, encode_mu_law},
+    };
 
     #[test]
     fn image_encode() {
@@ -69,6 +122,179 @@ mod tests {
         sleep(Duration::from_secs(1));
     }
 
+    #[test]
+    fn audio_playback_with_gain() {
+        let host = io::default_host();
+        let audio_device = io::get_audio_device(host);
+
+        let output_device = audio_device.output.unwrap();
+
+        let sample_rate = output_device
+            .default_output_config()
+            .unwrap()
+            .sample_rate()
+            .0 as f32;
+
+        let mut sample_clock = 0f32;
+
+        let next_value = move || {
+            sample_clock = (sample_clock + 1.0) % sample_rate;
+            Some((sample_clock * 440.0 * 2.0 * std::f32::consts::PI / sample_rate).sin())
+        };
+
+        let err_callback = |err| eprintln!("an error occurred on stream: {}", err);
+
+        let gain = GainControl::new(0.5);
+        let stream = stream_audio_with_gain(
+            output_device,
+            gain.clone(),
+            err_callback,
+            std::iter::from_fn(next_value),
+        )
+        .unwrap();
+
+        stream.play().unwrap();
+
+        sleep(Duration::from_millis(500));
+        assert_eq!(gain.current_gain(), 0.5);
+
+        gain.set_muted(true);
+        sleep(Duration::from_millis(500));
+        assert_eq!(gain.current_gain(), 0.);
+
+        gain.set_muted(false);
+        sleep(Duration::from_millis(500));
+
+        gain.fade_out_blocking();
+        assert_eq!(gain.current_gain(), 0.);
+    }
+
+    #[test]
+    fn audio_playback_queue() {
+        let host = io::default_host();
+        let audio_device = io::get_audio_device(host);
+
+        let err_callback = |err| eprintln!("an error occurred on stream: {}", err);
+
+        let (stream, handle) =
+            stream_audio_queue::<f32, _>(audio_device.output.unwrap(), err_callback, 96000)
+                .unwrap();
+
+        let sample_rate = handle.sample_rate() as f32;
+        let channels = handle.channels() as usize;
+
+        stream.play().unwrap();
+
+        //Feed 20ms chunks like a receive -> decode -> play loop would
+        let chunk_frames = (sample_rate / 50.) as usize;
+        let mut sample_clock = 0f32;
+
+        for _ in 0..50 {
+            let mut chunk = Vec::with_capacity(chunk_frames * channels);
+
+            for _ in 0..chunk_frames {
+                sample_clock = (sample_clock + 1.0) % sample_rate;
+                let value = (sample_clock * 440.0 * 2.0 * std::f32::consts::PI / sample_rate).sin();
+                chunk.extend(std::iter::repeat_n(value, channels));
+            }
+
+            handle.push(&chunk);
+
+            sleep(Duration::from_millis(20));
+        }
+
+        dbg!(handle.queue_depth(), handle.underruns(), handle.overflows());
+
+        handle.flush();
+
+        assert_eq!(handle.queue_depth(), 0);
+    }
+
+    #[test]
+    fn audio_resampling() {
+        //One second of a 440Hz mono sine at 48kHz
+        let samples: Vec<f32> = (0..48000)
+            .map(|idx| (idx as f32 * 440.0 * 2.0 * std::f32::consts::PI / 48000.).sin())
+            .collect();
+
+        let resampled = resample(&samples, 48000, 1, 44100, 2).unwrap();
+
+        assert_eq!(resampled.len(), 44100 * 2);
+        assert!(resampled.chunks_exact(2).all(|frame| frame[0] == frame[1]));
+
+        //Feeding the samples in chunks must give the same result
+        let mut resampler = Resampler::new(48000, 1, 44100, 2).unwrap();
+        let mut chunked = vec![];
+
+        for chunk in samples.chunks(960) {
+            resampler.process(chunk, &mut chunked);
+        }
+        resampler.flush(&mut chunked);
+
+        assert_eq!(resampled.len(), chunked.len());
+        assert!(resampled
+            .iter()
+            .zip(&chunked)
+            .all(|(whole, chunked)| (whole - chunked).abs() < 1e-4));
+
+        //The last input frame is released when upsampling too
+        let upsampled = resample(&samples[..8000], 8000, 1, 48000, 1).unwrap();
+        assert_eq!(upsampled.len(), 48000);
+        assert_eq!(upsampled.last(), samples[..8000].last());
+
+        //The flushed resampler starts over
+        let mut flushed = vec![];
+        resampler.process(&samples[..8000], &mut flushed);
+        resampler.flush(&mut flushed);
+        assert_eq!(flushed.len(), resample(&samples[..8000], 48000, 1, 44100, 2).unwrap().len());
+    }
+
+    #[test]
+    fn audio_gain_ramp() {
+        let gain = GainControl::new(0.5);
+        gain.set_fade_duration(Duration::from_millis(10));
+
+        let mut gain_ramp = GainRamp::new(gain.clone(), 48000);
+
+        //The stream fades in from silence
+        let mut samples = vec![1.0f32; 960 * 2];
+        gain_ramp.apply(&mut samples, 2);
+
+        assert!(samples[0] < 0.01);
+        assert_eq!(samples[samples.len() - 1], 0.5);
+        assert_eq!(gain.current_gain(), 0.5);
+
+        //Muting ramps to silence without jumping
+        gain.set_muted(true);
+
+        let mut samples = vec![1.0f32; 960 * 2];
+        gain_ramp.apply(&mut samples, 2);
+
+        assert!(samples
+            .windows(2)
+            .all(|window| (window[0] - window[1]).abs() <= 1. / 480. + 1e-6));
+        assert_eq!(gain.current_gain(), 0.);
+
+        //Two streams driven by the same control don't overwrite each other's gain
+        gain.set_muted(false);
+
+        let mut first_ramp = GainRamp::new(gain.clone(), 48000);
+        let mut second_ramp = GainRamp::new(gain.clone(), 48000);
+        drop(gain_ramp);
+
+        first_ramp.apply(&mut [1.0f32; 960], 1);
+        second_ramp.apply(&mut [1.0f32; 10], 1);
+
+        //The loudest stream is reported, the second stream is still fading in
+        assert_eq!(gain.current_gain(), 0.5);
+
+        drop(first_ramp);
+        assert!(gain.current_gain() < 0.5);
+
+        drop(second_ramp);
+        assert_eq!(gain.current_gain(), 0.);
+    }
+
     #[test]
     fn audio_recording_and_playback() {
         let host = io::default_host();
@@ -114,8 +340,6 @@ mod tests {
         let audio_device = io::get_audio_device(host);
         let config = audio_device.get_input_config().unwrap().unwrap();
 
-        let channels = Channels::Stereo;
-
         let sample = record_audio(audio_device.input.unwrap(), config.clone());
 
         let encoder = create_opus_encoder(
@@ -126,9 +350,9 @@ mod tests {
         )
         .unwrap();
 
-        let sound_packets: Vec<crate::io::SoundPacket> = encode_samples_opus(encoder, &Into::<Vec<f32>>::into(sample), 20, channels).unwrap();
+        let sound_packets: Vec<crate::io::SoundPacket> = encode_samples_opus(encoder, &Into::<Vec<f32>>::into(sample), FrameDuration::Ms20).unwrap();
 
-        let decoder = create_opus_decoder(48000).unwrap();
+        let decoder = create_opus_decoder_for_packet(&sound_packets[0]).unwrap();
 
         dbg!(sound_packets.deep_size_of());
 
@@ -149,4 +373,1662 @@ mod tests {
 
         sleep(Duration::from_secs(3));
     }
+
+    #[test]
+    fn ogg_opus_roundtrip() {
+        //Two seconds of a 440Hz stereo sine at 48kHz
+        let samples: Vec<f32> = (0..96000)
+            .flat_map(|idx| {
+                let value = (idx as f32 * 440.0 * 2.0 * std::f32::consts::PI / 48000.).sin();
+                [value, value]
+            })
+            .collect();
+
+        let encoder = create_opus_encoder(
+            48000,
+            opus::Application::Audio,
+            opus::Bitrate::Bits(64000),
+            opus::Channels::Stereo,
+        )
+        .unwrap();
+
+        let sound_packets = encode_samples_opus(encoder, &samples, FrameDuration::Ms20).unwrap();
+
+        let mut writer = OggOpusWriter::new(
+            vec![],
+            OpusHead::new(2, 48000, 312),
+            vec!["TITLE=Roundtrip".to_string()],
+        )
+        .unwrap();
+
+        for sound_packet in &sound_packets {
+            writer.write_packet(sound_packet).unwrap();
+        }
+
+        let file = writer.finish().unwrap();
+
+        let mut reader = OggOpusReader::new(file.as_slice()).unwrap();
+
+        assert_eq!(reader.head(), &OpusHead::new(2, 48000, 312));
+        assert_eq!(reader.tags().comments, vec!["TITLE=Roundtrip".to_string()]);
+
+        let read_packets = reader.read_packets().unwrap();
+
+        assert_eq!(read_packets.len(), sound_packets.len());
+
+        for (read_packet, sound_packet) in read_packets.iter().zip(&sound_packets) {
+            assert_eq!(read_packet.bytes, sound_packet.bytes);
+            assert_eq!(read_packet.samples_per_frame, sound_packet.samples_per_frame);
+        }
+
+        let decoder = create_opus_decoder(48000, Channels::Stereo).unwrap();
+        let decoded = decode_samples_opus(decoder, read_packets).unwrap();
+
+        assert_eq!(decoded.len(), samples.len());
+
+        //A corrupted page must be rejected
+        let mut corrupted = file.clone();
+        let last_idx = corrupted.len() - 1;
+        corrupted[last_idx] ^= 0xff;
+
+        let mut reader = OggOpusReader::new(corrupted.as_slice()).unwrap();
+
+        assert!(reader.read_packets().is_err());
+    }
+
+    #[test]
+    fn opus_encoder_session() {
+        //One second of a 440Hz mono sine at 48kHz
+        let samples: Vec<f32> = (0..48000)
+            .map(|idx| (idx as f32 * 440.0 * 2.0 * std::f32::consts::PI / 48000.).sin())
+            .collect();
+
+        let encoder = create_opus_encoder(
+            48000,
+            opus::Application::Voip,
+            opus::Bitrate::Bits(24000),
+            opus::Channels::Mono,
+        )
+        .unwrap();
+
+        let mut session = OpusEncoderSession::new(encoder, FrameDuration::Ms20);
+
+        //Feed the audio in uneven pieces
+        let mut sound_packets = vec![];
+
+        for piece in samples.chunks(1234) {
+            sound_packets.extend(session.push(piece).unwrap());
+        }
+
+        assert_eq!(sound_packets.len(), 48000 / 960);
+        assert_eq!(session.buffered_samples(), 0);
+        assert!(session.flush().unwrap().is_none());
+        assert!(sound_packets.iter().all(|sound_packet| sound_packet.channels == 1
+            && sound_packet.samples_per_frame == 960));
+
+        //Mono packets decode into mono samples
+        let decoder = create_opus_decoder_for_packet(&sound_packets[0]).unwrap();
+        let decoded = decode_samples_opus(decoder, sound_packets).unwrap();
+
+        assert_eq!(decoded.len(), samples.len());
+
+        //Incomplete frames are only encoded when flushing
+        assert!(session.push(&samples[..100]).unwrap().is_empty());
+        assert_eq!(session.buffered_samples(), 100);
+        assert!(session.flush().unwrap().is_some());
+        assert_eq!(session.buffered_samples(), 0);
+    }
+
+    #[test]
+    fn opus_encoder_config() {
+        let config = OpusEncoderConfig::voip();
+
+        let mut encoder =
+            create_opus_encoder_with_config(48000, opus::Application::Voip, Channels::Mono, &config)
+                .unwrap();
+
+        assert_eq!(encoder.config().unwrap(), config);
+
+        //Change the settings on the live encoder
+        let music_config = OpusEncoderConfig {
+            bitrate: OpusBitrate::Bits(96000),
+            ..OpusEncoderConfig::music()
+        };
+
+        encoder.apply_config(&music_config).unwrap();
+
+        assert_eq!(encoder.config().unwrap(), music_config);
+        assert_eq!(encoder.signal().unwrap(), OpusSignal::Music);
+
+        //The requested bitrate is reported, not the one the encoder resolved it to
+        encoder.set_bitrate(OpusBitrate::Auto).unwrap();
+        assert_eq!(encoder.bitrate(), OpusBitrate::Auto);
+        assert!(encoder.resolved_bitrate().unwrap() > 0);
+
+        encoder.apply_config(&OpusEncoderConfig::default()).unwrap();
+        assert_eq!(encoder.config().unwrap(), OpusEncoderConfig::default());
+
+        //FEC is independent of the application
+        encoder.set_inband_fec(false).unwrap();
+        assert!(!encoder.inband_fec().unwrap());
+
+        assert!(encoder.set_complexity(11).is_err());
+    }
+
+    #[test]
+    fn opus_frame_duration() {
+        assert_eq!(FrameDuration::from_millis(20).unwrap(), FrameDuration::Ms20);
+        assert!(FrameDuration::from_millis(15).is_err());
+        assert!(FrameDuration::from_millis(30).is_err());
+        assert_eq!(
+            FrameDuration::from_duration(Duration::from_micros(2500)).unwrap(),
+            FrameDuration::Ms2_5
+        );
+
+        assert_eq!(FrameDuration::Ms2_5.samples_per_channel(48000), 120);
+        assert_eq!(FrameDuration::Ms20.samples_per_frame(48000, Channels::Stereo), 1920);
+        assert_eq!(
+            FrameDuration::from_samples_per_channel(960, 48000).unwrap(),
+            FrameDuration::Ms20
+        );
+        assert!(FrameDuration::from_samples_per_channel(1000, 48000).is_err());
+
+        assert_eq!(FrameDuration::Ms20.byte_budget(32000), 80);
+        assert_eq!(FrameDuration::Ms20.bitrate_for_byte_budget(80), 32000);
+
+        //Every duration must be accepted by the encoder
+        for frame_duration in FrameDuration::ALL {
+            let encoder = create_opus_encoder(
+                48000,
+                opus::Application::Audio,
+                opus::Bitrate::Bits(64000),
+                opus::Channels::Stereo,
+            )
+            .unwrap();
+
+            let samples = vec![0.; frame_duration.samples_per_frame(48000, Channels::Stereo)];
+            let sound_packets = encode_samples_opus(encoder, &samples, frame_duration).unwrap();
+
+            assert_eq!(sound_packets.len(), 1);
+        }
+    }
+
+    #[test]
+    fn opus_dtx_comfort_noise() {
+        let mut encoder = create_opus_encoder(
+            48000,
+            opus::Application::Voip,
+            opus::Bitrate::Bits(24000),
+            opus::Channels::Mono,
+        )
+        .unwrap();
+
+        encoder.set_dtx(true).unwrap();
+
+        //Half a second of tone, then two seconds of silence
+        let mut samples: Vec<f32> = (0..24000)
+            .map(|index| (index as f32 * 440.0 * 2.0 * std::f32::consts::PI / 48000.).sin() * 0.5)
+            .collect();
+        samples.resize(24000 + 96000, 0.);
+
+        let sound_packets = encode_samples_opus(encoder, &samples, FrameDuration::Ms20).unwrap();
+
+        //None of the tone's frames are in DTX, the encoder only enters it during the silence
+        assert!(sound_packets[..25].iter().all(|sound_packet| !sound_packet.dtx));
+        assert!(sound_packets.iter().any(|sound_packet| sound_packet.dtx));
+
+        //Drop the DTX packets, and fill the gaps with comfort noise
+        let mut decoder = create_opus_decoder(48000, Channels::Mono).unwrap();
+        let mut decoded = vec![];
+        let packet_count = sound_packets.len();
+
+        for sound_packet in sound_packets {
+            if sound_packet.dtx {
+                decoded.extend(
+                    decode_missing_frame_opus(
+                        &mut decoder,
+                        sound_packet.samples_per_frame,
+                        Channels::Mono,
+                    )
+                    .unwrap(),
+                );
+            } else {
+                decoded.extend(
+                    decode_sample_set_size_opus(&mut decoder, sound_packet, false)
+                        .unwrap(),
+                );
+            }
+        }
+
+        assert_eq!(decoded.len(), packet_count * 960);
+        assert!(decoded.iter().all(|sample| sample.is_finite()));
+    }
+
+    #[test]
+    fn opus_packet_inspection() {
+        let encoder = create_opus_encoder(
+            48000,
+            opus::Application::Audio,
+            opus::Bitrate::Bits(64000),
+            opus::Channels::Stereo,
+        )
+        .unwrap();
+
+        let samples: Vec<f32> = (0..1920)
+            .map(|idx| (idx as f32 * 440.0 * 2.0 * std::f32::consts::PI / 48000.).sin())
+            .collect();
+
+        let sound_packets = encode_samples_opus(encoder, &samples, FrameDuration::Ms20).unwrap();
+        let info = inspect_sound_packet(&sound_packets[0]).unwrap();
+
+        assert_eq!(info.frame_count(), 1);
+        assert_eq!(info.duration(), Duration::from_millis(20));
+        assert_eq!(info.samples_per_channel(48000), 960);
+        assert!(info.is_stereo());
+        assert_eq!(info.mode(), OpusMode::Celt);
+
+        //Config 1 (SILK NB 20 ms), mono, code 3 CBR with padding: 3 frames of 2 bytes and 2 bytes of padding
+        let packet = [1 << 3 | 3, 0b0100_0011, 2, 1, 1, 2, 2, 3, 3, 0, 0];
+        let info = parse_opus_packet(&packet).unwrap();
+
+        assert_eq!(info.mode(), OpusMode::Silk);
+        assert_eq!(info.bandwidth(), OpusBandwidth::Narrowband);
+        assert!(!info.is_stereo());
+        assert_eq!(info.frames, vec![3..5, 5..7, 7..9]);
+        assert_eq!(info.padding, 2);
+        assert_eq!(info.duration(), Duration::from_millis(60));
+
+        //Config 13 (Hybrid SWB 20 ms), code 2 with a two byte length
+        let mut packet = vec![13 << 3 | 0b100 | 2, 252, 1];
+        packet.extend(vec![0; 256 + 10]);
+        let info = parse_opus_packet(&packet).unwrap();
+
+        assert_eq!(info.mode(), OpusMode::Hybrid);
+        assert_eq!(info.bandwidth(), OpusBandwidth::Superwideband);
+        assert_eq!(info.frames, vec![3..259, 259..269]);
+
+        //Malformed packets
+        assert!(parse_opus_packet(&[]).is_err());
+        //Odd payload of two equal frames
+        assert!(parse_opus_packet(&[1, 0, 0, 0]).is_err());
+        //The first frame is longer than the packet
+        assert!(parse_opus_packet(&[2, 10, 0]).is_err());
+        //Zero frames
+        assert!(parse_opus_packet(&[3, 0]).is_err());
+        //7 frames of 20 ms are longer than 120 ms
+        assert!(parse_opus_packet(&[1 << 3 | 3, 7]).is_err());
+        //Frame longer than 1275 bytes
+        assert!(parse_opus_packet(&[0; 1300]).is_err());
+    }
+
+    #[test]
+    fn opus_repacketizer() {
+        let encoder = create_opus_encoder(
+            48000,
+            opus::Application::Voip,
+            opus::Bitrate::Bits(32000),
+            opus::Channels::Mono,
+        )
+        .unwrap();
+
+        let samples: Vec<f32> = (0..48000)
+            .map(|idx| (idx as f32 * 440.0 * 2.0 * std::f32::consts::PI / 48000.).sin())
+            .collect();
+
+        let sound_packets = encode_samples_opus(encoder, &samples, FrameDuration::Ms20).unwrap();
+
+        //Three 20 ms packets into one 60 ms packet
+        let merged = merge_sound_packets(&sound_packets[..3]).unwrap();
+        let info = inspect_sound_packet(&merged).unwrap();
+
+        assert_eq!(info.frame_count(), 3);
+        assert_eq!(info.duration(), Duration::from_millis(60));
+        assert_eq!(merged.samples_per_frame, 960 * 3);
+
+        //Splitting returns the original packets
+        let split = split_sound_packet(&merged).unwrap();
+
+        assert_eq!(split.len(), 3);
+        for (split, original) in split.iter().zip(&sound_packets) {
+            assert_eq!(split.bytes, original.bytes);
+            assert_eq!(split.samples_per_frame, original.samples_per_frame);
+        }
+
+        //The whole stream keeps its sample count
+        let repacketized = repacketize_sound_packets(&sound_packets, FrameDuration::Ms60).unwrap();
+        let total = |sound_packets: &[io::SoundPacket]| {
+            sound_packets
+                .iter()
+                .map(|sound_packet| sound_packet.samples_per_frame)
+                .sum::<u64>()
+        };
+
+        assert!(repacketized.len() < sound_packets.len());
+        assert_eq!(total(&repacketized), total(&sound_packets));
+
+        let decoder = create_opus_decoder(48000, Channels::Mono).unwrap();
+        let decoded = decode_samples_opus(decoder, repacketized).unwrap();
+
+        assert_eq!(decoded.len(), sound_packets.len() * 960);
+
+        //Packets longer than 120 ms can not be merged
+        assert!(merge_sound_packets(&sound_packets[..7]).is_err());
+        assert!(merge_sound_packets(&[]).is_err());
+    }
+
+    #[test]
+    fn opus_adaptive_bitrate() {
+        let config = AdaptiveBitrateConfig::default();
+        let mut controller = AdaptiveBitrateController::new(config);
+        let mut encoder = create_opus_encoder(
+            48000,
+            opus::Application::Voip,
+            opus::Bitrate::Max,
+            opus::Channels::Mono,
+        )
+        .unwrap();
+
+        let feedback = |packet_loss: f32, rtt_ms: u64| NetworkFeedback {
+            packet_loss,
+            rtt: Duration::from_millis(rtt_ms),
+            bandwidth_estimate: None,
+        };
+
+        //A clean network: the bitrate slowly climbs to the maximum
+        for _ in 0..100 {
+            controller
+                .update_encoder(&mut encoder, &feedback(0., 50))
+                .unwrap();
+        }
+
+        assert_eq!(controller.bitrate(), config.max_bitrate);
+        assert!(!controller.inband_fec());
+        assert_eq!(
+            encoder.bitrate(),
+            OpusBitrate::Bits(config.max_bitrate as i32)
+        );
+
+        //Congestion: the bitrate drops, FEC gets enabled
+        let mut adjustments = vec![];
+        for _ in 0..20 {
+            adjustments.push(
+                controller
+                    .update_encoder(&mut encoder, &feedback(0.2, 150))
+                    .unwrap(),
+            );
+        }
+
+        assert!(adjustments.contains(&BitrateAdjustment::Decreased));
+        assert_eq!(controller.bitrate(), config.min_bitrate);
+        assert!(controller.inband_fec());
+        assert!(encoder.inband_fec().unwrap());
+        assert!(encoder.packet_loss_perc().unwrap() >= 15);
+
+        //Loss between the thresholds: the bitrate is held and FEC stays on (hysteresis)
+        let mut held = AdaptiveBitrateController::new(config);
+        held.update(&feedback(0.05, 50));
+        let bitrate = held.bitrate();
+        for packet_loss in [0.05, 0.02, 0.06, 0.03, 0.05, 0.02, 0.04] {
+            for _ in 0..5 {
+                assert_eq!(
+                    held.update(&feedback(packet_loss, 50)),
+                    BitrateAdjustment::Held
+                );
+            }
+        }
+
+        assert_eq!(held.bitrate(), bitrate);
+        assert!(held.inband_fec());
+
+        //A high RTT alone counts as congestion
+        let mut delayed = AdaptiveBitrateController::new(config);
+        assert_eq!(
+            delayed.update(&feedback(0., 800)),
+            BitrateAdjustment::Decreased
+        );
+
+        //The bandwidth estimate caps the bitrate
+        let mut capped = AdaptiveBitrateController::new(config);
+        for _ in 0..100 {
+            capped.update(&NetworkFeedback {
+                packet_loss: 0.,
+                rtt: Duration::from_millis(50),
+                bandwidth_estimate: Some(20000),
+            });
+        }
+
+        assert_eq!(capped.bitrate(), 17000);
+    }
+
+    #[test]
+    fn opus_multistream_surround() {
+        let encoder = create_opus_multistream_encoder(
+            48000,
+            ChannelLayout::Surround5_1,
+            opus::Application::Audio,
+            OpusBitrate::Bits(256000),
+        )
+        .unwrap();
+
+        let mapping = encoder.mapping().clone();
+
+        assert_eq!(encoder.bitrate(), OpusBitrate::Bits(256000));
+        assert_eq!(mapping.family, 1);
+        assert_eq!(mapping.channels(), 6);
+        assert_eq!(mapping.streams, 4);
+        assert_eq!(mapping.coupled_streams, 2);
+
+        //A different tone on every channel
+        let samples: Vec<f32> = (0..48000 * 6)
+            .map(|idx| {
+                let (frame, channel) = (idx / 6, idx % 6);
+                (frame as f32 * (220.0 * (channel + 1) as f32) * 2.0 * std::f32::consts::PI
+                    / 48000.)
+                    .sin()
+                    * 0.5
+            })
+            .collect();
+
+        let sound_packets =
+            encode_samples_opus_multistream(encoder, &samples, FrameDuration::Ms20).unwrap();
+
+        assert_eq!(sound_packets[0].channels, 6);
+        assert_eq!(sound_packets[0].channel_mapping.as_ref(), Some(&mapping));
+
+        //The mapping survives an Ogg roundtrip
+        let mut writer = OggOpusWriter::new(
+            Vec::new(),
+            OpusHead::with_mapping(&mapping, 48000, 0),
+            vec![],
+        )
+        .unwrap();
+        for sound_packet in &sound_packets {
+            writer.write_packet(sound_packet).unwrap();
+        }
+        let file = writer.finish().unwrap();
+
+        let mut reader = OggOpusReader::new(file.as_slice()).unwrap();
+        assert_eq!(reader.head().mapping(), mapping);
+        let read_packets = reader.read_packets().unwrap();
+        assert_eq!(read_packets.len(), sound_packets.len());
+        assert_eq!(read_packets[0].channel_mapping.as_ref(), Some(&mapping));
+
+        let decoder = create_opus_multistream_decoder_for_packet(&read_packets[0]).unwrap();
+        let decoded = decode_samples_opus_multistream(decoder, read_packets).unwrap();
+
+        assert_eq!(decoded.len(), samples.len());
+        assert!(decoded.iter().any(|sample| sample.abs() > 0.1));
+
+        //7.1 and discrete layouts
+        let encoder = OpusMultistreamEncoder::new(
+            48000,
+            ChannelLayout::Surround7_1,
+            opus::Application::Audio,
+        )
+        .unwrap();
+        assert_eq!(encoder.mapping().streams, 5);
+        assert_eq!(encoder.mapping().coupled_streams, 3);
+
+        let encoder =
+            OpusMultistreamEncoder::new(48000, ChannelLayout::Discrete(4), opus::Application::Voip)
+                .unwrap();
+        assert_eq!(encoder.mapping().family, 255);
+        assert_eq!(encoder.mapping().streams, 4);
+        assert_eq!(encoder.mapping().coupled_streams, 0);
+
+        //Two stereo microphone pairs in coupled streams
+        let pairs = io::ChannelMapping {
+            family: 255,
+            streams: 2,
+            coupled_streams: 2,
+            mapping: vec![0, 1, 2, 3],
+        };
+        let encoder =
+            OpusMultistreamEncoder::with_mapping(48000, pairs.clone(), opus::Application::Audio)
+                .unwrap();
+        let sound_packets = encode_samples_opus_multistream(
+            encoder,
+            &samples[..960 * 4],
+            FrameDuration::Ms20,
+        )
+        .unwrap();
+        let decoder = OpusMultistreamDecoder::new(48000, pairs).unwrap();
+        let decoded = decode_samples_opus_multistream(decoder, sound_packets).unwrap();
+        assert_eq!(decoded.len(), 960 * 4);
+
+        //Invalid mappings
+        assert!(OpusMultistreamDecoder::new(
+            48000,
+            io::ChannelMapping {
+                family: 255,
+                streams: 1,
+                coupled_streams: 0,
+                mapping: vec![0, 3],
+            }
+        )
+        .is_err());
+        assert!(OpusMultistreamEncoder::new(
+            48000,
+            ChannelLayout::Surround(9),
+            opus::Application::Audio
+        )
+        .is_err());
+    }
+
+    #[cfg(feature = "g711")]
+    #[test]
+    fn g711_encoding_decoding() {
+        //Reference values of ITU-T G.711
+        assert_eq!(encode_mu_law(0), 0xFF);
+        assert_eq!(encode_a_law(0), 0xD5);
+        assert_eq!(decode_mu_law(0xFF), 0);
+        assert_eq!(decode_mu_law(0x80), 32124);
+        assert_eq!(decode_a_law(0xD5), 8);
+        assert_eq!(decode_a_law(0xAA), 32256);
+
+        //Every code word decodes into a value which encodes back into the same code word
+        for byte in 0..=255u8 {
+            //μ-law has a negative zero (0x7F), which encodes back into zero (0xFF)
+            let mu_law_byte = if byte == 0x7F { 0xFF } else { byte };
+
+            assert_eq!(encode_mu_law(decode_mu_law(byte)), mu_law_byte);
+            assert_eq!(encode_a_law(decode_a_law(byte)), byte);
+        }
+
+        let samples: Vec<f32> = (0..48000)
+            .map(|idx| (idx as f32 * 440.0 * 2.0 * std::f32::consts::PI / 48000.).sin() * 0.5)
+            .collect();
+
+        for law in [io::G711Law::MuLaw, io::G711Law::ALaw] {
+            let sound_packets =
+                encode_samples_g711(law, &samples, 48000, 1, Duration::from_millis(20)).unwrap();
+
+            //One second of audio at 8kHz, in 20 ms frames
+            assert_eq!(sound_packets.len(), 50);
+            assert!(sound_packets.iter().all(|sound_packet| {
+                sound_packet.sample_rate == 8000
+                    && sound_packet.bytes.len() == 160
+                    && sound_packet.encoder_type == io::EncoderType::G711(law)
+            }));
+
+            let decoded = decode_samples_g711(sound_packets).unwrap();
+            let expected = resample(&samples, 48000, 1, 8000, 1).unwrap();
+
+            assert_eq!(decoded.len(), 8000);
+            //The anti-aliasing filter spreads the onset of the tone into the first sample
+            for (decoded, expected) in decoded.iter().zip(&expected).skip(1) {
+                assert!((decoded - expected).abs() < 0.02);
+            }
+        }
+
+        //Opus packets are rejected by the G.711 decoder and the other way around
+        let encoder = create_opus_encoder(
+            48000,
+            opus::Application::Voip,
+            opus::Bitrate::Bits(32000),
+            opus::Channels::Mono,
+        )
+        .unwrap();
+        let opus_packets = encode_samples_opus(encoder, &samples[..960], FrameDuration::Ms20).unwrap();
+        assert!(decode_samples_g711(opus_packets).is_err());
+
+        let g711_packets =
+            encode_samples_g711(io::G711Law::ALaw, &samples, 48000, 1, Duration::from_millis(20))
+                .unwrap();
+        let decoder = create_opus_decoder(8000, Channels::Mono).unwrap();
+        assert!(decode_samples_opus(decoder, g711_packets).is_err());
+
+        //A 6kHz tone is above the 8kHz Nyquist frequency, it is filtered out instead of folding back to 2kHz
+        let high_samples: Vec<f32> = (0..48000)
+            .map(|idx| (idx as f32 * 6000.0 * 2.0 * std::f32::consts::PI / 48000.).sin() * 0.5)
+            .collect();
+        let high_packets = encode_samples_g711(
+            io::G711Law::MuLaw,
+            &high_samples,
+            48000,
+            1,
+            Duration::from_millis(20),
+        )
+        .unwrap();
+        let decoded = decode_samples_g711(high_packets).unwrap();
+
+        let energy = |samples: &[f32]| {
+            samples.iter().map(|sample| sample * sample).sum::<f32>() / samples.len() as f32
+        };
+        assert!(energy(&decoded) < energy(&high_samples) * 0.001);
+    }
+
+    #[cfg(all(feature = "g711", feature = "flac"))]
+    #[test]
+    fn mixed_codec_decoding() {
+        use crate::io::decode::{decode_samples, SoundPacketDecoder};
+
+        //A call which switches from opus to G.711 and then to FLAC, with a different format every time
+        let stereo_samples: Vec<f32> = (0..9600 * 2)
+            .map(|idx| ((idx / 2) as f32 * 440.0 * 2.0 * std::f32::consts::PI / 48000.).sin() * 0.5)
+            .collect();
+        let mono_samples: Vec<f32> = (0..9600)
+            .map(|idx| (idx as f32 * 440.0 * 2.0 * std::f32::consts::PI / 48000.).sin() * 0.5)
+            .collect();
+
+        let encoder = create_opus_encoder(
+            48000,
+            opus::Application::Audio,
+            opus::Bitrate::Bits(64000),
+            opus::Channels::Stereo,
+        )
+        .unwrap();
+        let mut sound_packets =
+            encode_samples_opus(encoder, &stereo_samples, FrameDuration::Ms20).unwrap();
+        sound_packets.extend(
+            encode_samples_g711(
+                io::G711Law::MuLaw,
+                &mono_samples,
+                48000,
+                1,
+                Duration::from_millis(20),
+            )
+            .unwrap(),
+        );
+        let flac_samples = resample(&mono_samples, 48000, 1, 16000, 1).unwrap();
+        sound_packets.extend(encode_samples_flac(&flac_samples, 16000, 1, 16).unwrap());
+
+        //Every part is 200 ms long, nothing is lost at the codec switches
+        let decoded = decode_samples(sound_packets.clone(), 48000, 1).unwrap();
+        assert_eq!(decoded.len(), 28800);
+        assert!(decoded.iter().all(|sample| sample.is_finite() && sample.abs() <= 1.));
+
+        //Every part contains the tone
+        for part in decoded.chunks(9600) {
+            assert!(part.iter().any(|sample| sample.abs() > 0.4));
+        }
+
+        //The packets are decoded one by one in the same way
+        let mut decoder = SoundPacketDecoder::new(48000, 1).unwrap();
+        let mut samples = vec![];
+        for sound_packet in sound_packets {
+            samples.extend(decoder.decode(sound_packet).unwrap());
+        }
+        decoder.flush(&mut samples);
+        assert_eq!(samples, decoded);
+
+        assert!(SoundPacketDecoder::new(0, 1).is_err());
+    }
+
+    #[cfg(feature = "flac")]
+    #[test]
+    fn flac_encoding_decoding() {
+        let samples: Vec<f32> = (0..48000 * 2)
+            .map(|idx| {
+                let time = (idx / 2) as f32 / 48000.;
+                let channel = (idx % 2) as f32;
+
+                (time * (440.0 + channel * 110.) * 2.0 * std::f32::consts::PI).sin() * 0.5
+            })
+            .collect();
+
+        //FLAC is lossless after quantizing to the bit depth
+        for bits_per_sample in [8, 16, 24] {
+            let scale = ((1i64 << (bits_per_sample - 1)) - 1) as f32;
+            let quantized: Vec<f32> = samples
+                .iter()
+                .map(|sample| (sample * scale).round() / scale)
+                .collect();
+
+            let sound_packets = encode_samples_flac(&samples, 48000, 2, bits_per_sample).unwrap();
+
+            //48000 samples per channel in blocks of 4096, the last block is shorter
+            assert_eq!(sound_packets.len(), 12);
+            assert_eq!(sound_packets.last().unwrap().samples_per_frame, 2944 * 2);
+
+            let encoded_size: usize = sound_packets.iter().map(|packet| packet.bytes.len()).sum();
+            assert!(encoded_size < samples.len() * bits_per_sample as usize / 8);
+
+            assert_eq!(decode_samples_flac(sound_packets).unwrap(), quantized);
+        }
+
+        //Feeding the encoder in pieces like io::record does
+        let mut encoder = FlacEncoder::with_block_size(44100, 1, 16, 1000).unwrap();
+        let mut sound_packets = vec![];
+
+        for chunk in samples[..4410].chunks(441) {
+            sound_packets.extend(encoder.push(chunk).unwrap());
+        }
+
+        assert_eq!(sound_packets.len(), 4);
+        assert_eq!(encoder.buffered_samples(), 410);
+        sound_packets.extend(encoder.flush().unwrap());
+        assert_eq!(sound_packets.len(), 5);
+
+        let decoded = decode_samples_flac(sound_packets.clone()).unwrap();
+        assert_eq!(decoded.len(), 4410);
+        for (decoded, sample) in decoded.iter().zip(&samples) {
+            assert!((decoded - sample).abs() < 0.0001);
+        }
+
+        //Silence is stored as constant subframes
+        let silence = encode_samples_flac(&[0.; 4096], 8000, 1, 16).unwrap();
+        assert!(silence[0].bytes.len() < 16);
+        assert_eq!(decode_samples_flac(silence).unwrap(), vec![0.; 4096]);
+
+        //Writing and reading a .flac stream
+        let mut file = vec![];
+        write_flac(&mut file, &sound_packets).unwrap();
+        assert!(file.starts_with(b"fLaC"));
+
+        let (stream_info, read_packets) = read_flac(&mut file.as_slice()).unwrap();
+        assert_eq!(
+            stream_info,
+            FlacStreamInfo::from_sound_packets(&sound_packets).unwrap()
+        );
+        assert_eq!(stream_info.sample_rate, 44100);
+        assert_eq!(stream_info.channels, 1);
+        assert_eq!(stream_info.bits_per_sample, 16);
+        assert_eq!(stream_info.min_block_size, 1000);
+        assert_eq!(stream_info.total_samples, 4410);
+        assert_eq!(read_packets.len(), sound_packets.len());
+        assert!(read_packets
+            .iter()
+            .zip(&sound_packets)
+            .all(|(read, written)| read.bytes == written.bytes
+                && read.samples_per_frame == written.samples_per_frame));
+        assert_eq!(decode_samples_flac(read_packets).unwrap(), decoded);
+
+        //Streams with an unsupported bit depth are rejected when reading the STREAMINFO
+        let mut unsupported_info = stream_info.clone();
+        unsupported_info.bits_per_sample = 32;
+        let mut unsupported_file = file.clone();
+        unsupported_file[8..8 + 34].copy_from_slice(&unsupported_info.to_bytes());
+        assert!(read_flac(&mut unsupported_file.as_slice()).is_err());
+
+        //Corrupted and non FLAC packets are rejected
+        let mut corrupted = sound_packets[0].clone();
+        let mut corrupted_bytes = corrupted.bytes.to_vec();
+        let last = corrupted_bytes.len() - 1;
+        corrupted_bytes[last] ^= 0xFF;
+        corrupted.bytes = corrupted_bytes.into();
+        assert!(decode_sample_set_size_flac(corrupted).is_err());
+
+        let encoder = create_opus_encoder(
+            48000,
+            opus::Application::Audio,
+            opus::Bitrate::Bits(64000),
+            opus::Channels::Stereo,
+        )
+        .unwrap();
+        let opus_packets =
+            encode_samples_opus(encoder, &samples[..1920], FrameDuration::Ms20).unwrap();
+        assert!(decode_samples_flac(opus_packets.clone()).is_err());
+        assert!(write_flac(&mut vec![], &opus_packets).is_err());
+        assert!(FlacEncoder::new(48000, 2, 15).is_err());
+
+        //Only full blocks can be encoded directly, the short last block is encoded by `flush`
+        let mut encoder = FlacEncoder::new(48000, 1, 16).unwrap();
+        let block = vec![0.; encoder.block_size()];
+        assert!(encoder.encode_block(&block).is_ok());
+        assert!(encoder.encode_block(&block[..1000]).is_err());
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn opus_parallel_batch_encoding() {
+        use crate::opus::batch::{encode_samples_opus_parallel, BatchEncodeConfig};
+
+        //10.01 seconds, so the last frame is padded
+        let samples: Vec<f32> = (0..480480)
+            .map(|idx| (idx as f32 * 440.0 * 2.0 * std::f32::consts::PI / 48000.).sin() * 0.5)
+            .collect();
+        let config = OpusEncoderConfig::music();
+
+        let encoder =
+            create_opus_encoder_with_config(48000, opus::Application::Audio, Channels::Mono, &config)
+                .unwrap();
+        let serial_packets = encode_samples_opus(encoder, &samples, FrameDuration::Ms20).unwrap();
+
+        let batch_config = BatchEncodeConfig {
+            segment_frames: 50,
+            warm_up_frames: 5,
+            threads: Some(4),
+        };
+        let parallel_packets = encode_samples_opus_parallel(
+            &samples,
+            48000,
+            opus::Application::Audio,
+            Channels::Mono,
+            &config,
+            FrameDuration::Ms20,
+            &batch_config,
+        )
+        .unwrap();
+
+        assert_eq!(parallel_packets.len(), serial_packets.len());
+        assert_eq!(parallel_packets.len(), 501);
+        assert!(parallel_packets
+            .iter()
+            .all(|sound_packet| sound_packet.samples_per_frame == 960));
+
+        //The segments are stitched without audible seams
+        let serial = decode_samples_opus(
+            create_opus_decoder(48000, Channels::Mono).unwrap(),
+            serial_packets,
+        )
+        .unwrap();
+        let parallel = decode_samples_opus(
+            create_opus_decoder(48000, Channels::Mono).unwrap(),
+            parallel_packets,
+        )
+        .unwrap();
+
+        assert_eq!(serial.len(), parallel.len());
+        let error = (serial
+            .iter()
+            .zip(&parallel)
+            .map(|(serial, parallel)| (serial - parallel).powi(2))
+            .sum::<f32>()
+            / serial.len() as f32)
+            .sqrt();
+        assert!(error < 0.05, "{error}");
+
+        //Every segment is encoded, even if there are less segments than threads
+        let short_packets = encode_samples_opus_parallel(
+            &samples[..960 * 3],
+            48000,
+            opus::Application::Audio,
+            Channels::Mono,
+            &config,
+            FrameDuration::Ms20,
+            &BatchEncodeConfig::default(),
+        )
+        .unwrap();
+        assert_eq!(short_packets.len(), 3);
+
+        assert!(encode_samples_opus_parallel(
+            &samples,
+            48000,
+            opus::Application::Audio,
+            Channels::Mono,
+            &config,
+            FrameDuration::Ms20,
+            &BatchEncodeConfig {
+                segment_frames: 0,
+                ..Default::default()
+            },
+        )
+        .is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn sound_packet_serde_compatibility() {
+        use serde::{de::value::SeqDeserializer, Deserialize};
+
+        //0.1.x serialized the payload (`Vec<u8>`) as a sequence, which is still accepted
+        let deserializer =
+            SeqDeserializer::<_, serde::de::value::Error>::new(vec![1u8, 2, 3].into_iter());
+        let payload = bytes::Bytes::deserialize(deserializer).unwrap();
+
+        assert_eq!(payload, bytes::Bytes::from_static(&[1, 2, 3]));
+    }
+
+    #[test]
+    fn opus_buffer_reuse() {
+        let samples: Vec<f32> = (0..48000)
+            .map(|idx| (idx as f32 * 440.0 * 2.0 * std::f32::consts::PI / 48000.).sin() * 0.5)
+            .collect();
+        let create_encoder = || {
+            create_opus_encoder(
+                48000,
+                opus::Application::Voip,
+                opus::Bitrate::Bits(32000),
+                opus::Channels::Mono,
+            )
+            .unwrap()
+        };
+
+        //Encoding into a shared buffer creates the same packets
+        let mut pooled_encoder = create_encoder();
+        let mut encoder = create_encoder();
+        let mut buffer = bytes::BytesMut::new();
+        let mut sound_packets = vec![];
+
+        for frame in samples.chunks_exact(960) {
+            let pooled_packet = encode_sample_set_size_opus_into(
+                &mut pooled_encoder,
+                frame,
+                FrameDuration::Ms20,
+                &mut buffer,
+            )
+            .unwrap();
+            let sound_packet =
+                encode_sample_set_size_opus(&mut encoder, frame, FrameDuration::Ms20).unwrap();
+
+            assert_eq!(pooled_packet.bytes, sound_packet.bytes);
+            sound_packets.push(pooled_packet);
+        }
+
+        //Packets are forwarded without copying their payload
+        let forwarded = sound_packets[0].clone();
+        assert_eq!(forwarded.bytes.as_ptr(), sound_packets[0].bytes.as_ptr());
+        assert_eq!(
+            forwarded.deep_size_of(),
+            std::mem::size_of::<io::SoundPacket>() + forwarded.bytes.len()
+        );
+
+        //The output buffer is only allocated once
+        let mut decoder = create_opus_decoder(48000, Channels::Mono).unwrap();
+        let mut output = Vec::with_capacity(960);
+        let output_ptr = output.as_ptr();
+
+        for sound_packet in &sound_packets {
+            output.clear();
+
+            let decoded_samples =
+                decode_sample_set_size_opus_into(&mut decoder, sound_packet, true, &mut output)
+                    .unwrap();
+
+            assert_eq!(decoded_samples, 960);
+            assert_eq!(output.len(), 960);
+        }
+        assert_eq!(output.as_ptr(), output_ptr);
+
+        //The samples are appended to the buffer, a failed decode leaves it untouched
+        let mut corrupted = sound_packets[1].clone();
+        corrupted.samples_per_frame = 961;
+        assert!(
+            decode_sample_set_size_opus_into(&mut decoder, &corrupted, true, &mut output).is_err()
+        );
+        assert_eq!(output.len(), 960);
+
+        //Multistream packets are split off from a shared buffer too, they only hold the encoded bytes
+        let stereo_samples: Vec<f32> = samples.iter().flat_map(|sample| [*sample; 2]).collect();
+        let pairs = io::ChannelMapping {
+            family: 255,
+            streams: 2,
+            coupled_streams: 1,
+            mapping: vec![0, 1, 2],
+        };
+        let mut encoder =
+            OpusMultistreamEncoder::with_mapping(48000, pairs.clone(), opus::Application::Audio)
+                .unwrap();
+        let surround_samples: Vec<f32> = stereo_samples
+            .chunks_exact(2)
+            .flat_map(|frame| [frame[0], frame[1], frame[0]])
+            .collect();
+        let mut buffer = bytes::BytesMut::new();
+        let sound_packets: Vec<io::SoundPacket> = surround_samples
+            .chunks_exact(960 * 3)
+            .map(|frame| {
+                encode_sample_set_size_opus_multistream_into(
+                    &mut encoder,
+                    frame,
+                    FrameDuration::Ms20,
+                    &mut buffer,
+                )
+                .unwrap()
+            })
+            .collect();
+
+        assert_eq!(
+            sound_packets[1].bytes.as_ptr(),
+            sound_packets[0].bytes[sound_packets[0].bytes.len()..].as_ptr()
+        );
+
+        let mut decoder = OpusMultistreamDecoder::new(48000, pairs).unwrap();
+        let mut output = Vec::with_capacity(960 * 3);
+        let output_ptr = output.as_ptr();
+
+        for sound_packet in &sound_packets {
+            output.clear();
+
+            let decoded_samples = decode_sample_set_size_opus_multistream_into(
+                &mut decoder,
+                sound_packet,
+                false,
+                &mut output,
+            )
+            .unwrap();
+
+            assert_eq!(decoded_samples, 960 * 3);
+        }
+        assert_eq!(output.as_ptr(), output_ptr);
+    }
+
+    #[test]
+    fn av1_video_encoding() {
+        let (width, height) = (64, 48);
+        //A gradient moving to the right
+        let frame = |idx: usize| {
+            (0..width * height)
+                .flat_map(|pixel| {
+                    let x = (pixel % width + idx * 2) as u8;
+                    let y = (pixel / width) as u8;
+
+                    [x.wrapping_mul(4), y.wrapping_mul(4), 128]
+                })
+                .collect::<Vec<u8>>()
+        };
+
+        let mut encoder = Av1VideoEncoder::new(VideoEncoderConfig {
+            width,
+            height,
+            keyframe_interval: 10,
+            bitrate: Some(100_000),
+            speed: 10,
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(!encoder.sequence_header().is_empty());
+
+        let mut encoded_frames = vec![];
+
+        for idx in 0..25 {
+            if idx == 15 {
+                encoder.force_keyframe();
+            }
+
+            let packets = encoder.encode_frame(&frame(idx), width, height).unwrap();
+
+            //Low latency mode returns a frame for every frame after the lookahead
+            assert!(packets.len() <= 1);
+            encoded_frames.extend(packets);
+        }
+        encoded_frames.extend(encoder.flush().unwrap());
+
+        assert_eq!(encoded_frames.len(), 25);
+        assert!(encoded_frames
+            .iter()
+            .enumerate()
+            .all(|(idx, encoded_frame)| encoded_frame.frame_number == idx as u64));
+
+        let keyframes: Vec<u64> = encoded_frames
+            .iter()
+            .filter(|encoded_frame| encoded_frame.keyframe)
+            .map(|encoded_frame| encoded_frame.frame_number)
+            .collect();
+        assert_eq!(keyframes, vec![0, 10, 15]);
+
+        //Inter frames are predicted from the previous frames
+        let inter_frame_size = encoded_frames[5].data.len();
+        assert!(inter_frame_size < encoded_frames[0].data.len());
+
+        assert!(encoder.encode_frame(&frame(0), 32, 32).is_err());
+        assert!(Av1VideoEncoder::new(VideoEncoderConfig {
+            frame_rate: 0,
+            ..Default::default()
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn avif_container_parsing() {
+        let (width, height) = (24, 16);
+        let pixels = (0..width * height)
+            .map(|pixel| ravif::RGBA8::new((pixel % 256) as u8, 64, 192, (pixel % 3 * 100) as u8))
+            .collect::<Vec<ravif::RGBA8>>();
+
+        let encoder = ravif::Encoder::new().with_speed(10);
+
+        let opaque = encoder
+            .encode_rgb(ravif::Img::new(
+                pixels
+                    .iter()
+                    .map(|pixel| pixel.rgb())
+                    .collect::<Vec<ravif::RGB8>>()
+                    .as_slice(),
+                width,
+                height,
+            ))
+            .unwrap();
+        let avif = parse_avif(&opaque.avif_file).unwrap();
+
+        assert_eq!((avif.width, avif.height), (width as u32, height as u32));
+        assert!(!avif.color_data.is_empty());
+        assert_eq!(avif.alpha_data, None);
+        assert_eq!(avif.matrix_coefficients, MATRIX_BT601);
+        assert!(avif.full_range);
+
+        let transparent = encoder
+            .encode_rgba(ravif::Img::new(pixels.as_slice(), width, height))
+            .unwrap();
+        let avif = parse_avif(&transparent.avif_file).unwrap();
+
+        assert!(avif.alpha_data.is_some_and(|alpha_data| !alpha_data.is_empty()));
+        assert!(!avif.premultiplied_alpha);
+
+        assert!(parse_avif(&transparent.avif_file[..100]).is_err());
+        assert!(parse_avif(b"not an avif file").is_err());
+    }
+
+    #[test]
+    fn yuv_to_rgb_conversion() {
+        let (width, height) = (6, 4);
+        //Every 2x2 block has the same color, so the chroma subsampling is lossless
+        let image = (0..width * height)
+            .flat_map(|pixel| {
+                let block = (pixel % width / 2 + pixel / width / 2 * 3) as u8;
+
+                [block * 40, 255 - block * 30, block * 20 + 10]
+            })
+            .collect::<Vec<u8>>();
+
+        let [y_plane, u_plane, v_plane] = rgb_to_yuv420(&image, width, height);
+
+        let mut frame = YuvFrame {
+            planes: [&y_plane, &u_plane, &v_plane],
+            strides: [width, width / 2, width / 2],
+            width,
+            height,
+            bit_depth: 8,
+            subsampling: ChromaSubsampling::Cs420,
+            matrix: YuvMatrix::Bt601,
+            full_range: true,
+        };
+
+        let decoded = yuv_to_rgb8(&frame).unwrap();
+
+        assert_eq!(decoded.len(), image.len());
+        assert!(decoded
+            .iter()
+            .zip(&image)
+            .all(|(decoded, original)| decoded.abs_diff(*original) <= 2));
+
+        //Limited range 10 bit gray
+        let gray = [(16u16 << 2), (235 << 2), (126 << 2)]
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect::<Vec<u8>>();
+        frame = YuvFrame {
+            planes: [&gray, &[], &[]],
+            strides: [6, 0, 0],
+            width: 3,
+            height: 1,
+            bit_depth: 10,
+            subsampling: ChromaSubsampling::Monochrome,
+            matrix: YuvMatrix::from_cicp(1),
+            full_range: false,
+        };
+
+        assert_eq!(
+            yuv_to_rgb8(&frame).unwrap(),
+            vec![0, 0, 0, 255, 255, 255, 128, 128, 128]
+        );
+
+        frame.strides[0] = 4;
+        assert!(yuv_to_rgb8(&frame).is_err());
+    }
+
+    #[test]
+    fn avif_alpha_grayscale_10_bit_encoding() {
+        let (width, height) = (16, 16);
+        let config = AvifEncoderConfig {
+            speed: 10,
+            alpha_quality: 50.,
+            ..Default::default()
+        };
+
+        let rgba = (0..width * height)
+            .flat_map(|pixel| [pixel as u8, 100, 200, (pixel % width * 16) as u8])
+            .collect::<Vec<u8>>();
+        let encoded =
+            encode_raw_image_rgba(config.to_encoder().unwrap(), &rgba, width, height).unwrap();
+        assert_ne!(encoded.alpha_byte_size, 0);
+        assert!(parse_avif(&encoded.avif_file).unwrap().alpha_data.is_some());
+
+        //Fully opaque images do not get an alpha channel
+        let opaque = rgba
+            .chunks_exact(4)
+            .flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 255])
+            .collect::<Vec<u8>>();
+        let encoded =
+            encode_raw_image_rgba(config.to_encoder().unwrap(), &opaque, width, height).unwrap();
+        assert!(parse_avif(&encoded.avif_file).unwrap().alpha_data.is_none());
+
+        //Formatted images keep their alpha
+        let mut png = std::io::Cursor::new(vec![]);
+        image::RgbaImage::from_raw(width as u32, height as u32, rgba.clone())
+            .unwrap()
+            .write_to(&mut png, image::ImageFormat::Png)
+            .unwrap();
+        let encoded = encode_image(config.to_encoder().unwrap(), png.get_ref()).unwrap();
+        assert!(parse_avif(&encoded.avif_file).unwrap().alpha_data.is_some());
+
+        let gray = (0..width * height).map(|pixel| pixel as u8).collect::<Vec<u8>>();
+        let encoded =
+            encode_raw_image_grayscale(config.to_encoder().unwrap(), &gray, width, height).unwrap();
+        assert!(parse_avif(&encoded.avif_file).is_ok());
+        assert!(encode_raw_image_grayscale(config.to_encoder().unwrap(), &gray, width, 8).is_err());
+
+        let rgb10 = (0..width * height)
+            .flat_map(|pixel| [(pixel * 4) as u16, 1023, 0])
+            .collect::<Vec<u16>>();
+        for color_space in [AvifColorSpace::YCbCr, AvifColorSpace::Rgb] {
+            let encoded = encode_raw_image_10_bit(
+                config.to_encoder().unwrap(),
+                &rgb10,
+                width,
+                height,
+                color_space,
+            )
+            .unwrap();
+            let avif = parse_avif(&encoded.avif_file).unwrap();
+
+            assert_eq!(avif.matrix_coefficients == 0, color_space == AvifColorSpace::Rgb);
+        }
+
+        //The transparent half has noisy colors, which are cleared by the premultiplication
+        let rgba10 = (0..width * height)
+            .flat_map(|pixel| {
+                let noise = (pixel * 7919 % 1024) as u16;
+                let alpha = if pixel % width < width / 2 { 0 } else { 1023 };
+
+                [noise, 1023 - noise, (pixel * 4) as u16, alpha]
+            })
+            .collect::<Vec<u16>>();
+        let cleared = rgba10
+            .chunks_exact(4)
+            .flat_map(|pixel| match pixel[3] {
+                0 => [0, 0, 0, 0],
+                _ => [pixel[0], pixel[1], pixel[2], pixel[3]],
+            })
+            .collect::<Vec<u16>>();
+        let premultiplied_config = AvifEncoderConfig {
+            premultiplied_alpha: true,
+            ..config
+        };
+
+        let straight = encode_raw_image_rgba_10_bit(&config, &rgba10, width, height).unwrap();
+        assert!(!parse_avif(&straight.avif_file).unwrap().premultiplied_alpha);
+        assert_ne!(
+            straight.avif_file,
+            encode_raw_image_rgba_10_bit(&config, &cleared, width, height)
+                .unwrap()
+                .avif_file
+        );
+
+        //Premultiplying the already cleared pixels does not change them
+        let premultiplied =
+            encode_raw_image_rgba_10_bit(&premultiplied_config, &rgba10, width, height).unwrap();
+        assert!(parse_avif(&premultiplied.avif_file).unwrap().premultiplied_alpha);
+        assert_eq!(
+            premultiplied.avif_file,
+            encode_raw_image_rgba_10_bit(&premultiplied_config, &cleared, width, height)
+                .unwrap()
+                .avif_file
+        );
+
+        let mut out_of_range = rgb10.clone();
+        out_of_range[5] = 1024;
+        assert!(encode_raw_image_10_bit(
+            config.to_encoder().unwrap(),
+            &out_of_range,
+            width,
+            height,
+            AvifColorSpace::YCbCr
+        )
+        .is_err());
+
+        assert!(AvifEncoderConfig {
+            quality: 0.,
+            ..Default::default()
+        }
+        .to_encoder()
+        .is_err());
+        assert!(AvifEncoderConfig {
+            bit_depth: Some(12),
+            ..Default::default()
+        }
+        .to_encoder()
+        .is_err());
+    }
+
+    #[test]
+    fn frame_scaling() {
+        let (width, height) = (64, 36);
+        let frame = (0..width * height)
+            .flat_map(|pixel| [(pixel % width) as u8, (pixel / width) as u8, 0])
+            .collect::<Vec<u8>>();
+
+        //Fitting a 16:9 frame into 4:3 keeps the aspect ratio
+        let (fitted, fit_width, fit_height) =
+            scale_raw_image(&frame, width, height, &ScaleConfig::new(32, 32)).unwrap();
+        assert_eq!((fit_width, fit_height), (32, 18));
+        assert_eq!(fitted.len(), 32 * 18 * 3);
+
+        for filter in [
+            ScaleFilter::Nearest,
+            ScaleFilter::Bilinear,
+            ScaleFilter::CatmullRom,
+            ScaleFilter::Lanczos3,
+        ] {
+            let config = ScaleConfig {
+                mode: ScaleMode::Fill,
+                filter,
+                ..ScaleConfig::new(32, 32)
+            };
+            let (filled, fill_width, fill_height) =
+                scale_raw_image(&frame, width, height, &config).unwrap();
+
+            assert_eq!((fill_width, fill_height), (32, 32));
+            assert_eq!(filled.len(), 32 * 32 * 3);
+        }
+
+        let stretch = ScaleConfig {
+            mode: ScaleMode::Stretch,
+            ..ScaleConfig::new(10, 30)
+        };
+        assert_eq!(stretch.output_size(width, height), (10, 30));
+
+        let crop = CropRect {
+            x: 10,
+            y: 5,
+            width: 20,
+            height: 10,
+        };
+        let cropped = crop_raw_image(&frame, width, height, crop).unwrap();
+        assert_eq!(cropped.len(), 20 * 10 * 3);
+        assert_eq!(&cropped[..3], &[10, 5, 0]);
+        assert_eq!(&cropped[cropped.len() - 3..], &[29, 14, 0]);
+
+        //Cropping to the target size does not resample the frame
+        let config = ScaleConfig {
+            crop: Some(crop),
+            ..ScaleConfig::new(20, 10)
+        };
+        assert_eq!(
+            scale_raw_image(&frame, width, height, &config).unwrap(),
+            (cropped, 20, 10)
+        );
+
+        assert!(crop_raw_image(
+            &frame,
+            width,
+            height,
+            CropRect {
+                x: 60,
+                ..crop
+            }
+        )
+        .is_err());
+        assert!(scale_raw_image(&frame, width, height + 1, &ScaleConfig::p360()).is_err());
+        assert!(scale_raw_image(&frame, width, height, &ScaleConfig::new(0, 10)).is_err());
+    }
+
+    #[test]
+    fn avif_byte_budget_encoding() {
+        let (width, height) = (64, 48);
+        //A gradient with some noise, so the size depends on the quality
+        let frame = |seed: u32| {
+            (0..(width * height * 3) as u32)
+                .map(|idx| {
+                    let noise = idx.wrapping_add(seed).wrapping_mul(2654435761) >> 28;
+
+                    ((idx / 3 % width as u32) * 3 + noise) as u8
+                })
+                .collect::<Vec<u8>>()
+        };
+
+        let unlimited = encode_raw_image_with_budget(
+            AvifEncoderConfig::default(),
+            &frame(0),
+            width,
+            height,
+            usize::MAX,
+        )
+        .unwrap();
+        assert!(unlimited.quality >= 98.);
+
+        let mut encoder = BudgetEncoder::new(
+            AvifEncoderConfig::default(),
+            ByteBudgetConfig {
+                quality_tolerance: 4.,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let max_bytes = unlimited.image.avif_file.len() / 2;
+        let first = encoder
+            .encode_raw_image(&frame(0), width, height, max_bytes)
+            .unwrap();
+        assert!(first.image.avif_file.len() <= max_bytes);
+        assert!(first.quality < unlimited.quality);
+        assert_eq!(encoder.last_quality(), Some(first.quality));
+
+        //The next similar frame starts from the cached quality
+        let second = encoder
+            .encode_raw_image(&frame(1), width, height, max_bytes)
+            .unwrap();
+        assert!(second.image.avif_file.len() <= max_bytes);
+        assert!(second.attempts <= first.attempts + 1);
+        assert!(first.attempts <= 8 && second.attempts <= 8);
+
+        let rgba = frame(2)
+            .chunks_exact(3)
+            .flat_map(|pixel| [pixel[0], pixel[1], pixel[2], pixel[0]])
+            .collect::<Vec<u8>>();
+        let transparent = encoder
+            .encode_raw_image_rgba(&rgba, width, height, max_bytes)
+            .unwrap();
+        assert!(transparent.image.avif_file.len() <= max_bytes);
+
+        encoder.reset();
+        assert_eq!(encoder.last_quality(), None);
+
+        //Every encoding counts against the maximum count of attempts
+        let mut capped_encoder = BudgetEncoder::new(
+            AvifEncoderConfig::default(),
+            ByteBudgetConfig {
+                max_attempts: 3,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        for seed in 0..2 {
+            let capped = capped_encoder
+                .encode_raw_image(&frame(seed), width, height, max_bytes)
+                .unwrap();
+            assert!(capped.image.avif_file.len() <= max_bytes);
+            assert!(capped.attempts <= 3);
+        }
+
+        assert!(encoder
+            .encode_raw_image(&frame(0), width, height, 10)
+            .is_err());
+        assert!(BudgetEncoder::new(
+            AvifEncoderConfig::default(),
+            ByteBudgetConfig {
+                min_quality: 90.,
+                max_quality: 10.,
+                ..Default::default()
+            }
+        )
+        .is_err());
+        assert!(BudgetEncoder::new(
+            AvifEncoderConfig::default(),
+            ByteBudgetConfig {
+                max_attempts: 0,
+                ..Default::default()
+            }
+        )
+        .is_err());
+    }
+
+    #[cfg(feature = "av1-decode")]
+    #[test]
+    fn avif_decoding_roundtrip() {
+        use crate::avif::decoding::{decode_avif_rgb8, PixelFormat};
+
+        let (width, height) = (48, 32);
+        let image = (0..width * height)
+            .flat_map(|pixel| {
+                let (x, y) = (pixel % width, pixel / width);
+
+                [(x * 5) as u8, (y * 7) as u8, 128]
+            })
+            .collect::<Vec<u8>>();
+
+        let encoded = encode_raw_image(
+            ravif::Encoder::new().with_quality(95.).with_speed(10),
+            &image,
+            width,
+            height,
+        )
+        .unwrap();
+        let decoded = decode_avif_rgb8(&encoded.avif_file).unwrap();
+
+        assert_eq!((decoded.width, decoded.height), (width, height));
+        assert_eq!(decoded.format, PixelFormat::Rgb8);
+        assert_eq!(decoded.pixels.len(), image.len());
+
+        let max_difference = image
+            .iter()
+            .zip(&decoded.pixels)
+            .map(|(original, decoded)| original.abs_diff(*decoded))
+            .max()
+            .unwrap();
+        assert!(dbg!(max_difference) <= 12);
+    }
+
+    #[cfg(feature = "av1-decode")]
+    #[test]
+    fn av1_video_decoding_roundtrip() {
+        use crate::avif::decoding::Av1VideoDecoder;
+
+        let (width, height) = (64, 48);
+        let frame = |idx: usize| {
+            (0..width * height)
+                .flat_map(|pixel| {
+                    let x = (pixel % width + idx * 2) as u8;
+                    let y = (pixel / width) as u8;
+
+                    [x.wrapping_mul(2), y.wrapping_mul(4), 128]
+                })
+                .collect::<Vec<u8>>()
+        };
+
+        let mut encoder = Av1VideoEncoder::new(VideoEncoderConfig {
+            width,
+            height,
+            bitrate: None,
+            quantizer: 40,
+            speed: 10,
+            ..Default::default()
+        })
+        .unwrap();
+
+        let mut encoded_frames = vec![];
+
+        for idx in 0..10 {
+            encoded_frames.extend(encoder.encode_frame(&frame(idx), width, height).unwrap());
+        }
+        encoded_frames.extend(encoder.flush().unwrap());
+
+        let mut decoder = Av1VideoDecoder::new().unwrap();
+        let mut decoded_frames = vec![];
+
+        for encoded_frame in &encoded_frames {
+            decoded_frames.extend(decoder.decode_frame(encoded_frame).unwrap());
+        }
+
+        assert_eq!(decoded_frames.len(), 10);
+        assert_eq!(decoder.frame_count(), 10);
+
+        for (idx, decoded) in decoded_frames.iter().enumerate() {
+            assert_eq!((decoded.width, decoded.height), (width, height));
+
+            //The average difference, as the edges of the gradient wrap around
+            let difference = frame(idx)
+                .iter()
+                .zip(&decoded.pixels)
+                .map(|(original, decoded)| original.abs_diff(*decoded) as usize)
+                .sum::<usize>()
+                / decoded.pixels.len();
+            assert!(dbg!(difference) <= 8);
+        }
+    }
+
+    #[test]
+    fn webcam_fourcc() {
+        use crate::cam::properties::FourCc;
+
+        //OpenCV packs the first character into the lowest byte
+        assert_eq!(FourCc::from_code(0x4750_4A4D), FourCc::MJPG);
+        assert_eq!(FourCc::MJPG.code(), 0x4750_4A4D);
+
+        for fourcc in [FourCc::MJPG, FourCc::YUYV, FourCc::NV12, FourCc::H264] {
+            assert_eq!(FourCc::from_code(fourcc.code()), fourcc);
+        }
+
+        assert_eq!(FourCc::YUYV.to_string(), "YUYV");
+        assert_eq!(FourCc([b'H', b'2', 0xFF, b'4']).to_string(), "H2\u{FFFD}4");
+    }
+
+    #[test]
+    fn webcam_resolution() {
+        let mut webcam = cam::Webcam::new_def(0).unwrap();
+        let (width, height) = (webcam.width().unwrap(), webcam.height().unwrap());
+        assert!(width != 0 && height != 0);
+
+        //The current resolution is always supported
+        webcam.set_resolution(width, height).unwrap();
+        let (_, size) = webcam.get_frame().unwrap();
+        assert_eq!((size.width as u32, size.height as u32), (width, height));
+
+        //No camera delivers 1x1 frames, so the request is reported as not applied
+        assert!(webcam.set_resolution(1, 1).is_err());
+    }
+
+    #[test]
+    fn webcam_listing() {
+        let cameras = cam::devices::list_cameras_def();
+        assert!(!dbg!(&cameras).is_empty());
+
+        for camera in &cameras {
+            assert!(!camera.backend.is_empty());
+            assert!(!camera.resolutions.is_empty());
+            assert!(camera
+                .resolutions
+                .windows(2)
+                .all(|pair| pair[0].0 * pair[0].1 <= pair[1].0 * pair[1].1));
+        }
+
+        //The cameras are released after probing, so they can be opened again
+        let mut webcam = cam::Webcam::new_def(cameras[0].index).unwrap();
+        let resolutions = webcam
+            .supported_resolutions(&cam::devices::COMMON_RESOLUTIONS)
+            .unwrap();
+        assert!(resolutions.contains(&(webcam.width().unwrap(), webcam.height().unwrap())));
+    }
+
+    #[test]
+    fn webcam_stream() {
+        let webcam = cam::Webcam::new_def(0).unwrap();
+        let stream = cam::stream::WebcamStream::new(webcam).unwrap();
+        let receiver = stream.subscribe();
+
+        //Wait for the camera to start delivering frames
+        sleep(Duration::from_secs(2));
+        assert!(stream.is_running());
+
+        let first = stream.latest_frame().unwrap();
+        assert_eq!(
+            first.image.len(),
+            (first.size.width * first.size.height * 3) as usize
+        );
+
+        sleep(Duration::from_millis(500));
+
+        let second = stream.latest_frame().unwrap();
+        assert!(second.frame_number > first.frame_number);
+        assert!(receiver.has_changed().unwrap());
+
+        let (mut webcam, result) = stream.stop().unwrap();
+        result.unwrap();
+
+        //The receivers are notified that the stream has ended
+        assert!(receiver.has_changed().is_err());
+
+        //The webcam is usable after the stream stopped
+        assert!(!webcam.get_frame().unwrap().0.is_empty());
+    }
 }